@@ -23,7 +23,7 @@ extern crate time;
 use std::str;
 use docopt::Docopt;
 use git2::{Repository, Signature, Commit, ObjectType, Time, DiffOptions};
-use git2::{Pathspec, Diff, Error, DiffFormat};
+use git2::{Diff, Error, DiffFormat, LogFilter};
 
 #[deriving(RustcDecodable)]
 struct Args {
@@ -86,45 +86,27 @@ fn run(args: &Args) -> Result<(), Error> {
         try!(revwalk.push_head());
     }
 
-    // Prepare our diff options and pathspec matcher
-    let (mut diffopts, mut diffopts2) = (DiffOptions::new(), DiffOptions::new());
+    // Prepare our diff options
+    let mut diffopts = DiffOptions::new();
     for spec in args.arg_spec.iter() {
         diffopts.pathspec(spec);
-        diffopts2.pathspec(spec);
     }
-    let ps = try!(Pathspec::new(args.arg_spec.iter()));
 
     // Filter our revwalk based on the CLI parameters
-    macro_rules! filter_try {
-        ($e:expr) => (match $e { Ok(t) => t, Err(e) => return Some(Err(e)) })
+    let mut filter = LogFilter::new(&repo, revwalk);
+    for spec in args.arg_spec.iter() {
+        filter.path(spec.as_slice());
     }
-    let mut revwalk = revwalk.filter_map(|id| {
-        let commit = filter_try!(repo.find_commit(id));
+    let revwalk = filter.filter(|commit| {
+        let commit = match *commit { Ok(ref c) => c, Err(..) => return true };
         let parents = commit.parents().len();
-        if parents < args.min_parents() { return None }
+        if parents < args.min_parents() { return false }
         if let Some(n) = args.max_parents() {
-            if parents >= n { return None }
-        }
-        if args.arg_spec.len() > 0 {
-            match commit.parents().len() {
-                0 => {
-                    let tree = filter_try!(commit.tree());
-                    let flags = git2::PATHSPEC_NO_MATCH_ERROR;
-                    if ps.match_tree(&tree, flags).is_err() { return None }
-                }
-                _ => {
-                    let m = commit.parents().all(|parent| {
-                        match_with_parent(&repo, &commit, &parent, &mut diffopts)
-                                        .unwrap_or(false)
-                    });
-                    if !m { return None }
-                }
-            }
+            if parents >= n { return false }
         }
-        if !sig_matches(commit.author(), &args.flag_author) { return None }
-        if !sig_matches(commit.committer(), &args.flag_committer) { return None }
-        if !log_message_matches(commit.message(), &args.flag_grep) { return None }
-        Some(Ok(commit))
+        if !sig_matches(commit.author(), &args.flag_author) { return false }
+        if !sig_matches(commit.committer(), &args.flag_committer) { return false }
+        log_message_matches(commit.message(), &args.flag_grep)
     }).skip(args.flag_skip.unwrap_or(0)).take(args.flag_max_count.unwrap_or(-1));
 
     // print!
@@ -140,7 +122,7 @@ fn run(args: &Args) -> Result<(), Error> {
         };
         let b = try!(commit.tree());
         let diff = try!(Diff::tree_to_tree(&repo, a.as_ref(), Some(&b),
-                                           Some(&mut diffopts2)));
+                                           Some(&mut diffopts)));
         try!(diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
             match line.origin() {
                 ' ' | '+' | '-' => print!("{}", line.origin()),
@@ -211,14 +193,6 @@ fn print_time(time: &Time, prefix: &str) {
 
 }
 
-fn match_with_parent(repo: &Repository, commit: &Commit, parent: &Commit,
-                     opts: &mut DiffOptions) -> Result<bool, Error> {
-    let a = try!(parent.tree());
-    let b = try!(commit.tree());
-    let diff = try!(Diff::tree_to_tree(repo, Some(&a), Some(&b), Some(opts)));
-    Ok(diff.deltas().len() > 0)
-}
-
 impl Args {
     fn min_parents(&self) -> uint {
         if self.flag_no_min_parents { return 0 }
@@ -22,11 +22,13 @@ pub use git_treewalk_mode::*;
 pub use git_config_level_t::*;
 pub use git_submodule_update_t::*;
 pub use git_submodule_ignore_t::*;
+pub use git_submodule_recurse_t::*;
 pub use git_credtype_t::*;
 pub use git_repository_init_flag_t::*;
 pub use git_repository_init_mode_t::*;
 pub use git_index_add_option_t::*;
 pub use git_cert_t::*;
+pub use git_remote_autotag_option_t::*;
 pub use git_status_t::*;
 pub use git_status_opt_t::*;
 pub use git_status_show_t::*;
@@ -43,12 +45,15 @@ pub const GIT_CLONE_OPTIONS_VERSION: c_uint = 1;
 pub const GIT_CHECKOUT_OPTIONS_VERSION: c_uint = 1;
 pub const GIT_REMOTE_CALLBACKS_VERSION: c_uint = 1;
 pub const GIT_STATUS_OPTIONS_VERSION: c_uint = 1;
+pub const GIT_DIFF_FIND_OPTIONS_VERSION: c_uint = 1;
 
 pub enum git_blob {}
 pub enum git_branch_iterator {}
 pub enum git_commit {}
 pub enum git_config {}
 pub enum git_config_iterator {}
+pub enum git_filter_list {}
+pub enum git_filter_source {}
 pub enum git_index {}
 pub enum git_object {}
 pub enum git_reference {}
@@ -69,6 +74,8 @@ pub enum git_pathspec {}
 pub enum git_pathspec_match_list {}
 pub enum git_diff {}
 pub enum git_diff_stats {}
+pub enum git_odb {}
+pub enum git_odb_object {}
 
 #[repr(C)]
 pub struct git_revspec {
@@ -95,6 +102,12 @@ pub struct git_strarray {
     pub count: size_t,
 }
 
+#[repr(C)]
+pub struct git_oidarray {
+    pub ids: *mut git_oid,
+    pub count: size_t,
+}
+
 #[repr(C)]
 pub struct git_signature {
     pub name: *mut c_char,
@@ -207,6 +220,8 @@ pub struct git_checkout_options {
     pub their_label: *const c_char,
 }
 
+pub type git_revwalk_hide_cb = extern fn(*const git_oid, *mut c_void) -> c_int;
+
 pub type git_checkout_notify_cb = extern fn(git_checkout_notify_t,
                                             *const c_char,
                                             *const git_diff_file,
@@ -239,6 +254,14 @@ pub struct git_remote_callbacks {
     pub payload: *mut c_void,
 }
 
+#[repr(C)]
+#[deriving(Copy)]
+pub enum git_remote_autotag_option_t {
+    GIT_REMOTE_DOWNLOAD_TAGS_AUTO,
+    GIT_REMOTE_DOWNLOAD_TAGS_NONE,
+    GIT_REMOTE_DOWNLOAD_TAGS_ALL,
+}
+
 #[repr(C)]
 #[deriving(Copy)]
 pub enum git_remote_completion_type {
@@ -275,6 +298,25 @@ pub struct git_cert {
     pub cert_type: git_cert_t,
 }
 
+#[repr(C)]
+pub struct git_cert_hostkey {
+    pub parent: git_cert,
+    pub kind: git_cert_ssh_t,
+    pub hash_md5: [u8, ..16],
+    pub hash_sha1: [u8, ..20],
+}
+
+#[repr(C)]
+pub struct git_cert_x509 {
+    pub parent: git_cert,
+    pub data: *mut c_void,
+    pub len: size_t,
+}
+
+pub type git_cert_ssh_t = c_uint;
+pub const GIT_CERT_SSH_MD5: c_uint = 1 << 0;
+pub const GIT_CERT_SSH_SHA1: c_uint = 1 << 1;
+
 #[repr(C)]
 #[deriving(Copy)]
 pub struct git_transfer_progress {
@@ -572,6 +614,16 @@ pub enum git_submodule_ignore_t {
     GIT_SUBMODULE_IGNORE_DEFAULT   = 0
 }
 
+#[repr(C)]
+#[deriving(Copy)]
+pub enum git_submodule_recurse_t {
+    GIT_SUBMODULE_RECURSE_RESET    = -1,
+
+    GIT_SUBMODULE_RECURSE_NO       = 0,
+    GIT_SUBMODULE_RECURSE_YES      = 1,
+    GIT_SUBMODULE_RECURSE_ONDEMAND = 2,
+}
+
 #[repr(C)]
 pub struct git_cred {
     pub credtype: git_credtype_t,
@@ -698,6 +750,72 @@ pub struct git_remote_head {
     pub symref_target: *mut c_char,
 }
 
+pub const GIT_IDXENTRY_NAMEMASK: u16 = 0x0fff;
+pub const GIT_IDXENTRY_STAGEMASK: u16 = 0x3000;
+pub const GIT_IDXENTRY_STAGESHIFT: uint = 12;
+pub const GIT_IDXENTRY_EXTENDED: u16 = 0x4000;
+pub const GIT_IDXENTRY_VALID: u16 = 0x8000;
+pub const GIT_IDXENTRY_INTENT_TO_ADD: u16 = 1 << 13;
+pub const GIT_IDXENTRY_SKIP_WORKTREE: u16 = 1 << 14;
+
+pub type git_attr_t = c_uint;
+pub const GIT_ATTR_UNSPECIFIED_T: c_uint = 0;
+pub const GIT_ATTR_TRUE_T: c_uint = 1;
+pub const GIT_ATTR_FALSE_T: c_uint = 2;
+pub const GIT_ATTR_VALUE_T: c_uint = 3;
+
+pub const GIT_ATTR_CHECK_FILE_THEN_INDEX: u32 = 0;
+pub const GIT_ATTR_CHECK_INDEX_THEN_FILE: u32 = 1;
+pub const GIT_ATTR_CHECK_INDEX_ONLY: u32 = 2;
+pub const GIT_ATTR_CHECK_NO_SYSTEM: u32 = 1 << 2;
+
+pub type git_attr_foreach_cb = extern fn(name: *const c_char,
+                                         value: *const c_char,
+                                         payload: *mut c_void) -> c_int;
+
+pub type git_filter_mode_t = c_uint;
+pub const GIT_FILTER_TO_WORKTREE: c_uint = 0;
+pub const GIT_FILTER_SMUDGE: c_uint = GIT_FILTER_TO_WORKTREE;
+pub const GIT_FILTER_TO_ODB: c_uint = 1;
+pub const GIT_FILTER_CLEAN: c_uint = GIT_FILTER_TO_ODB;
+
+pub const GIT_FILTER_DEFAULT: u32 = 0;
+pub const GIT_FILTER_ALLOW_UNSAFE: u32 = 1 << 0;
+
+pub const GIT_FILTER_VERSION: c_uint = 1;
+pub const GIT_FILTER_CRLF_PRIORITY: c_int = 0;
+pub const GIT_FILTER_IDENT_PRIORITY: c_int = 100;
+pub const GIT_FILTER_DRIVER_PRIORITY: c_int = 200;
+
+pub const GITERR_FILTER: c_int = 24;
+
+pub type git_filter_init_fn = extern fn(filter: *mut git_filter) -> c_int;
+pub type git_filter_shutdown_fn = extern fn(filter: *mut git_filter);
+pub type git_filter_check_fn = extern fn(filter: *mut git_filter,
+                                         payload: *mut *mut c_void,
+                                         src: *const git_filter_source,
+                                         attr_values: *mut *const c_char)
+                                         -> c_int;
+pub type git_filter_apply_fn = extern fn(filter: *mut git_filter,
+                                         payload: *mut *mut c_void,
+                                         to: *mut git_buf,
+                                         from: *const git_buf,
+                                         src: *const git_filter_source)
+                                         -> c_int;
+pub type git_filter_cleanup_fn = extern fn(filter: *mut git_filter,
+                                           payload: *mut c_void);
+
+#[repr(C)]
+pub struct git_filter {
+    pub version: c_uint,
+    pub attributes: *const c_char,
+    pub initialize: Option<git_filter_init_fn>,
+    pub shutdown: Option<git_filter_shutdown_fn>,
+    pub check: Option<git_filter_check_fn>,
+    pub apply: Option<git_filter_apply_fn>,
+    pub cleanup: Option<git_filter_cleanup_fn>,
+}
+
 pub type git_pathspec_flag_t = u32;
 pub const GIT_PATHSPEC_DEFAULT: u32 = 0;
 pub const GIT_PATHSPEC_IGNORE_CASE: u32 = 1 << 0;
@@ -788,6 +906,26 @@ pub type git_diff_notify_cb = extern fn(*const git_diff,
                                         *const c_char,
                                         *mut c_void) -> c_int;
 
+#[repr(C)]
+pub struct git_diff_find_options {
+    pub version: c_uint,
+    pub flags: u32,
+    pub rename_threshold: u16,
+    pub rename_from_rewrite_threshold: u16,
+    pub copy_threshold: u16,
+    pub break_rewrite_threshold: u16,
+    pub rename_limit: size_t,
+    pub metric: *mut c_void,
+}
+
+pub type git_diff_find_t = u32;
+pub const GIT_DIFF_FIND_BY_CONFIG: u32 = 0;
+pub const GIT_DIFF_FIND_RENAMES: u32 = 1 << 0;
+pub const GIT_DIFF_FIND_RENAMES_FROM_REWRITES: u32 = 1 << 1;
+pub const GIT_DIFF_FIND_COPIES: u32 = 1 << 2;
+pub const GIT_DIFF_FIND_COPIES_FROM_UNMODIFIED: u32 = 1 << 3;
+pub const GIT_DIFF_FIND_REWRITES: u32 = 1 << 4;
+
 pub type git_diff_options_t = u32;
 pub const GIT_DIFF_NORMAL: u32 = 0;
 pub const GIT_DIFF_REVERSE: u32 = 1 << 0;
@@ -962,6 +1100,11 @@ extern {
                                         -> *const c_char;
     pub fn git_repository_head(out: *mut *mut git_reference,
                                repo: *mut git_repository) -> c_int;
+    pub fn git_repository_set_head_detached(repo: *mut git_repository,
+                                            commitish: *const git_oid,
+                                            signature: *const git_signature,
+                                            log_message: *const c_char)
+                                            -> c_int;
     pub fn git_repository_is_bare(repo: *mut git_repository) -> c_int;
     pub fn git_repository_is_empty(repo: *mut git_repository) -> c_int;
     pub fn git_repository_is_shallow(repo: *mut git_repository) -> c_int;
@@ -978,6 +1121,69 @@ extern {
                                    start_path: *const c_char,
                                    across_fs: c_int,
                                    ceiling_dirs: *const c_char) -> c_int;
+    pub fn git_repository_hashfile(out: *mut git_oid,
+                                   repo: *mut git_repository,
+                                   path: *const c_char,
+                                   kind: git_otype,
+                                   as_path: *const c_char) -> c_int;
+
+    // attr
+    pub fn git_attr_get(value_out: *mut *const c_char,
+                        repo: *mut git_repository,
+                        flags: u32,
+                        path: *const c_char,
+                        name: *const c_char) -> c_int;
+    pub fn git_attr_get_many(values_out: *mut *const c_char,
+                             repo: *mut git_repository,
+                             flags: u32,
+                             path: *const c_char,
+                             num_attr: size_t,
+                             names: *mut *const c_char) -> c_int;
+    pub fn git_attr_foreach(repo: *mut git_repository,
+                            flags: u32,
+                            path: *const c_char,
+                            callback: git_attr_foreach_cb,
+                            payload: *mut c_void) -> c_int;
+    pub fn git_attr_value(attr: *const c_char) -> git_attr_t;
+    pub fn git_attr_add_macro(repo: *mut git_repository,
+                              name: *const c_char,
+                              values: *const c_char) -> c_int;
+    pub fn git_attr_cache_flush(repo: *mut git_repository);
+
+    // filter
+    pub fn git_filter_list_load(filters: *mut *mut git_filter_list,
+                                repo: *mut git_repository,
+                                blob: *mut git_blob,
+                                path: *const c_char,
+                                mode: git_filter_mode_t,
+                                flags: u32) -> c_int;
+    pub fn git_filter_list_length(filters: *const git_filter_list) -> size_t;
+    pub fn git_filter_list_apply_to_data(out: *mut git_buf,
+                                         filters: *mut git_filter_list,
+                                         input: *mut git_buf) -> c_int;
+    pub fn git_filter_list_apply_to_file(out: *mut git_buf,
+                                         filters: *mut git_filter_list,
+                                         repo: *mut git_repository,
+                                         path: *const c_char) -> c_int;
+    pub fn git_filter_list_apply_to_blob(out: *mut git_buf,
+                                         filters: *mut git_filter_list,
+                                         blob: *mut git_blob) -> c_int;
+    pub fn git_filter_list_free(filters: *mut git_filter_list);
+    pub fn git_filter_lookup(name: *const c_char) -> *mut git_filter;
+    pub fn git_filter_register(name: *const c_char,
+                               filter: *mut git_filter,
+                               priority: c_int) -> c_int;
+    pub fn git_filter_unregister(name: *const c_char) -> c_int;
+    pub fn git_filter_source_repo(src: *const git_filter_source)
+                                  -> *mut git_repository;
+    pub fn git_filter_source_path(src: *const git_filter_source)
+                                  -> *const c_char;
+    pub fn git_filter_source_filemode(src: *const git_filter_source) -> u16;
+    pub fn git_filter_source_id(src: *const git_filter_source)
+                                -> *const git_oid;
+    pub fn git_filter_source_mode(src: *const git_filter_source)
+                                  -> git_filter_mode_t;
+
 
     // revparse
     pub fn git_revparse(revspec: *mut git_revspec,
@@ -1021,6 +1227,7 @@ extern {
     pub fn giterr_last() -> *const git_error;
     pub fn giterr_clear();
     pub fn giterr_detach(cpy: *mut git_error) -> c_int;
+    pub fn giterr_set_str(error_class: c_int, string: *const c_char);
 
     // remote
     pub fn git_remote_create(out: *mut *mut git_remote,
@@ -1088,6 +1295,10 @@ extern {
     pub fn git_remote_set_callbacks(remote: *mut git_remote,
                                     callbacks: *const git_remote_callbacks)
                                     -> c_int;
+    pub fn git_remote_autotag(remote: *const git_remote)
+                              -> git_remote_autotag_option_t;
+    pub fn git_remote_set_autotag(remote: *mut git_remote,
+                                  value: git_remote_autotag_option_t);
     pub fn git_remote_init_callbacks(opts: *mut git_remote_callbacks,
                                      version: c_uint) -> c_int;
     pub fn git_remote_stats(remote: *mut git_remote)
@@ -1109,6 +1320,7 @@ extern {
 
     // strarray
     pub fn git_strarray_free(array: *mut git_strarray);
+    pub fn git_oidarray_free(array: *mut git_oidarray);
 
     // signature
     pub fn git_signature_default(out: *mut *mut git_signature,
@@ -1138,6 +1350,12 @@ extern {
     pub fn git_status_file(status_flags: *mut c_uint,
                            repo: *mut git_repository,
                            path: *const c_char) -> c_int;
+    pub fn git_ignore_add_rule(repo: *mut git_repository,
+                               rules: *const c_char) -> c_int;
+    pub fn git_ignore_clear_internal_rules(repo: *mut git_repository) -> c_int;
+    pub fn git_ignore_path_is_ignored(ignored: *mut c_int,
+                                      repo: *mut git_repository,
+                                      path: *const c_char) -> c_int;
     pub fn git_status_should_ignore(ignored: *mut c_int,
                                     repo: *mut git_repository,
                                     path: *const c_char) -> c_int;
@@ -1231,8 +1449,12 @@ extern {
                                                      *const c_char,
                                                      *mut c_void) -> c_int,
                                  payload: *mut c_void) -> c_int;
+    pub fn git_submodule_fetch_recurse_submodules(submodule: *mut git_submodule)
+                                                  -> git_submodule_recurse_t;
     pub fn git_submodule_free(submodule: *mut git_submodule);
     pub fn git_submodule_head_id(submodule: *mut git_submodule) -> *const git_oid;
+    pub fn git_submodule_ignore(submodule: *mut git_submodule)
+                                -> git_submodule_ignore_t;
     pub fn git_submodule_index_id(submodule: *mut git_submodule) -> *const git_oid;
     pub fn git_submodule_init(submodule: *mut git_submodule,
                               overwrite: c_int) -> c_int;
@@ -1244,12 +1466,17 @@ extern {
     pub fn git_submodule_name(submodule: *mut git_submodule) -> *const c_char;
     pub fn git_submodule_open(repo: *mut *mut git_repository,
                               submodule: *mut git_submodule) -> c_int;
+    pub fn git_submodule_owner(submodule: *mut git_submodule)
+                               -> *mut git_repository;
     pub fn git_submodule_path(submodule: *mut git_submodule) -> *const c_char;
     pub fn git_submodule_reload(submodule: *mut git_submodule,
                                 force: c_int) -> c_int;
     pub fn git_submodule_reload_all(repo: *mut git_repository,
                                     force: c_int) -> c_int;
     pub fn git_submodule_save(submodule: *mut git_submodule) -> c_int;
+    pub fn git_submodule_set_fetch_recurse_submodules(
+        submodule: *mut git_submodule,
+        recurse: git_submodule_recurse_t) -> git_submodule_recurse_t;
     pub fn git_submodule_set_ignore(submodule: *mut git_submodule,
                                     ignore: git_submodule_ignore_t)
                                     -> git_submodule_ignore_t;
@@ -1323,12 +1550,34 @@ extern {
                          callback: git_treewalk_cb,
                          payload: *mut c_void) -> c_int;
 
+    // odb
+    pub fn git_repository_odb(out: *mut *mut git_odb,
+                              repo: *mut git_repository) -> c_int;
+    pub fn git_odb_free(db: *mut git_odb);
+    pub fn git_odb_read(out: *mut *mut git_odb_object,
+                        db: *mut git_odb,
+                        oid: *const git_oid) -> c_int;
+    pub fn git_odb_object_data(object: *mut git_odb_object) -> *const c_void;
+    pub fn git_odb_object_size(object: *mut git_odb_object) -> size_t;
+    pub fn git_odb_object_free(object: *mut git_odb_object);
+    pub fn git_odb_write(out: *mut git_oid,
+                         odb: *mut git_odb,
+                         data: *const c_void,
+                         len: size_t,
+                         kind: git_otype) -> c_int;
+
     // buf
     pub fn git_buf_free(buffer: *mut git_buf);
     pub fn git_buf_grow(buffer: *mut git_buf, target_size: size_t) -> c_int;
     pub fn git_buf_set(buffer: *mut git_buf, data: *const c_void,
                        datalen: size_t) -> c_int;
 
+    // message
+    pub fn git_message_prettify(out: *mut git_buf,
+                                message: *const c_char,
+                                strip_comments: c_int,
+                                comment_char: c_char) -> c_int;
+
     // commit
     pub fn git_commit_author(commit: *const git_commit) -> *const git_signature;
     pub fn git_commit_committer(commit: *const git_commit) -> *const git_signature;
@@ -1374,6 +1623,7 @@ extern {
                              tree: *const git_tree,
                              parent_count: size_t,
                              parents: *const *const git_commit) -> c_int;
+    pub fn git_commit_owner(commit: *const git_commit) -> *mut git_repository;
 
     // branch
     pub fn git_branch_create(out: *mut *mut git_reference,
@@ -1434,6 +1684,7 @@ extern {
     pub fn git_index_new(index: *mut *mut git_index) -> c_int;
     pub fn git_index_open(index: *mut *mut git_index,
                           index_path: *const c_char) -> c_int;
+    pub fn git_index_owner(index: *const git_index) -> *mut git_repository;
     pub fn git_index_path(index: *const git_index) -> *const c_char;
     pub fn git_index_read(index: *mut git_index, force: c_int) -> c_int;
     pub fn git_index_read_tree(index: *mut git_index,
@@ -1468,6 +1719,10 @@ extern {
                                       force: c_int) -> c_int;
     pub fn git_config_delete_entry(cfg: *mut git_config,
                                    name: *const c_char) -> c_int;
+    pub fn git_config_set_multivar(cfg: *mut git_config,
+                                   name: *const c_char,
+                                   regexp: *const c_char,
+                                   value: *const c_char) -> c_int;
     pub fn git_config_delete_multivar(cfg: *mut git_config,
                                       name: *const c_char,
                                       regexp: *const c_char) -> c_int;
@@ -1595,6 +1850,7 @@ extern {
                                      repo: *mut git_repository,
                                      buffer: *const c_char,
                                      force: c_int) -> c_int;
+    pub fn git_tag_owner(tag: *const git_tag) -> *mut git_repository;
     pub fn git_tag_create_lightweight(oid: *mut git_oid,
                                       repo: *mut git_repository,
                                       tag_name: *const c_char,
@@ -1703,12 +1959,42 @@ extern {
                                  refname: *const c_char) -> c_int;
 
     pub fn git_revwalk_next(out: *mut git_oid, walk: *mut git_revwalk) -> c_int;
+    pub fn git_revwalk_add_hide_cb(walk: *mut git_revwalk,
+                                   hide_cb: Option<git_revwalk_hide_cb>,
+                                   payload: *mut c_void) -> c_int;
+    pub fn git_revwalk_repository(walk: *mut git_revwalk) -> *mut git_repository;
 
     // merge
     pub fn git_merge_base(out: *mut git_oid,
                           repo: *mut git_repository,
                           one: *const git_oid,
                           two: *const git_oid) -> c_int;
+    pub fn git_merge_bases(out: *mut git_oidarray,
+                           repo: *mut git_repository,
+                           one: *const git_oid,
+                           two: *const git_oid) -> c_int;
+    pub fn git_merge_base_many(out: *mut git_oid,
+                               repo: *mut git_repository,
+                               length: size_t,
+                               input_array: *const git_oid) -> c_int;
+    pub fn git_merge_bases_many(out: *mut git_oidarray,
+                                repo: *mut git_repository,
+                                length: size_t,
+                                input_array: *const git_oid) -> c_int;
+    pub fn git_merge_base_octopus(out: *mut git_oid,
+                                  repo: *mut git_repository,
+                                  length: size_t,
+                                  input_array: *const git_oid) -> c_int;
+
+    // graph
+    pub fn git_graph_ahead_behind(ahead: *mut size_t,
+                                  behind: *mut size_t,
+                                  repo: *mut git_repository,
+                                  local: *const git_oid,
+                                  upstream: *const git_oid) -> c_int;
+    pub fn git_graph_descendant_of(repo: *mut git_repository,
+                                   commit: *const git_oid,
+                                   ancestor: *const git_oid) -> c_int;
 
     // pathspec
     pub fn git_pathspec_free(ps: *mut git_pathspec);
@@ -1792,6 +2078,10 @@ extern {
                                      opts: *const git_diff_options) -> c_int;
     pub fn git_diff_init_options(opts: *mut git_diff_options,
                                  version: c_uint) -> c_int;
+    pub fn git_diff_find_init_options(opts: *mut git_diff_find_options,
+                                      version: c_uint) -> c_int;
+    pub fn git_diff_find_similar(diff: *mut git_diff,
+                                 options: *const git_diff_find_options) -> c_int;
     pub fn git_diff_is_sorted_icase(diff: *const git_diff) -> c_int;
     pub fn git_diff_merge(onto: *mut git_diff,
                           from: *const git_diff) -> c_int;
@@ -0,0 +1,147 @@
+use std::c_str::CString;
+use libc::c_char;
+
+use raw;
+
+/// The value of a git attribute for a path, as returned by
+/// `Repository::get_attr`.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub enum AttrValue {
+    /// The attribute is set, as with `attr` in `.gitattributes`.
+    True,
+    /// The attribute is unset, as with `-attr`.
+    False,
+    /// The attribute is not mentioned for the path, or reset with `!attr`.
+    Unspecified,
+    /// The attribute is set to a value, as with `attr=value`.
+    String(String),
+    /// The attribute is set to a value which is not valid utf-8.
+    Bytes(Vec<u8>),
+}
+
+impl AttrValue {
+    /// Converts a raw attribute value, as returned by `git_attr_get`, to its
+    /// typed representation.
+    ///
+    /// This function is unsafe as the pointer is not guaranteed to be valid.
+    pub unsafe fn from_raw(value: *const c_char) -> AttrValue {
+        match raw::git_attr_value(value) {
+            raw::GIT_ATTR_TRUE_T => AttrValue::True,
+            raw::GIT_ATTR_FALSE_T => AttrValue::False,
+            raw::GIT_ATTR_VALUE_T => {
+                let bytes = CString::new(value, false).as_bytes_no_nul()
+                                                      .to_vec();
+                match String::from_utf8(bytes) {
+                    Ok(s) => AttrValue::String(s),
+                    Err(bytes) => AttrValue::Bytes(bytes),
+                }
+            }
+            _ => AttrValue::Unspecified,
+        }
+    }
+
+    /// Returns the value of the attribute if it is set to a utf-8 value.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            AttrValue::String(ref s) => Some(s.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the attribute if it is set to a value.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            AttrValue::String(ref s) => Some(s.as_bytes()),
+            AttrValue::Bytes(ref b) => Some(b.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the attribute is mentioned for the path at all.
+    pub fn is_specified(&self) -> bool {
+        match *self {
+            AttrValue::Unspecified => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{fs, File};
+    use {AttrValue, ATTR_CHECK_FILE_THEN_INDEX, ATTR_CHECK_INDEX_ONLY};
+    use {ATTR_CHECK_NO_SYSTEM};
+
+    #[test]
+    fn smoke() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        File::create(&root.join(".gitattributes")).write_str("\
+*.bin binary lfs=yes
+*.txt text -diff eol=lf
+docs/** !text
+").unwrap();
+        let flags = ATTR_CHECK_FILE_THEN_INDEX | ATTR_CHECK_NO_SYSTEM;
+        let path = Path::new("a.txt");
+
+        assert_eq!(repo.get_attr(&path, "text", flags).unwrap(),
+                   AttrValue::True);
+        assert_eq!(repo.get_attr(&path, "diff", flags).unwrap(),
+                   AttrValue::False);
+        assert_eq!(repo.get_attr(&path, "eol", flags).unwrap().as_str(),
+                   Some("lf"));
+        assert_eq!(repo.get_attr(&path, "lfs", flags).unwrap(),
+                   AttrValue::Unspecified);
+        assert_eq!(repo.get_attr(&Path::new("docs/a.txt"), "text", flags)
+                       .unwrap(),
+                   AttrValue::Unspecified);
+        assert_eq!(repo.get_attr(&path, "text", ATTR_CHECK_INDEX_ONLY)
+                       .unwrap(),
+                   AttrValue::Unspecified);
+
+        // `binary` is a built-in macro for `-diff -merge -text`
+        assert_eq!(repo.get_attr_many(&Path::new("a.bin"),
+                                      &["lfs", "text", "merge", "eol"],
+                                      flags).unwrap(),
+                   vec![AttrValue::String("yes".to_string()), AttrValue::False,
+                        AttrValue::False, AttrValue::Unspecified]);
+
+        let mut attrs = Vec::new();
+        repo.attr_foreach(&path, flags, |name, value| {
+            attrs.push((String::from_utf8(name.to_vec()).unwrap(), value));
+            true
+        }).unwrap();
+        attrs.sort_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+        assert_eq!(attrs, vec![("diff".to_string(), AttrValue::False),
+                               ("eol".to_string(),
+                                AttrValue::String("lf".to_string())),
+                               ("text".to_string(), AttrValue::True)]);
+    }
+
+    #[test]
+    fn macros_and_cache() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        let info = repo.path().join("info");
+        fs::mkdir_recursive(&info, ::std::io::USER_RWX).unwrap();
+        File::create(&info.join("attributes")).write_str("*.dat data\n")
+            .unwrap();
+        repo.add_attr_macro("data", "-text lockable").unwrap();
+        let path = Path::new("x.dat");
+        assert_eq!(repo.get_attr(&path, "lockable", ATTR_CHECK_NO_SYSTEM)
+                       .unwrap(),
+                   AttrValue::True);
+        assert_eq!(repo.get_attr(&path, "text", ATTR_CHECK_NO_SYSTEM).unwrap(),
+                   AttrValue::False);
+
+        // Flushing the cache forgets the macro, so `data` no longer unsets
+        // `text` and the new .gitattributes applies.
+        File::create(&root.join(".gitattributes")).write_str("*.dat text\n")
+            .unwrap();
+        repo.clear_attr_cache();
+        assert_eq!(repo.get_attr(&path, "text", ATTR_CHECK_NO_SYSTEM).unwrap(),
+                   AttrValue::True);
+        assert_eq!(repo.get_attr(&path, "data", ATTR_CHECK_NO_SYSTEM).unwrap(),
+                   AttrValue::True);
+    }
+}
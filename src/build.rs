@@ -1,11 +1,16 @@
 //! Builder-pattern objects for configuration various git operations.
 
 use std::c_str::CString;
+use std::cmp;
 use std::io;
 use std::mem;
+use std::sync::Mutex;
+use std::thread::Thread;
 use libc::{c_char, size_t, c_void, c_uint, c_int};
 
 use {raw, Signature, Error, Repository, RemoteCallbacks, panic};
+use {Remote, AutotagOption, Oid, Tree, Commit, ObjectType, DiffFile, sign};
+use {CheckoutNotificationType, SubmoduleUpdate, SubmoduleUpdateOptions};
 
 /// A builder struct which is used to build configuration for cloning a new git
 /// repository.
@@ -17,9 +22,76 @@ pub struct RepoBuilder<'cb> {
     hardlinks: bool,
     checkout: Option<CheckoutBuilder<'cb>>,
     callbacks: Option<RemoteCallbacks<'cb>>,
+    recurse_submodules: bool,
+    submodule_jobs: uint,
+    submodule_hooks: SubmoduleHooks<'cb>,
+}
+
+/// Callback creating the remote callbacks used to fetch a submodule during a
+/// recursive clone, given the path of the submodule.
+pub type SubmoduleCallbacks<'a> = Fn(&Path) -> RemoteCallbacks<'a>
+                                  + Send + Sync + 'a;
+
+/// Callback rewriting the url of a submodule during a recursive clone.
+///
+/// It is given the path and url of the submodule, and returns the url to use
+/// instead, if any.
+pub type SubmoduleUrl<'a> = Fn(&Path, &str) -> Option<String>
+                            + Send + Sync + 'a;
+
+/// Callback deciding whether a submodule is cloned during a recursive clone,
+/// given its path.
+pub type SubmoduleFilter<'a> = Fn(&Path) -> bool + Send + Sync + 'a;
+
+struct SubmoduleHooks<'cb> {
+    callbacks: Option<Box<SubmoduleCallbacks<'cb>>>,
+    url: Option<Box<SubmoduleUrl<'cb>>>,
+    filter: Option<Box<SubmoduleFilter<'cb>>>,
+    checkout: Option<CheckoutSettings>,
+}
+
+// The settings of a checkout builder which carry over to the checkouts of
+// submodules, unlike its callbacks and paths.
+#[deriving(Copy)]
+struct CheckoutSettings {
+    checkout_opts: uint,
+    disable_filters: bool,
+    file_perm: Option<io::FilePermission>,
+    dir_perm: Option<io::FilePermission>,
+}
+
+/// A builder struct which is used to configure a new remote before it is
+/// created in a repository's configuration.
+pub struct RemoteBuilder {
+    fetch: Option<CString>,
+    mirror: bool,
+    pushurl: Option<CString>,
+    autotag: AutotagOption,
+    prune: Option<bool>,
+}
+
+/// A builder struct which is used to assemble a commit object field by field.
+///
+/// Unlike `Repository::commit`, the commit created by this builder is only
+/// written to the object database and no reference is updated. Arbitrary
+/// extra headers may also be added, and `Commit::with_changes` can be used to
+/// start from the fields of an existing commit.
+#[deriving(Clone)]
+pub struct CommitBuilder {
+    tree: Option<Oid>,
+    parents: Vec<Oid>,
+    author: Option<Vec<u8>>,
+    committer: Option<Vec<u8>>,
+    encoding: Option<String>,
+    headers: Vec<(String, Vec<u8>)>,
+    message: Option<Vec<u8>>,
 }
 
 /// A builder struct for configuring checkouts of a repository.
+///
+/// Performance data about a checkout, such as the number of `mkdir` and
+/// `stat` calls made, is not reported as libgit2 0.22 has no perfdata
+/// callback.
 #[allow(raw_pointer_deriving)]
 pub struct CheckoutBuilder<'cb> {
     their_label: Option<CString>,
@@ -33,6 +105,8 @@ pub struct CheckoutBuilder<'cb> {
     disable_filters: bool,
     checkout_opts: uint,
     progress: Option<Box<Progress<'cb>>>,
+    notify: Option<Box<Notify<'cb>>>,
+    notify_flags: CheckoutNotificationType,
 }
 
 /// Checkout progress notification callback.
@@ -41,6 +115,17 @@ pub struct CheckoutBuilder<'cb> {
 /// of completed steps so far, and the final is the total number of steps.
 pub type Progress<'a> = FnMut(Option<&[u8]>, uint, uint) + 'a;
 
+/// Checkout notification callback.
+///
+/// The first argument is the type of the notification and the second the path
+/// it is about. The remaining arguments are the file in the baseline, in the
+/// target and in the working directory, if it exists there. Returning `false`
+/// aborts the checkout.
+pub type Notify<'a> = FnMut(CheckoutNotificationType, Option<&[u8]>,
+                            Option<DiffFile>, Option<DiffFile>,
+                            Option<DiffFile>) -> bool + 'a;
+
+
 impl<'cb> RepoBuilder<'cb> {
     /// Creates a new repository builder with all of the default configuration.
     ///
@@ -56,6 +141,14 @@ impl<'cb> RepoBuilder<'cb> {
             hardlinks: true,
             checkout: None,
             callbacks: None,
+            recurse_submodules: false,
+            submodule_jobs: 1,
+            submodule_hooks: SubmoduleHooks {
+                callbacks: None,
+                url: None,
+                filter: None,
+                checkout: None,
+            },
         }
     }
 
@@ -114,6 +207,70 @@ impl<'cb> RepoBuilder<'cb> {
         self
     }
 
+    /// Indicate whether the submodules of the repository are cloned and
+    /// checked out as well, recursively, as `git clone --recurse-submodules`
+    /// does.
+    ///
+    /// Each submodule is initialized, cloned and updated to the commit
+    /// recorded in its superproject, see `Submodule::update`. The strategy,
+    /// filters and permissions of the checkout configured with
+    /// `with_checkout` apply to the submodules too. This has no effect for
+    /// bare clones. Defaults to `false`.
+    pub fn recurse_submodules(&mut self, recurse: bool)
+                              -> &mut RepoBuilder<'cb> {
+        self.recurse_submodules = recurse;
+        self
+    }
+
+    /// Set the number of submodules cloned at the same time.
+    ///
+    /// Each submodule is cloned on its own thread, along with its nested
+    /// submodules. Defaults to 1, cloning them one after the other.
+    pub fn submodule_jobs(&mut self, jobs: uint) -> &mut RepoBuilder<'cb> {
+        self.submodule_jobs = jobs;
+        self
+    }
+
+    /// Set a callback creating the remote callbacks used to fetch each
+    /// submodule, for instance to provide credentials or report progress.
+    ///
+    /// The callback is given the path of the submodule relative to the
+    /// working directory of the clone. It may be called from several threads
+    /// when `submodule_jobs` is greater than 1.
+    pub fn submodule_callbacks<F>(&mut self, cb: F) -> &mut RepoBuilder<'cb>
+                                  where F: Fn(&Path) -> RemoteCallbacks<'cb>
+                                           + Send + Sync + 'cb {
+        self.submodule_hooks.callbacks =
+            Some(box cb as Box<SubmoduleCallbacks<'cb>>);
+        self
+    }
+
+    /// Set a callback rewriting the url each submodule is cloned from.
+    ///
+    /// The callback is given the path of the submodule relative to the
+    /// working directory of the clone and the url configured for it, and
+    /// returns the url to use instead, or `None` to keep it. The new url is
+    /// written to the configuration of the superproject, leaving
+    /// `.gitmodules` untouched.
+    pub fn submodule_url<F>(&mut self, cb: F) -> &mut RepoBuilder<'cb>
+                            where F: Fn(&Path, &str) -> Option<String>
+                                     + Send + Sync + 'cb {
+        self.submodule_hooks.url = Some(box cb as Box<SubmoduleUrl<'cb>>);
+        self
+    }
+
+    /// Set a callback deciding whether each submodule is cloned.
+    ///
+    /// The callback is given the path of the submodule relative to the
+    /// working directory of the clone, and returns `false` to skip it along
+    /// with its nested submodules.
+    pub fn submodule_filter<F>(&mut self, cb: F) -> &mut RepoBuilder<'cb>
+                               where F: Fn(&Path) -> bool + Send + Sync + 'cb {
+        self.submodule_hooks.filter =
+            Some(box cb as Box<SubmoduleFilter<'cb>>);
+        self
+    }
+
     /// Clone a remote repository.
     ///
     /// This will use the options configured so far to clone the specified url
@@ -153,10 +310,419 @@ impl<'cb> RepoBuilder<'cb> {
         }
 
         let mut raw = 0 as *mut raw::git_repository;
-        unsafe {
+        let repo = unsafe {
             try_call_panic!(raw::git_clone(&mut raw, url.to_c_str(),
                                            into.to_c_str(), &opts));
-            Ok(Repository::from_raw(raw))
+            // The checkout progress callback cannot fail the clone.
+            panic::check();
+            Repository::from_raw(raw)
+        };
+        if self.recurse_submodules && !self.bare {
+            self.submodule_hooks.checkout = self.checkout.as_ref().map(|c| {
+                c.settings()
+            });
+            try!(update_submodules(&repo, &Path::new("."),
+                                   &self.submodule_hooks,
+                                   self.submodule_jobs));
+        }
+        Ok(repo)
+    }
+}
+
+// Initialize, clone and check out the submodules of `repo` recursively.
+// `prefix` is the path of `repo` relative to the top-level clone.
+fn update_submodules(repo: &Repository, prefix: &Path, hooks: &SubmoduleHooks,
+                     jobs: uint) -> Result<(), Error> {
+    let workdir = match repo.workdir() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+
+    // Everything touching the configuration of the superproject is done
+    // up front, as it cannot be written from several threads.
+    let mut todo = Vec::new();
+    for submodule in try!(repo.submodules()).iter_mut() {
+        let path = prefix.join(submodule.path());
+        match hooks.filter {
+            Some(ref f) if !(**f)(&path) => continue,
+            _ => {}
+        }
+        try!(submodule.init(false));
+        let name = String::from_utf8_lossy(submodule.name_bytes())
+                          .into_owned();
+        let url = match hooks.url {
+            Some(ref f) => {
+                let url = String::from_utf8_lossy(submodule.url_bytes());
+                (**f)(&path, url.as_slice())
+            }
+            None => None,
+        };
+        match url {
+            Some(url) => {
+                let mut config = try!(repo.config());
+                let key = format!("submodule.{}.url", name);
+                try!(config.set_str(key.as_slice(), url.as_slice()));
+            }
+            None => {}
+        }
+        todo.push((name, path));
+    }
+
+    if jobs <= 1 || todo.len() <= 1 {
+        let errors = todo.iter().filter_map(|&(ref name, ref path)| {
+            update_submodule(repo, name.as_slice(), path, hooks).err()
+        }).collect();
+        return combine_errors(errors)
+    }
+
+    // Each thread works with its own handle on the superproject, as
+    // repositories cannot be shared between threads.
+    let queue = Mutex::new(todo);
+    let errors = Mutex::new(Vec::new());
+    {
+        let jobs = cmp::min(jobs, queue.lock().len());
+        let _workers = range(0, jobs).map(|_| {
+            Thread::scoped(|| {
+                let repo = match Repository::open(&workdir) {
+                    Ok(repo) => repo,
+                    Err(e) => return errors.lock().push(e),
+                };
+                loop {
+                    let next = queue.lock().pop();
+                    let (name, path) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    match update_submodule(&repo, name.as_slice(), &path,
+                                           hooks) {
+                        Ok(()) => {}
+                        Err(e) => errors.lock().push(e),
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+    }
+    combine_errors(errors.into_inner())
+}
+
+// Report every error from updating several submodules, one per line.
+fn combine_errors(mut errors: Vec<Error>) -> Result<(), Error> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.pop().unwrap()),
+        _ => {
+            let msgs = errors.iter().map(|e| e.message())
+                             .collect::<Vec<String>>();
+            Err(Error::from_str(msgs.connect("\n").as_slice()))
+        }
+    }
+}
+
+fn update_submodule(repo: &Repository, name: &str, path: &Path,
+                    hooks: &SubmoduleHooks) -> Result<(), Error> {
+    let mut submodule = try!(repo.find_submodule(name));
+    if submodule.update_strategy() == SubmoduleUpdate::None {
+        return Ok(())
+    }
+    let mut opts = SubmoduleUpdateOptions::new();
+    match hooks.callbacks {
+        Some(ref f) => { opts.remote_callbacks((**f)(path)); }
+        None => {}
+    }
+    match hooks.checkout {
+        Some(settings) => {
+            opts.checkout(CheckoutBuilder::from_settings(settings));
+        }
+        None => {}
+    }
+    let repo = match submodule.update(false, Some(&mut opts)).and_then(|()| {
+        submodule.open()
+    }) {
+        Ok(repo) => repo,
+        Err(e) => {
+            let msg = format!("failed to update submodule `{}`: {}",
+                              path.display(), e.message());
+            return Err(Error::from_str(msg.as_slice()))
+        }
+    };
+    update_submodules(&repo, path, hooks, 1)
+}
+
+impl RemoteBuilder {
+    /// Creates a new remote builder with all of the default configuration.
+    ///
+    /// When ready, the `create()` method can be used to create the remote.
+    pub fn new() -> RemoteBuilder {
+        ::init();
+        RemoteBuilder {
+            fetch: None,
+            mirror: false,
+            pushurl: None,
+            autotag: AutotagOption::Auto,
+            prune: None,
+        }
+    }
+
+    /// Use a custom fetch refspec instead of the default
+    /// `+refs/heads/*:refs/remotes/<name>/*`.
+    pub fn fetch(&mut self, spec: &str) -> &mut RemoteBuilder {
+        self.fetch = Some(spec.to_c_str());
+        self
+    }
+
+    /// Configure the remote as a mirror, as `git remote add --mirror=fetch`
+    /// does.
+    ///
+    /// All references are fetched with `+refs/*:refs/*` and
+    /// `remote.<name>.mirror` is set. This takes precedence over `fetch`.
+    pub fn mirror(&mut self, mirror: bool) -> &mut RemoteBuilder {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Set a separate url to push to.
+    pub fn pushurl(&mut self, url: &str) -> &mut RemoteBuilder {
+        self.pushurl = Some(url.to_c_str());
+        self
+    }
+
+    /// Configure how tags are downloaded when fetching from the remote.
+    pub fn autotag(&mut self, value: AutotagOption) -> &mut RemoteBuilder {
+        self.autotag = value;
+        self
+    }
+
+    /// Configure whether stale remote-tracking references are pruned when
+    /// fetching from the remote.
+    pub fn prune(&mut self, prune: bool) -> &mut RemoteBuilder {
+        self.prune = Some(prune);
+        self
+    }
+
+    fn fetchspec(&self) -> Option<CString> {
+        if self.mirror {
+            Some("+refs/*:refs/*".to_c_str())
+        } else {
+            self.fetch.clone()
+        }
+    }
+
+    /// Create a new remote named `name` in the configuration of `repo`.
+    ///
+    /// The remote returned is loaded after all of the configuration has been
+    /// written, so it reflects every setting of this builder.
+    pub fn create<'a, 'cb>(&self, repo: &'a Repository, name: &str, url: &str)
+                           -> Result<Remote<'a, 'cb>, Error> {
+        let mut ret = 0 as *mut raw::git_remote;
+        unsafe {
+            try_call!(raw::git_remote_create(&mut ret, repo.raw(),
+                                             name.to_c_str(), url.to_c_str()));
+            drop(Remote::from_raw(ret));
+        }
+
+        let mut config = try!(repo.config());
+        match self.fetchspec() {
+            Some(spec) => {
+                let key = format!("remote.{}.fetch", name);
+                try!(config.set_multivar(key.as_slice(), ".*",
+                                         spec.as_str().unwrap()));
+            }
+            None => {}
+        }
+        if self.mirror {
+            try!(config.set_bool(format!("remote.{}.mirror", name).as_slice(),
+                                 true));
+        }
+        match self.pushurl {
+            Some(ref url) => {
+                try!(repo.remote_set_pushurl(name, url.as_str()));
+            }
+            None => {}
+        }
+        try!(repo.remote_set_autotag(name, self.autotag));
+        match self.prune {
+            Some(prune) => try!(repo.remote_set_prune(name, prune)),
+            None => {}
+        }
+
+        unsafe {
+            try_call!(raw::git_remote_lookup(&mut ret, repo.raw(),
+                                             name.to_c_str()));
+            Ok(Remote::from_raw(ret))
+        }
+    }
+}
+
+impl CommitBuilder {
+    /// Creates a new commit builder with no fields set.
+    ///
+    /// At least a tree, an author, a committer and a message must be set
+    /// before the commit can be created.
+    pub fn new() -> CommitBuilder {
+        ::init();
+        CommitBuilder {
+            tree: None,
+            parents: Vec::new(),
+            author: None,
+            committer: None,
+            encoding: None,
+            headers: Vec::new(),
+            message: None,
+        }
+    }
+
+    /// Set the tree of the commit.
+    pub fn tree(&mut self, tree: &Tree) -> &mut CommitBuilder {
+        self.tree_id(tree.id())
+    }
+
+    /// Set the tree of the commit by its id.
+    pub fn tree_id(&mut self, id: Oid) -> &mut CommitBuilder {
+        self.tree = Some(id);
+        self
+    }
+
+    /// Add a parent to the commit.
+    pub fn parent(&mut self, commit: &Commit) -> &mut CommitBuilder {
+        self.parent_id(commit.id())
+    }
+
+    /// Add a parent to the commit by its id.
+    pub fn parent_id(&mut self, id: Oid) -> &mut CommitBuilder {
+        self.parents.push(id);
+        self
+    }
+
+    /// Replace all parents of the commit with `ids`.
+    pub fn parent_ids(&mut self, ids: &[Oid]) -> &mut CommitBuilder {
+        self.parents = ids.to_vec();
+        self
+    }
+
+    /// Set the author of the commit.
+    pub fn author(&mut self, sig: &Signature) -> &mut CommitBuilder {
+        self.author = Some(sign::header_signature(sig));
+        self
+    }
+
+    /// Set the committer of the commit.
+    pub fn committer(&mut self, sig: &Signature) -> &mut CommitBuilder {
+        self.committer = Some(sign::header_signature(sig));
+        self
+    }
+
+    /// Set the encoding of the commit message, recorded in the `encoding`
+    /// header.
+    ///
+    /// `None` removes the header, in which case the message is assumed to be
+    /// UTF-8.
+    pub fn message_encoding(&mut self, encoding: Option<&str>)
+                            -> &mut CommitBuilder {
+        self.encoding = encoding.map(|s| s.to_string());
+        self
+    }
+
+    /// Set the message of the commit.
+    ///
+    /// The message is stored exactly as given; see `message_prettify` for
+    /// cleaning it up first.
+    pub fn message(&mut self, message: &str) -> &mut CommitBuilder {
+        self.message_bytes(message.as_bytes())
+    }
+
+    /// Set the message of the commit as raw bytes, for messages which are not
+    /// UTF-8.
+    pub fn message_bytes(&mut self, message: &[u8]) -> &mut CommitBuilder {
+        self.message = Some(message.to_vec());
+        self
+    }
+
+    /// Add an extra header to the commit.
+    ///
+    /// Extra headers are written after the standard `tree`, `parent`,
+    /// `author`, `committer` and `encoding` headers, in the order they were
+    /// added. A value spanning several lines is written as a multi-line
+    /// header.
+    pub fn header(&mut self, name: &str, value: &str) -> &mut CommitBuilder {
+        self.header_bytes(name, value.as_bytes())
+    }
+
+    /// Add an extra header to the commit whose value is not UTF-8.
+    pub fn header_bytes(&mut self, name: &str, value: &[u8])
+                        -> &mut CommitBuilder {
+        self.headers.push((name.to_string(), value.to_vec()));
+        self
+    }
+
+    /// Remove all extra headers named `name`.
+    pub fn remove_header(&mut self, name: &str) -> &mut CommitBuilder {
+        self.headers.retain(|&(ref n, _)| n.as_slice() != name);
+        self
+    }
+
+    /// Build the contents of the commit object without writing it.
+    ///
+    /// An error is returned if a required field has not been set or if an
+    /// extra header has an invalid name.
+    pub fn buffer(&self) -> Result<Vec<u8>, Error> {
+        fn missing(field: &str) -> Error {
+            Error::from_str(format!("commit has no {}", field).as_slice())
+        }
+        let tree = try!(self.tree.ok_or_else(|| missing("tree")));
+        let author = try!(self.author.as_ref()
+                              .ok_or_else(|| missing("author")));
+        let committer = try!(self.committer.as_ref()
+                                 .ok_or_else(|| missing("committer")));
+        let message = try!(self.message.as_ref()
+                               .ok_or_else(|| missing("message")));
+
+        let mut ret = Vec::new();
+        ret.push_all(format!("tree {}\n", tree).as_bytes());
+        for parent in self.parents.iter() {
+            ret.push_all(format!("parent {}\n", parent).as_bytes());
+        }
+        ret.push_all(b"author ");
+        ret.push_all(author.as_slice());
+        ret.push_all(b"\ncommitter ");
+        ret.push_all(committer.as_slice());
+        ret.push(b'\n');
+        match self.encoding {
+            Some(ref e) => ret.push_all(format!("encoding {}\n", e).as_bytes()),
+            None => {}
+        }
+        for &(ref name, ref value) in self.headers.iter() {
+            if name.len() == 0 ||
+               name.as_slice().chars().any(|c| c == ' ' || c == '\n') {
+                return Err(Error::from_str(format!("invalid header name `{}`",
+                                                   name).as_slice()))
+            }
+            ret.push_all(name.as_bytes());
+            ret.push(b' ');
+            for (i, line) in value.as_slice().split(|b| *b == b'\n')
+                                  .enumerate() {
+                if i > 0 { ret.push_all(b"\n ") }
+                ret.push_all(line);
+            }
+            ret.push(b'\n');
+        }
+        ret.push(b'\n');
+        ret.push_all(message.as_slice());
+        Ok(ret)
+    }
+
+    /// Write the commit to the object database of `repo`, returning its id.
+    ///
+    /// An error is returned if the tree or any of the parents is not found in
+    /// `repo`. No reference is updated; use `Repository::reference` to point
+    /// a branch at the new commit.
+    pub fn create(&self, repo: &Repository) -> Result<Oid, Error> {
+        let buf = try!(self.buffer());
+        try!(repo.find_tree(self.tree.unwrap()));
+        for parent in self.parents.iter() {
+            try!(repo.find_commit(*parent));
+        }
+        unsafe {
+            sign::write_object(repo.raw(), ObjectType::Commit, buf.as_slice())
         }
     }
 }
@@ -178,6 +744,8 @@ impl<'cb> CheckoutBuilder<'cb> {
             their_label: None,
             checkout_opts: raw::GIT_CHECKOUT_SAFE_CREATE as uint,
             progress: None,
+            notify: None,
+            notify_flags: CheckoutNotificationType::empty(),
         }
     }
 
@@ -380,6 +948,48 @@ impl<'cb> CheckoutBuilder<'cb> {
         self
     }
 
+    /// Set the types of notifications passed to the `notify` callback.
+    ///
+    /// No notification is emitted by default.
+    pub fn notify_on(&mut self, types: CheckoutNotificationType)
+                     -> &mut CheckoutBuilder<'cb> {
+        self.notify_flags = types;
+        self
+    }
+
+    /// Set a callback to be notified of the files the checkout is about to
+    /// touch, of the kinds selected with `notify_on`.
+    ///
+    /// Notifications are emitted before any file is modified, so returning
+    /// `false` from the callback aborts the checkout without changing the
+    /// working directory. Combined with `dry_run` this lists the files a
+    /// checkout would conflict with or overwrite.
+    pub fn notify<F>(&mut self, cb: F) -> &mut CheckoutBuilder<'cb>
+                     where F: FnMut(CheckoutNotificationType, Option<&[u8]>,
+                                    Option<DiffFile>, Option<DiffFile>,
+                                    Option<DiffFile>) -> bool + 'cb {
+        self.notify = Some(box cb as Box<Notify<'cb>>);
+        self
+    }
+
+    fn settings(&self) -> CheckoutSettings {
+        CheckoutSettings {
+            checkout_opts: self.checkout_opts,
+            disable_filters: self.disable_filters,
+            file_perm: self.file_perm,
+            dir_perm: self.dir_perm,
+        }
+    }
+
+    fn from_settings(settings: CheckoutSettings) -> CheckoutBuilder<'cb> {
+        let mut c = CheckoutBuilder::new();
+        c.checkout_opts = settings.checkout_opts;
+        c.disable_filters = settings.disable_filters;
+        c.file_perm = settings.file_perm;
+        c.dir_perm = settings.dir_perm;
+        c
+    }
+
     /// Configure a raw checkout options based on this configuration.
     ///
     /// This method is unsafe as there is no guarantee that this structure will
@@ -416,6 +1026,12 @@ impl<'cb> CheckoutBuilder<'cb> {
             opts.progress_cb = Some(f);
             opts.progress_payload = self as *mut _ as *mut _;
         }
+        if self.notify.is_some() {
+            let f: raw::git_checkout_notify_cb = notify_cb;
+            opts.notify_cb = Some(f);
+            opts.notify_payload = self as *mut _ as *mut _;
+            opts.notify_flags = self.notify_flags.bits() as c_uint;
+        }
         opts.checkout_strategy = self.checkout_opts as c_uint;
     }
 }
@@ -442,11 +1058,45 @@ extern fn progress_cb(path: *const c_char,
     }
 }
 
+extern fn notify_cb(why: raw::git_checkout_notify_t,
+                    path: *const c_char,
+                    baseline: *const raw::git_diff_file,
+                    target: *const raw::git_diff_file,
+                    workdir: *const raw::git_diff_file,
+                    data: *mut c_void) -> c_int {
+    unsafe {
+        let payload: &mut CheckoutBuilder = &mut *(data as *mut CheckoutBuilder);
+        let callback = match payload.notify {
+            Some(ref mut c) => c,
+            None => return 0,
+        };
+        let path = if path.is_null() {
+            None
+        } else {
+            Some(CString::new(path, false))
+        };
+        let why = CheckoutNotificationType::from_bits_truncate(why as u32);
+        let file = |&: p: *const raw::git_diff_file| {
+            if p.is_null() { None } else { Some(DiffFile::from_raw(p)) }
+        };
+        let ok = panic::wrap(|| {
+            callback.call_mut((why,
+                               path.as_ref().map(|p| p.as_bytes_no_nul()),
+                               file(baseline), file(target), file(workdir)))
+        });
+        if ok == Some(true) {0} else {-1}
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::io::{fs, TempDir};
-    use super::RepoBuilder;
-    use Repository;
+    use std::io::{fs, File, TempDir};
+    use std::io::fs::PathExtensions;
+    use std::sync::atomic::{AtomicUint, SeqCst};
+    use super::{RepoBuilder, RemoteBuilder, CheckoutBuilder};
+    use {Repository, AutotagOption, RemoteCallbacks};
+    use {CHECKOUT_NOTIFICATION_CONFLICT, CHECKOUT_NOTIFICATION_UNTRACKED};
+    use CHECKOUT_NOTIFICATION_UPDATED;
 
     #[test]
     fn smoke() {
@@ -477,4 +1127,149 @@ mod tests {
                                   .clone(url.as_slice(), &dst).is_err());
     }
 
+    #[test]
+    fn remote_builder() {
+        let (_td, repo) = ::test::repo_init();
+        let remote = RemoteBuilder::new()
+            .fetch("+refs/heads/master:refs/remotes/upstream/master")
+            .pushurl("/path/to/push")
+            .autotag(AutotagOption::None)
+            .prune(true)
+            .create(&repo, "upstream", "/path/to/nowhere").unwrap();
+        assert_eq!(remote.pushurl(), Some("/path/to/push"));
+        assert_eq!(remote.download_tags(), AutotagOption::None);
+        let cfg = repo.config().unwrap();
+        assert!(cfg.get_bool("remote.upstream.prune").unwrap());
+        {
+            let specs = remote.refspecs().map(|s| s.str().unwrap().to_string())
+                              .collect::<Vec<_>>();
+            assert_eq!(specs, vec!["+refs/heads/master:refs/remotes/upstream/master"
+                                       .to_string()]);
+        }
+
+        let remote = RemoteBuilder::new().mirror(true)
+            .create(&repo, "backup", "/path/to/backup").unwrap();
+        assert_eq!(remote.refspecs().next().unwrap().str(),
+                   Some("+refs/*:refs/*"));
+        let cfg = repo.config().unwrap();
+        assert!(cfg.get_bool("remote.backup.mirror").unwrap());
+        let fetch = cfg.entries(Some("remote.backup.fetch")).unwrap();
+        assert_eq!((&fetch).count(), 1);
+
+        assert!(repo.remote_prune("upstream").unwrap());
+        assert!(!repo.remote_prune("backup").unwrap());
+        repo.config().unwrap().set_bool("fetch.prune", true).unwrap();
+        assert!(repo.remote_prune("backup").unwrap());
+        repo.remote_set_prune("backup", false).unwrap();
+        assert!(!repo.remote_prune("backup").unwrap());
+        assert!(RemoteBuilder::new().create(&repo, "bad name", "/x").is_err());
+    }
+
+    #[test]
+    fn checkout_notify() {
+        let (td, repo) = ::test::repo_init();
+        let path = td.path().join("foo");
+        File::create(&path).write_str("committed").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&Path::new("foo")).unwrap();
+        index.write().unwrap();
+        File::create(&path).write_str("modified").unwrap();
+        File::create(&td.path().join("bar")).unwrap();
+
+        let mut conflicts = Vec::new();
+        let mut untracked = Vec::new();
+        {
+            let mut opts = CheckoutBuilder::new();
+            opts.dry_run()
+                .notify_on(CHECKOUT_NOTIFICATION_CONFLICT |
+                           CHECKOUT_NOTIFICATION_UNTRACKED)
+                .notify(|why, path, _baseline, target, workdir| {
+                    let path = path.unwrap().to_vec();
+                    if why == CHECKOUT_NOTIFICATION_CONFLICT {
+                        assert!(target.is_some());
+                        assert!(workdir.is_some());
+                        conflicts.push(path);
+                    } else {
+                        assert!(target.is_none());
+                        untracked.push(path);
+                    }
+                    true
+                });
+            repo.checkout_index(None, Some(&mut opts)).unwrap();
+        }
+        assert_eq!(conflicts, vec![b"foo".to_vec()]);
+        assert_eq!(untracked, vec![b"bar".to_vec()]);
+
+        let mut opts = CheckoutBuilder::new();
+        opts.force()
+            .notify_on(CHECKOUT_NOTIFICATION_UPDATED)
+            .notify(|_, _, _, _, _| false);
+        assert!(repo.checkout_index(None, Some(&mut opts)).is_err());
+        assert_eq!(File::open(&path).read_to_string().unwrap().as_slice(),
+                   "modified");
+    }
+
+    #[test]
+    fn recurse_submodules() {
+        let (td, _repo, sub_td, _sub) =
+            ::test::repo_with_submodules(&["sub", "sub2"]);
+        let url = td.path().as_str().unwrap();
+        let sub_url = sub_td.path().as_str().unwrap().to_string();
+
+        let dst = TempDir::new("test").unwrap();
+        let fetches = AtomicUint::new(0);
+        RepoBuilder::new().recurse_submodules(true).submodule_jobs(2)
+            .submodule_callbacks(|path| {
+                assert!(*path == Path::new("sub") ||
+                        *path == Path::new("sub2"));
+                fetches.fetch_add(1, SeqCst);
+                RemoteCallbacks::new()
+            })
+            .clone(url, dst.path()).unwrap();
+        assert!(dst.path().join("sub/file").exists());
+        assert!(dst.path().join("sub2/file").exists());
+        assert_eq!(fetches.load(SeqCst), 2);
+
+        let dst = TempDir::new("test").unwrap();
+        RepoBuilder::new().recurse_submodules(true)
+            .submodule_filter(|path| *path != Path::new("sub2"))
+            .clone(url, dst.path()).unwrap();
+        assert!(dst.path().join("sub/file").exists());
+        assert!(!dst.path().join("sub2/file").exists());
+
+        let dst = TempDir::new("test").unwrap();
+        let err = RepoBuilder::new().recurse_submodules(true).submodule_jobs(2)
+            .submodule_url(|_, _| Some("/path/to/nowhere".to_string()))
+            .clone(url, dst.path()).err().unwrap();
+        assert!(err.message().contains("submodule `sub`"));
+        assert!(err.message().contains("submodule `sub2`"));
+
+        let dst = TempDir::new("test").unwrap();
+        let err = RepoBuilder::new().recurse_submodules(true).submodule_jobs(1)
+            .submodule_url(|_, _| Some("/path/to/nowhere".to_string()))
+            .clone(url, dst.path()).err().unwrap();
+        assert!(err.message().contains("submodule `sub`"));
+        assert!(err.message().contains("submodule `sub2`"));
+
+        let dst = TempDir::new("test").unwrap();
+        let perm = ::std::io::USER_READ | ::std::io::USER_WRITE;
+        let mut checkout = CheckoutBuilder::new();
+        checkout.file_perm(perm);
+        RepoBuilder::new().recurse_submodules(true).with_checkout(checkout)
+            .clone(url, dst.path()).unwrap();
+        let stat = fs::stat(&dst.path().join("sub/file")).unwrap();
+        assert_eq!(stat.perm, perm);
+
+        let dst = TempDir::new("test").unwrap();
+        let repo = RepoBuilder::new().recurse_submodules(true)
+            .submodule_url(|_, url| {
+                assert_eq!(url, sub_url.as_slice());
+                Some(url.to_string())
+            })
+            .clone(url, dst.path()).unwrap();
+        assert!(dst.path().join("sub2/file").exists());
+        let config = repo.config().unwrap();
+        assert_eq!(config.get_str("submodule.sub.url").unwrap(),
+                   sub_url.as_slice());
+    }
 }
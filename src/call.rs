@@ -58,7 +58,8 @@ mod impls {
     use libc;
 
     use {raw, ConfigLevel, ResetType, ObjectType, BranchType, Direction};
-    use {DiffFormat};
+    use {DiffFormat, AutotagOption, SubmoduleUpdate, SubmoduleIgnore};
+    use SubmoduleRecurse;
     use call::Convert;
 
     impl<T: Copy> Convert<T> for T {
@@ -110,6 +111,16 @@ mod impls {
         }
     }
 
+    impl Convert<raw::git_remote_autotag_option_t> for AutotagOption {
+        fn convert(&self) -> raw::git_remote_autotag_option_t {
+            match *self {
+                AutotagOption::None => raw::GIT_REMOTE_DOWNLOAD_TAGS_NONE,
+                AutotagOption::Auto => raw::GIT_REMOTE_DOWNLOAD_TAGS_AUTO,
+                AutotagOption::All => raw::GIT_REMOTE_DOWNLOAD_TAGS_ALL,
+            }
+        }
+    }
+
     impl Convert<raw::git_otype> for ObjectType {
         fn convert(&self) -> raw::git_otype {
             match *self {
@@ -167,4 +178,40 @@ mod impls {
             }
         }
     }
+
+    impl Convert<raw::git_submodule_update_t> for SubmoduleUpdate {
+        fn convert(&self) -> raw::git_submodule_update_t {
+            match *self {
+                SubmoduleUpdate::Checkout => raw::GIT_SUBMODULE_UPDATE_CHECKOUT,
+                SubmoduleUpdate::Rebase => raw::GIT_SUBMODULE_UPDATE_REBASE,
+                SubmoduleUpdate::Merge => raw::GIT_SUBMODULE_UPDATE_MERGE,
+                SubmoduleUpdate::None => raw::GIT_SUBMODULE_UPDATE_NONE,
+                SubmoduleUpdate::Default => raw::GIT_SUBMODULE_UPDATE_DEFAULT,
+            }
+        }
+    }
+
+    impl Convert<raw::git_submodule_ignore_t> for SubmoduleIgnore {
+        fn convert(&self) -> raw::git_submodule_ignore_t {
+            match *self {
+                SubmoduleIgnore::None => raw::GIT_SUBMODULE_IGNORE_NONE,
+                SubmoduleIgnore::Untracked =>
+                    raw::GIT_SUBMODULE_IGNORE_UNTRACKED,
+                SubmoduleIgnore::Dirty => raw::GIT_SUBMODULE_IGNORE_DIRTY,
+                SubmoduleIgnore::All => raw::GIT_SUBMODULE_IGNORE_ALL,
+                SubmoduleIgnore::Default => raw::GIT_SUBMODULE_IGNORE_DEFAULT,
+            }
+        }
+    }
+
+    impl Convert<raw::git_submodule_recurse_t> for SubmoduleRecurse {
+        fn convert(&self) -> raw::git_submodule_recurse_t {
+            match *self {
+                SubmoduleRecurse::No => raw::GIT_SUBMODULE_RECURSE_NO,
+                SubmoduleRecurse::Yes => raw::GIT_SUBMODULE_RECURSE_YES,
+                SubmoduleRecurse::OnDemand =>
+                    raw::GIT_SUBMODULE_RECURSE_ONDEMAND,
+            }
+        }
+    }
 }
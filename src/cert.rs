@@ -0,0 +1,159 @@
+//! Certificate types which are passed to `RemoteCallbacks::certificate_check`.
+
+use std::kinds::marker;
+use std::mem;
+use std::raw as stdraw;
+
+use raw;
+
+/// A certificate for a remote connection, viewable as one of `CertHostkey` or
+/// `CertX509` currently.
+pub struct Cert<'a> {
+    raw: *mut raw::git_cert,
+    marker1: marker::ContravariantLifetime<'a>,
+    marker2: marker::NoSend,
+    marker3: marker::NoSync,
+}
+
+/// Hostkey information taken from libssh2
+///
+/// Only the MD5 and SHA-1 fingerprints of the key are available. libgit2
+/// 0.22 passes neither the SHA-256 fingerprint nor the key itself and its
+/// type, so they cannot be computed here.
+pub struct CertHostkey<'a> {
+    raw: *mut raw::git_cert_hostkey,
+    marker1: marker::ContravariantLifetime<'a>,
+    marker2: marker::NoSend,
+    marker3: marker::NoSync,
+}
+
+/// X.509 certificate information
+pub struct CertX509<'a> {
+    raw: *mut raw::git_cert_x509,
+    marker1: marker::ContravariantLifetime<'a>,
+    marker2: marker::NoSend,
+    marker3: marker::NoSync,
+}
+
+impl<'a> Cert<'a> {
+    /// Creates a new certificate from its raw component.
+    ///
+    /// This function is unsafe as there is no anchor for the returned lifetime
+    /// and the validity of the pointer cannot be guaranteed.
+    pub unsafe fn from_raw(raw: *mut raw::git_cert) -> Cert<'a> {
+        Cert {
+            raw: raw,
+            marker1: marker::ContravariantLifetime,
+            marker2: marker::NoSend,
+            marker3: marker::NoSync,
+        }
+    }
+
+    /// Attempt to view this certificate as an SSH hostkey.
+    ///
+    /// Returns `None` if this is not actually an SSH hostkey.
+    pub fn as_hostkey(&self) -> Option<CertHostkey> {
+        match unsafe { (*self.raw).cert_type } {
+            raw::GIT_CERT_HOSTKEY_LIBSSH2 => Some(CertHostkey {
+                raw: self.raw as *mut raw::git_cert_hostkey,
+                marker1: marker::ContravariantLifetime,
+                marker2: marker::NoSend,
+                marker3: marker::NoSync,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Attempt to view this certificate as an X.509 certificate.
+    ///
+    /// Returns `None` if this is not actually an X.509 certificate.
+    pub fn as_x509(&self) -> Option<CertX509> {
+        match unsafe { (*self.raw).cert_type } {
+            raw::GIT_CERT_X509 => Some(CertX509 {
+                raw: self.raw as *mut raw::git_cert_x509,
+                marker1: marker::ContravariantLifetime,
+                marker2: marker::NoSend,
+                marker3: marker::NoSync,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get access to the underlying raw certificate pointer.
+    pub fn raw(&self) -> *mut raw::git_cert { self.raw }
+}
+
+impl<'a> CertHostkey<'a> {
+    /// Returns the md5 hash of the hostkey, if available.
+    pub fn hash_md5(&self) -> Option<&[u8, ..16]> {
+        unsafe {
+            if (*self.raw).kind & raw::GIT_CERT_SSH_MD5 == 0 {
+                None
+            } else {
+                Some(&(*self.raw).hash_md5)
+            }
+        }
+    }
+
+    /// Returns the SHA-1 hash of the hostkey, if available.
+    pub fn hash_sha1(&self) -> Option<&[u8, ..20]> {
+        unsafe {
+            if (*self.raw).kind & raw::GIT_CERT_SSH_SHA1 == 0 {
+                None
+            } else {
+                Some(&(*self.raw).hash_sha1)
+            }
+        }
+    }
+}
+
+impl<'a> CertX509<'a> {
+    /// Return the X.509 certificate data as a DER-encoded byte array.
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            mem::transmute(stdraw::Slice {
+                data: (*self.raw).data as *const u8,
+                len: (*self.raw).len as uint,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use libc;
+    use raw;
+    use super::Cert;
+
+    #[test]
+    fn hostkey() {
+        let mut hostkey: raw::git_cert_hostkey = unsafe { mem::zeroed() };
+        hostkey.parent.cert_type = raw::GIT_CERT_HOSTKEY_LIBSSH2;
+        hostkey.kind = raw::GIT_CERT_SSH_SHA1;
+        hostkey.hash_sha1 = [1, ..20];
+
+        let cert = unsafe {
+            Cert::from_raw(&mut hostkey as *mut _ as *mut raw::git_cert)
+        };
+        assert!(cert.as_x509().is_none());
+        let hostkey = cert.as_hostkey().unwrap();
+        assert!(hostkey.hash_md5().is_none());
+        assert_eq!(hostkey.hash_sha1().unwrap().as_slice(), [1, ..20].as_slice());
+    }
+
+    #[test]
+    fn x509() {
+        let der = [0x30u8, 0x82, 0x01, 0x0a];
+        let mut x509: raw::git_cert_x509 = unsafe { mem::zeroed() };
+        x509.parent.cert_type = raw::GIT_CERT_X509;
+        x509.data = der.as_ptr() as *mut _;
+        x509.len = der.len() as libc::size_t;
+
+        let cert = unsafe {
+            Cert::from_raw(&mut x509 as *mut _ as *mut raw::git_cert)
+        };
+        assert!(cert.as_hostkey().is_none());
+        assert_eq!(cert.as_x509().unwrap().data(), der.as_slice());
+    }
+}
@@ -3,7 +3,16 @@ use std::iter::Range;
 use std::str;
 use libc;
 
-use {raw, Oid, Error, Signature, Tree, Time};
+use {raw, Buf, Oid, Error, Signature, Tree, Time, Verifier, MessageTrailers};
+use {Mailmap, sign};
+use build::CommitBuilder;
+
+// Headers which `with_changes` does not carry over as extra headers, either
+// because the builder writes them itself or because they would be invalid.
+static REWRITTEN_HEADERS: [&'static str, ..7] = [
+    "tree", "parent", "author", "committer", "encoding", "gpgsig",
+    "gpgsig-sha256",
+];
 
 /// A structure to represent a git [commit][1]
 ///
@@ -91,7 +100,7 @@ impl<'repo> Commit<'repo> {
     /// `None` will be returned if the encoding is not known
     pub fn message_encoding(&self) -> Option<&str> {
         let bytes = unsafe {
-            ::opt_bytes(self, raw::git_commit_message(&*self.raw))
+            ::opt_bytes(self, raw::git_commit_message_encoding(&*self.raw))
         };
         bytes.map(|b| str::from_utf8(b).unwrap())
     }
@@ -110,6 +119,16 @@ impl<'repo> Commit<'repo> {
         }
     }
 
+    /// Parse the trailers of the commit message, such as `Signed-off-by`.
+    ///
+    /// An error is returned if the message is not valid utf-8.
+    pub fn trailers(&self) -> Result<MessageTrailers, Error> {
+        match self.message_raw() {
+            Some(message) => ::message_trailers(message),
+            None => Err(Error::from_str("commit message is not valid utf-8")),
+        }
+    }
+
     /// Get the full raw text of the commit header.
     ///
     /// `None` will be returned if the message is not valid utf-8
@@ -191,13 +210,28 @@ impl<'repo> Commit<'repo> {
         }
     }
 
+    /// Get the author of this commit, using `mailmap` to map it to its
+    /// canonical identity.
+    pub fn author_with_mailmap(&self, mailmap: &Mailmap)
+                               -> Result<Signature<'static>, Error> {
+        mailmap.resolve_signature(&self.author())
+    }
+
+    /// Get the committer of this commit, using `mailmap` to map it to its
+    /// canonical identity.
+    pub fn committer_with_mailmap(&self, mailmap: &Mailmap)
+                                  -> Result<Signature<'static>, Error> {
+        mailmap.resolve_signature(&self.committer())
+    }
+
     /// Amend this existing commit with all non-`None` values
     ///
     /// This creates a new commit that is exactly the same as the old commit,
     /// except that any non-`None` values will be updated. The new commit has
     /// the same parents as the old commit.
     ///
-    /// For information about `update_ref`, see `new`.
+    /// For information about `update_ref`, see `Repository::commit`. If it
+    /// is `None` the new commit is only written to the object database.
     pub fn amend(&self,
                  update_ref: Option<&str>,
                  author: Option<&Signature>,
@@ -219,6 +253,81 @@ impl<'repo> Commit<'repo> {
         }
     }
 
+    /// Create a builder initialized with every field of this commit, to
+    /// create a rewritten copy of it.
+    ///
+    /// The tree, parents, author, committer, message, encoding and any extra
+    /// headers are carried over unchanged, except for signatures such as
+    /// `gpgsig` which would no longer be valid for the new commit. Calling
+    /// `create` on the returned builder without changing anything therefore
+    /// recreates this commit, minus its signature.
+    pub fn with_changes(&self) -> CommitBuilder {
+        let mut builder = CommitBuilder::new();
+        builder.tree_id(self.tree_id())
+               .parent_ids(self.parent_ids().collect::<Vec<_>>().as_slice())
+               .author(&self.author())
+               .committer(&self.committer())
+               .message_encoding(self.message_encoding())
+               .message_bytes(self.message_raw_bytes());
+
+        let mut headers: Vec<(&[u8], Vec<u8>)> = Vec::new();
+        for line in self.raw_header_bytes().split(|b| *b == b'\n') {
+            if line.len() == 0 { continue }
+            if line[0] == b' ' {
+                match headers.last_mut() {
+                    Some(&mut (_, ref mut value)) => {
+                        value.push(b'\n');
+                        value.push_all(line.slice_from(1));
+                    }
+                    None => {}
+                }
+                continue
+            }
+            let (name, value) = match line.position_elem(&b' ') {
+                Some(i) => (line.slice_to(i), line.slice_from(i + 1)),
+                None => (line, line.slice_from(line.len())),
+            };
+            headers.push((name, value.to_vec()));
+        }
+        for &(name, ref value) in headers.iter() {
+            let name = match str::from_utf8(name) {
+                Ok(name) => name,
+                Err(..) => continue,
+            };
+            if !REWRITTEN_HEADERS.contains(&name) {
+                builder.header_bytes(name, value.as_slice());
+            }
+        }
+        builder
+    }
+
+    /// Extract the signature of a signed commit.
+    ///
+    /// Returns the signature stored in the header `field`, `gpgsig` by
+    /// default, and the data which was signed, that is the commit without
+    /// that header. An error is returned if the commit is not signed.
+    pub fn extract_signature(&self, field: Option<&str>)
+                             -> Result<(Buf, Buf), Error> {
+        let content = try!(unsafe {
+            sign::read_object(raw::git_commit_owner(&*self.raw), self.id())
+        });
+        let field = field.unwrap_or("gpgsig");
+        match sign::commit_signature_split(content.as_slice(), field) {
+            Some((signature, data)) => {
+                Ok((try!(sign::buf(signature.as_slice())),
+                    try!(sign::buf(data.as_slice()))))
+            }
+            None => Err(Error::from_str("commit is not signed")),
+        }
+    }
+
+    /// Check the `gpgsig` signature of a signed commit with `verifier`.
+    pub fn verify_signature(&self, verifier: &mut Verifier)
+                            -> Result<bool, Error> {
+        let (signature, data) = try!(self.extract_signature(None));
+        verifier.verify(data.get(), signature.get())
+    }
+
     /// Get the specified parent of the commit.
     ///
     /// Use the `parents` iterator to return an iterator over all parents.
@@ -288,6 +397,8 @@ impl<'repo> Drop for Commit<'repo> {
 
 #[cfg(test)]
 mod tests {
+    use Signature;
+
     #[test]
     fn smoke() {
         let (_td, repo) = ::test::repo_init();
@@ -320,5 +431,104 @@ mod tests {
         let new_head = repo.find_commit(new_head).unwrap();
         assert_eq!(new_head.message(), Some("new message"));
     }
+
+    #[test]
+    fn signed() {
+        let (_td, repo) = ::test::repo_init();
+        let head = repo.head().unwrap();
+        let parent = repo.find_commit(head.target().unwrap()).unwrap();
+        assert!(parent.extract_signature(None).is_err());
+
+        let sig = repo.signature().unwrap();
+        let tree = parent.tree().unwrap();
+        let buf = repo.commit_create_buffer(&sig, &sig, "signed\n", &tree,
+                                            &[&parent]).unwrap();
+        assert!(buf.as_str().unwrap().starts_with("tree "));
+
+        let id = repo.commit_with_signer(&mut ::test::TestSigner, &sig, &sig,
+                                         "signed\n", &tree,
+                                         &[&parent]).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(parent.id()));
+        let commit = repo.find_commit(id).unwrap();
+        assert_eq!(commit.message(), Some("signed\n"));
+        let (signature, data) = commit.extract_signature(None).unwrap();
+        assert_eq!(data.get(), buf.get());
+        assert!(signature.as_str().unwrap()
+                         .starts_with("-----BEGIN PGP SIGNATURE-----"));
+        assert!(commit.verify_signature(&mut ::test::TestSigner).unwrap());
+        assert!(commit.extract_signature(Some("x-other")).is_err());
+        assert!(repo.commit_signed("bogus", "sig", None).is_err());
+
+        let forged = repo.commit_signed(buf.as_str().unwrap(), "bogus",
+                                        Some("gpgsig")).unwrap();
+        let forged = repo.find_commit(forged).unwrap();
+        assert!(!forged.verify_signature(&mut ::test::TestSigner).unwrap());
+    }
+
+    #[test]
+    fn trailers() {
+        let (_td, repo) = ::test::repo_init();
+        let head = repo.head().unwrap();
+        let commit = repo.find_commit(head.target().unwrap()).unwrap();
+        assert_eq!(commit.trailers().unwrap().len(), 0);
+
+        let sob = ("Signed-off-by", "name <email>");
+        let message = ::message_add_trailers(commit.message().unwrap(), &[sob])
+                          .unwrap();
+        let id = commit.with_changes().message(message.as_slice())
+                       .create(&repo).unwrap();
+        let commit = repo.find_commit(id).unwrap();
+        assert_eq!(commit.trailers().unwrap().values("Signed-off-by"),
+                   vec!["name <email>"]);
+    }
+
+    #[test]
+    fn with_changes() {
+        let (_td, repo) = ::test::repo_init();
+        let head = repo.head().unwrap();
+        let commit = repo.find_commit(head.target().unwrap()).unwrap();
+
+        let same = commit.with_changes().create(&repo).unwrap();
+        assert_eq!(same, commit.id());
+
+        let sig = Signature::new("other", "other@example.com", 1234, -90)
+                            .unwrap();
+        let id = commit.with_changes()
+                       .author(&sig)
+                       .message("reworded\n")
+                       .header("x-trailer", "one\ntwo")
+                       .create(&repo).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(commit.id()));
+        let new = repo.find_commit(id).unwrap();
+        assert_eq!(new.author().name(), Some("other"));
+        assert_eq!(new.author().when().offset_minutes(), -90);
+        assert_eq!(new.committer().name(), commit.committer().name());
+        assert_eq!(new.message(), Some("reworded\n"));
+        assert_eq!(new.tree_id(), commit.tree_id());
+        assert!(new.raw_header().unwrap().contains("x-trailer one\n two\n"));
+
+        let copy = new.with_changes().create(&repo).unwrap();
+        assert_eq!(copy, id);
+        assert!(new.with_changes().tree_id(id).create(&repo).is_err());
+        assert!(new.with_changes().parent_ids(&[new.tree_id()])
+                   .create(&repo).is_err());
+        let child = new.with_changes().parent_ids(&[id])
+                       .remove_header("x-trailer")
+                       .message_encoding(Some("ISO-8859-1"))
+                       .create(&repo).unwrap();
+        let child = repo.find_commit(child).unwrap();
+        assert_eq!(child.parent_id(0).unwrap(), id);
+        assert_eq!(child.message_encoding(), Some("ISO-8859-1"));
+        assert!(!child.raw_header().unwrap().contains("x-trailer"));
+
+        let signed = repo.commit_with_signer(&mut ::test::TestSigner, &sig,
+                                             &sig, "signed\n",
+                                             &commit.tree().unwrap(),
+                                             &[&commit]).unwrap();
+        let signed = repo.find_commit(signed).unwrap();
+        let unsigned = signed.with_changes().create(&repo).unwrap();
+        let unsigned = repo.find_commit(unsigned).unwrap();
+        assert!(unsigned.extract_signature(None).is_err());
+    }
 }
 
@@ -313,6 +313,35 @@ impl Config {
         Ok(())
     }
 
+    /// Set the value of a multivar config variable in the config file with the
+    /// highest level (usually the local one).
+    ///
+    /// All existing values matching the regular expression `regexp` are
+    /// replaced with `value`. If no values match, `value` is added as a new
+    /// entry, so a regexp which never matches (such as `$^`) appends a value.
+    pub fn set_multivar(&mut self, name: &str, regexp: &str, value: &str)
+                        -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_config_set_multivar(self.raw, name.to_c_str(),
+                                                   regexp.to_c_str(),
+                                                   value.to_c_str()));
+        }
+        Ok(())
+    }
+
+    /// Remove all values of a multivar config variable matching the regular
+    /// expression `regexp` from the config file with the highest level
+    /// (usually the local one).
+    pub fn remove_multivar(&mut self, name: &str, regexp: &str)
+                           -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_config_delete_multivar(self.raw,
+                                                      name.to_c_str(),
+                                                      regexp.to_c_str()));
+        }
+        Ok(())
+    }
+
     /// Create a snapshot of the configuration
     ///
     /// Create a snapshot of the current state of a configuration, which allows
@@ -438,6 +467,10 @@ mod tests {
         cfg.set_i32("foo.k2", 1).unwrap();
         cfg.set_i64("foo.k3", 2).unwrap();
         cfg.set_str("foo.k4", "bar").unwrap();
+        cfg.set_multivar("foo.k5", "$^", "a").unwrap();
+        cfg.set_multivar("foo.k5", "$^", "b").unwrap();
+        cfg.set_multivar("foo.k6", "$^", "c").unwrap();
+        cfg.remove_multivar("foo.k6", ".*").unwrap();
         cfg.snapshot().unwrap();
         drop(cfg);
 
@@ -446,6 +479,9 @@ mod tests {
         assert_eq!(cfg.get_i32("foo.k2").unwrap(), 1);
         assert_eq!(cfg.get_i64("foo.k3").unwrap(), 2);
         assert_eq!(cfg.get_str("foo.k4").unwrap(), "bar");
+        let k5 = cfg.entries(Some("foo.k5")).unwrap();
+        assert_eq!((&k5).count(), 2);
+        assert!(cfg.get_str("foo.k6").is_err());
 
         for entry in &cfg.entries(None).unwrap() {
             entry.name();
@@ -1,8 +1,8 @@
 use std::mem;
 use std::io::Command;
-use url::{mod, UrlParser};
+use url::{mod, Url, UrlParser};
 
-use {raw, Error, Config};
+use {raw, Error, Config, ConfigLevel};
 
 /// A structure to represent git credentials in libgit2.
 pub struct Cred {
@@ -10,14 +10,67 @@ pub struct Cred {
 }
 
 /// Management of the gitcredentials(7) interface.
+///
+/// A helper is configured with a list of credential stores, usually the
+/// `credential.helper` programs found in configuration, which are consulted in
+/// order by `execute` and are told the outcome of an authentication attempt
+/// through `approve` and `reject`.
 pub struct CredentialHelper {
     /// A public field representing the currently discovered username from
     /// configuration.
     pub username: Option<String>,
     protocol: Option<String>,
     host: Option<String>,
+    path: Option<String>,
+    url_username: Option<String>,
     url: String,
-    commands: Vec<String>,
+    use_http_path: bool,
+    stores: Vec<Box<CredentialStore + 'static>>,
+}
+
+/// A description of a credential as exchanged with credential helpers.
+///
+/// The fields correspond to the attributes of the gitcredentials(7) protocol.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct CredentialRequest {
+    /// The protocol over which the credential will be used, e.g. `https`.
+    pub protocol: Option<String>,
+    /// The remote hostname, including the port if one was specified.
+    pub host: Option<String>,
+    /// The path within the host, only present for http(s) urls when
+    /// `credential.useHttpPath` is set.
+    pub path: Option<String>,
+    /// The username, if known.
+    pub username: Option<String>,
+    /// The password, only present when storing or erasing a credential.
+    pub password: Option<String>,
+}
+
+/// A source and sink of credentials which can be plugged into a
+/// `CredentialHelper`.
+///
+/// Each configured `credential.helper` program is one store; custom
+/// implementations can be added with `CredentialHelper::add_store`, for
+/// example to keep credentials in process.
+pub trait CredentialStore {
+    /// Look up the username and password matching `request`.
+    ///
+    /// Either half may be returned on its own, in which case the next store is
+    /// consulted for the other half.
+    fn get(&self, request: &CredentialRequest)
+           -> (Option<String>, Option<String>);
+
+    /// Record that the credential in `request` was accepted.
+    fn store(&self, request: &CredentialRequest);
+
+    /// Forget the credential in `request` as it was rejected.
+    fn erase(&self, request: &CredentialRequest);
+}
+
+// A `credential.helper` program, invoked through the shell with the action to
+// perform appended.
+struct CommandStore {
+    command: String,
 }
 
 impl Cred {
@@ -92,6 +145,11 @@ impl Cred {
     /// The returned credential type will be a username/password credential if
     /// successful.
     ///
+    /// The helpers are not told whether the credential was accepted. To have
+    /// them store or erase it, use a `CredentialHelper` directly and call its
+    /// `approve` or `reject` method once the operation using the credential
+    /// has finished.
+    ///
     /// [1]: https://www.kernel.org/pub/software/scm/git/docs/gitcredentials.html
     pub fn credential_helper(config: &Config,
                              url: &str,
@@ -145,122 +203,177 @@ impl CredentialHelper {
         let mut ret = CredentialHelper {
             protocol: None,
             host: None,
+            path: None,
+            url_username: None,
             username: None,
             url: url.to_string(),
-            commands: Vec::new(),
+            use_http_path: false,
+            stores: Vec::new(),
         };
 
-        // Parse out the (protocol, host) if one is available
-        let parsed_url = UrlParser::new().scheme_type_mapper(mapper).parse(url);
-        match parsed_url {
+        // Parse out the (protocol, host, path) if one is available
+        match parse_url(url) {
             Ok(url) => {
-                match url.host() {
-                    Some(&url::Host::Domain(ref s)) => ret.host = Some(s.clone()),
-                    _ => {}
-                }
+                ret.host = url_host(&url);
+                ret.path = url_path(&url);
+                ret.url_username = url.username().and_then(|s| {
+                    if s.is_empty() {None} else {Some(s.to_string())}
+                });
                 ret.protocol = Some(url.scheme)
             }
             Err(..) => {}
         };
-        return ret;
-
-        fn mapper(s: &str) -> url::SchemeType {
-            match s {
-                "git" => url::SchemeType::Relative(9418),
-                "ssh" => url::SchemeType::Relative(22),
-                s => url::whatwg_scheme_type_mapper(s),
-            }
-        }
+        ret.username = ret.url_username.clone();
+        ret
     }
 
     /// Set the username that this credential helper will query with.
     ///
-    /// By default the username is `None`.
+    /// By default the username is the one in the url, if any.
     pub fn username(&mut self, username: Option<&str>) -> &mut CredentialHelper {
         self.username = username.map(|s| s.to_string());
         self
     }
 
+    /// Add a store to the end of the list of stores consulted by this helper.
+    ///
+    /// Stores are queried in the order they were added, with the
+    /// `credential.helper` programs discovered by `config` added in
+    /// configuration order.
+    pub fn add_store<S>(&mut self, store: S) -> &mut CredentialHelper
+                        where S: CredentialStore + 'static
+    {
+        self.stores.push(box store as Box<CredentialStore + 'static>);
+        self
+    }
+
     /// Query the specified configuration object to discover commands to
     /// execute, usernames to query, etc.
+    ///
+    /// Both `credential.*` and `credential.<url>.*` keys are consulted, the
+    /// latter only if `<url>` matches the url of this helper. Matching keys are
+    /// applied in configuration order, from the system level up to the local
+    /// level: single-valued keys take the last value seen while every `helper`
+    /// is appended to the list of stores, an empty `helper` clearing the list.
+    ///
+    /// see http://git-scm.com/docs/gitcredentials.html#_configuration_options
     pub fn config(&mut self, config: &Config) -> &mut CredentialHelper {
-        // Figure out the configured username/helper program.
-        //
-        // see http://git-scm.com/docs/gitcredentials.html#_configuration_options
-        //
-        // TODO: implement useHttpPath
-        if self.username.is_none() {
-            self.config_username(config);
+        let mut vars = Vec::new();
+        match config.entries(Some(r"^credential\.")) {
+            Ok(entries) => {
+                for entry in &entries {
+                    match (entry.name(), entry.value()) {
+                        (Some(name), Some(value)) => {
+                            vars.push((level_rank(entry.level()),
+                                       name.to_string(), value.to_string()));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(..) => {}
         }
-        self.config_helper(config);
-        self
-    }
+        vars.sort_by(|&(a, _, _), &(b, _, _)| a.cmp(&b));
 
-    // Configure the queried username from `config`
-    fn config_username(&mut self, config: &Config) {
-        let key = self.exact_key("username");
-        self.username = config.get_str(key.as_slice()).ok().or_else(|| {
-            self.url_key("username").and_then(|s| {
-                config.get_str(s.as_slice()).ok()
-            })
-        }).or_else(|| {
-            config.get_str("credential.username").ok()
-        }).map(|s| s.to_string());
-    }
-
-    // Discover all `helper` directives from `config`
-    fn config_helper(&mut self, config: &Config) {
-        let exact = config.get_str(self.exact_key("helper").as_slice());
-        self.add_command(exact.ok());
-        match self.url_key("helper") {
-            Some(key) => {
-                let url = config.get_str(key.as_slice());
-                self.add_command(url.ok());
+        let mut username = None;
+        for &(_, ref name, ref value) in vars.iter() {
+            let rest = name.as_slice().slice_from("credential.".len());
+            let var = match rest.rfind('.') {
+                Some(i) => {
+                    if !self.url_matches(rest.slice_to(i)) { continue }
+                    rest.slice_from(i + 1)
+                }
+                None => rest,
+            };
+            match var {
+                "helper" => self.add_command(value.as_slice()),
+                "username" => username = Some(value.clone()),
+                "usehttppath" => {
+                    self.use_http_path = config.get_bool(name.as_slice())
+                                               .unwrap_or(false);
+                }
+                _ => {}
             }
-            None => {}
         }
-        let global = config.get_str("credential.helper");
-        self.add_command(global.ok());
+        if self.username.is_none() {
+            self.username = username;
+        }
+        self
     }
 
     // Add a `helper` configured command to the list of commands to execute.
     //
     // see https://www.kernel.org/pub/software/scm/git/docs/technical
     //                           /api-credentials.html#_credential_helpers
-    fn add_command(&mut self, cmd: Option<&str>) {
-        let cmd = match cmd { Some(s) => s, None => return };
-        if cmd.starts_with("!") {
-            self.commands.push(cmd.slice_from(1).to_string());
+    fn add_command(&mut self, cmd: &str) {
+        let command = if cmd.is_empty() {
+            self.stores.clear();
+            return
+        } else if cmd.starts_with("!") {
+            cmd.slice_from(1).to_string()
         } else if cmd.starts_with("/") || cmd.starts_with("\\") ||
                   cmd.slice_from(1).starts_with(":\\") {
-            self.commands.push(format!("\"{}\"", cmd));
+            format!("\"{}\"", cmd)
         } else {
-            self.commands.push(format!("git credential-{}", cmd));
-        }
+            format!("git credential-{}", cmd)
+        };
+        self.add_store(CommandStore { command: command });
     }
 
-    fn exact_key(&self, name: &str) -> String {
-        format!("credential.{}.{}", self.url, name)
+    // Test whether the `<url>` of a `credential.<url>.*` key applies to this
+    // helper's url.
+    //
+    // The protocol and host must be the same, while the username and path are
+    // only compared if the pattern specifies them.
+    fn url_matches(&self, pattern: &str) -> bool {
+        if pattern == self.url.as_slice() { return true }
+        let url = match parse_url(pattern) { Ok(url) => url, Err(..) => return false };
+        if Some(&url.scheme) != self.protocol.as_ref() { return false }
+        if url_host(&url) != self.host { return false }
+        match url.username() {
+            Some(user) if !user.is_empty() => {
+                if Some(user) != self.url_username.as_ref().map(|s| s.as_slice()) {
+                    return false
+                }
+            }
+            _ => {}
+        }
+        match url_path(&url) {
+            Some(path) => self.path == Some(path),
+            None => true,
+        }
     }
 
-    fn url_key(&self, name: &str) -> Option<String> {
-        match (&self.host, &self.protocol) {
-            (&Some(ref host), &Some(ref protocol)) => {
-                Some(format!("credential.{}://{}.{}", protocol, host, name))
-            }
-            _ => None
+    // Describe the credential being requested, omitting the path of http(s)
+    // urls unless `credential.useHttpPath` is set.
+    fn request(&self, username: Option<String>,
+               password: Option<String>) -> CredentialRequest {
+        let http = match self.protocol.as_ref().map(|s| s.as_slice()) {
+            Some("http") | Some("https") => true,
+            _ => false,
+        };
+        CredentialRequest {
+            protocol: self.protocol.clone(),
+            host: self.host.clone(),
+            path: if http && !self.use_http_path {None} else {self.path.clone()},
+            username: username,
+            password: password,
         }
     }
 
     /// Execute this helper, attempting to discover a username/password pair.
     ///
+    /// Stores are consulted in order, each being passed the username found so
+    /// far, until both a username and a password are known.
+    ///
     /// All I/O errors are ignored, (to match git behavior), and this function
     /// only succeeds if both a username and a password were found
     pub fn execute(&self) -> Option<(String, String)> {
         let mut username = self.username.clone();
         let mut password = None;
-        for cmd in self.commands.iter() {
-            let (u, p) = self.execute_cmd(cmd.as_slice(), &username);
+        for store in self.stores.iter() {
+            let request = self.request(username.clone(), None);
+            let (u, p) = store.get(&request);
             if u.is_some() && username.is_none() {
                 username = u;
             }
@@ -276,68 +389,161 @@ impl CredentialHelper {
         }
     }
 
-    // Execute the given `cmd`, providing the appropriate variables on stdin and
-    // then afterwards parsing the output into the username/password on stdout.
-    fn execute_cmd(&self, cmd: &str, username: &Option<String>)
-                   -> (Option<String>, Option<String>) {
-        macro_rules! my_try( ($e:expr) => (
-            match $e { Ok(e) => e, Err(..) => return (None, None) }
-        ) );
+    /// Inform every store that authenticating with the given username and
+    /// password succeeded, so that it may be remembered.
+    ///
+    /// This is the `store` action of gitcredentials(7). libgit2 does not
+    /// report whether a credential it was given was accepted, so this is
+    /// never called automatically: call it once the fetch or push which used
+    /// the credential from `execute` has succeeded.
+    pub fn approve(&self, username: &str, password: &str) {
+        let request = self.request(Some(username.to_string()),
+                                   Some(password.to_string()));
+        for store in self.stores.iter() {
+            store.store(&request);
+        }
+    }
+
+    /// Inform every store that authenticating with the given username and
+    /// password failed, so that it may be forgotten.
+    ///
+    /// This is the `erase` action of gitcredentials(7). Like `approve`, it is
+    /// never called automatically: call it when the operation which used the
+    /// credential failed to authenticate.
+    pub fn reject(&self, username: &str, password: &str) {
+        let request = self.request(Some(username.to_string()),
+                                   Some(password.to_string()));
+        for store in self.stores.iter() {
+            store.erase(&request);
+        }
+    }
+}
 
-        let mut p = my_try!(Command::new("sh").arg("-c")
-                                              .arg(format!("{} get", cmd))
-                                              .spawn());
+impl CommandStore {
+    // Execute the command with the given `action`, providing the request on
+    // stdin and returning the output on stdout if the command succeeded.
+    fn run(&self, action: &str, request: &CredentialRequest) -> Option<Vec<u8>> {
+        let mut p = match Command::new("sh").arg("-c")
+                                           .arg(format!("{} {}", self.command,
+                                                        action))
+                                           .spawn() {
+            Ok(p) => p,
+            Err(..) => return None,
+        };
         // Ignore write errors as the command may not actually be listening for
         // stdin
         {
             let stdin = p.stdin.as_mut().unwrap();
-            match self.protocol {
-                Some(ref p) => { let _ = writeln!(stdin, "protocol={}", p); }
-                None => {}
-            }
-            match self.host {
-                Some(ref p) => { let _ = writeln!(stdin, "host={}", p); }
-                None => {}
-            }
-            match *username {
-                Some(ref p) => { let _ = writeln!(stdin, "username={}", p); }
-                None => {}
+            let attrs = [("protocol", &request.protocol),
+                         ("host", &request.host),
+                         ("path", &request.path),
+                         ("username", &request.username),
+                         ("password", &request.password)];
+            for &(key, value) in attrs.iter() {
+                match *value {
+                    Some(ref v) => { let _ = writeln!(stdin, "{}={}", key, v); }
+                    None => {}
+                }
             }
         }
-        let output = my_try!(p.wait_with_output());
-        if !output.status.success() { return (None, None) }
-        return self.parse_output(output.output)
+        let output = match p.wait_with_output() {
+            Ok(output) => output,
+            Err(..) => return None,
+        };
+        if output.status.success() {Some(output.output)} else {None}
     }
+}
 
-    // Parse the output of a command into the username/password found
-    fn parse_output(&self, output: Vec<u8>) -> (Option<String>, Option<String>) {
-        // Parse the output of the command, looking for username/password
-        let mut username = None;
-        let mut password = None;
-        for line in output.as_slice().split(|t| *t == b'\n') {
-            let mut parts = line.splitn(1, |t| *t == b'=');
-            let key = parts.next().unwrap();
-            let value = match parts.next() { Some(s) => s, None => continue };
-            let value = match String::from_utf8(value.to_vec()) {
-                Ok(s) => s,
-                Err(..) => continue,
-            };
-            match key {
-                b"username" => username = Some(value),
-                b"password" => password = Some(value),
-                _ => {}
-            }
+impl CredentialStore for CommandStore {
+    fn get(&self, request: &CredentialRequest)
+           -> (Option<String>, Option<String>) {
+        match self.run("get", request) {
+            Some(output) => parse_output(output),
+            None => (None, None),
         }
-        (username, password)
+    }
+
+    fn store(&self, request: &CredentialRequest) {
+        self.run("store", request);
+    }
+
+    fn erase(&self, request: &CredentialRequest) {
+        self.run("erase", request);
+    }
+}
+
+// Parse the output of a command into the username/password found
+fn parse_output(output: Vec<u8>) -> (Option<String>, Option<String>) {
+    // Parse the output of the command, looking for username/password
+    let mut username = None;
+    let mut password = None;
+    for line in output.as_slice().split(|t| *t == b'\n') {
+        let mut parts = line.splitn(1, |t| *t == b'=');
+        let key = parts.next().unwrap();
+        let value = match parts.next() { Some(s) => s, None => continue };
+        let value = match String::from_utf8(value.to_vec()) {
+            Ok(s) => s,
+            Err(..) => continue,
+        };
+        match key {
+            b"username" => username = Some(value),
+            b"password" => password = Some(value),
+            _ => {}
+        }
+    }
+    (username, password)
+}
+
+fn parse_url(url: &str) -> url::ParseResult<Url> {
+    return UrlParser::new().scheme_type_mapper(mapper).parse(url);
+
+    fn mapper(s: &str) -> url::SchemeType {
+        match s {
+            "git" => url::SchemeType::Relative(9418),
+            "ssh" => url::SchemeType::Relative(22),
+            s => url::whatwg_scheme_type_mapper(s),
+        }
+    }
+}
+
+fn url_host(url: &Url) -> Option<String> {
+    let host = match url.host() {
+        Some(&url::Host::Domain(ref s)) => s.clone(),
+        _ => return None,
+    };
+    match url.port() {
+        Some(port) => Some(format!("{}:{}", host, port)),
+        None => Some(host),
+    }
+}
+
+fn url_path(url: &Url) -> Option<String> {
+    url.path().map(|p| p.connect("/")).and_then(|p| {
+        let p = p.as_slice().trim_chars('/');
+        if p.is_empty() {None} else {Some(p.to_string())}
+    })
+}
+
+fn level_rank(level: ConfigLevel) -> uint {
+    match level {
+        ConfigLevel::System => 0,
+        ConfigLevel::XDG => 1,
+        ConfigLevel::Global => 2,
+        ConfigLevel::Local => 3,
+        ConfigLevel::App => 4,
+        ConfigLevel::Highest => 5,
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::cell::RefCell;
     use std::io::{mod, TempDir, File, fs};
     use std::os;
+    use std::rc::Rc;
 
     use {Cred, Config, CredentialHelper, ConfigLevel};
+    use {CredentialRequest, CredentialStore};
 
     macro_rules! cfg( ($($k:expr => $v:expr),*) => ({
         let td = TempDir::new("git2-rs").unwrap();
@@ -347,6 +553,32 @@ mod test {
         cfg
     }) );
 
+    // An in-process store which records every request made of it.
+    struct MemoryStore {
+        log: Rc<RefCell<Vec<CredentialRequest>>>,
+        username: Option<String>,
+        password: Option<String>,
+    }
+
+    impl CredentialStore for MemoryStore {
+        fn get(&self, request: &CredentialRequest)
+               -> (Option<String>, Option<String>) {
+            self.log.borrow_mut().push(request.clone());
+            (self.username.clone(), self.password.clone())
+        }
+        fn store(&self, _request: &CredentialRequest) {}
+        fn erase(&self, _request: &CredentialRequest) {}
+    }
+
+    fn memory_store(log: &Rc<RefCell<Vec<CredentialRequest>>>,
+                    password: Option<&str>) -> MemoryStore {
+        MemoryStore {
+            log: log.clone(),
+            username: Some("u".to_string()),
+            password: password.map(|s| s.to_string()),
+        }
+    }
+
     #[test]
     fn smoke() {
         Cred::default().unwrap();
@@ -432,4 +664,90 @@ echo username=c
         assert_eq!(u.as_slice(), "c");
         assert_eq!(p.as_slice(), "b");
     }
+
+    #[test]
+    fn credential_helper_store_erase() {
+        let td = TempDir::new("git2-rs").unwrap();
+        let out = td.path().join("out");
+        let helper = format!("!f() {{ test \"$1\" = get || cat >> {}; }}; f",
+                             out.display());
+        let cfg = cfg! {
+            "credential.helper" => helper.as_slice()
+        };
+        let mut helper = CredentialHelper::new("https://example.com/foo/bar");
+        helper.config(&cfg);
+        assert!(helper.execute().is_none());
+        helper.approve("a", "b");
+        helper.reject("a", "c");
+        let contents = File::open(&out).read_to_string().unwrap();
+        assert_eq!(contents.as_slice(), "protocol=https\nhost=example.com\n\
+                                         username=a\npassword=b\n\
+                                         protocol=https\nhost=example.com\n\
+                                         username=a\npassword=c\n");
+    }
+
+    #[test]
+    fn credential_helper_use_http_path() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let cfg = cfg! {};
+        CredentialHelper::new("https://example.com/foo/bar.git")
+                         .config(&cfg)
+                         .add_store(memory_store(&log, Some("p")))
+                         .execute().unwrap();
+        assert_eq!(log.borrow()[0].path, None);
+
+        let cfg = cfg! {
+            "credential.https://example.com.usehttppath" => "true"
+        };
+        let (u, p) = CredentialHelper::new("https://example.com/foo/bar.git")
+                                      .config(&cfg)
+                                      .add_store(memory_store(&log, None))
+                                      .add_store(memory_store(&log, Some("p")))
+                                      .execute().unwrap();
+        assert_eq!(u.as_slice(), "u");
+        assert_eq!(p.as_slice(), "p");
+        let log = log.borrow();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[1], CredentialRequest {
+            protocol: Some("https".to_string()),
+            host: Some("example.com".to_string()),
+            path: Some("foo/bar.git".to_string()),
+            username: None,
+            password: None,
+        });
+        assert_eq!(log[2].username, Some("u".to_string()));
+    }
+
+    #[test]
+    fn credential_helper_url_matching() {
+        let cfg = cfg! {
+            "credential.https://example.com/other.helper" =>
+                    "!f() { echo username=x; }; f",
+            "credential.http://example.com.helper" =>
+                    "!f() { echo username=y; }; f",
+            "credential.https://example.com/foo/bar.username" => "c",
+            "credential.helper" => "!f() { echo username=a; echo password=b; }; f"
+        };
+        let (u, p) = CredentialHelper::new("https://example.com/foo/bar")
+                                      .config(&cfg)
+                                      .execute().unwrap();
+        assert_eq!(u.as_slice(), "c");
+        assert_eq!(p.as_slice(), "b");
+    }
+
+    #[test]
+    fn credential_helper_chain_reset() {
+        let mut cfg = cfg! {};
+        cfg.set_multivar("credential.helper", "$^",
+                         "!f() { echo username=a; echo password=b; }; f").unwrap();
+        cfg.set_multivar("credential.helper", "$^", "").unwrap();
+        cfg.set_multivar("credential.helper", "$^",
+                         "!f() { echo password=d; }; f").unwrap();
+        let (u, p) = CredentialHelper::new("https://example.com/foo/bar")
+                                      .username(Some("c"))
+                                      .config(&cfg)
+                                      .execute().unwrap();
+        assert_eq!(u.as_slice(), "c");
+        assert_eq!(p.as_slice(), "d");
+    }
 }
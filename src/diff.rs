@@ -229,6 +229,22 @@ impl Diff {
         Ok(())
     }
 
+    /// Transform this diff, marking file renames.
+    ///
+    /// Pairs of deleted and added files which are similar enough, by
+    /// libgit2's default thresholds, are replaced with a single `Renamed`
+    /// delta.
+    pub fn find_renames(&mut self) -> Result<(), Error> {
+        unsafe {
+            let mut opts: raw::git_diff_find_options = mem::zeroed();
+            try_call!(raw::git_diff_find_init_options(&mut opts,
+                                            raw::GIT_DIFF_FIND_OPTIONS_VERSION));
+            opts.flags = raw::GIT_DIFF_FIND_RENAMES;
+            try_call!(raw::git_diff_find_similar(self.raw, &opts));
+        }
+        Ok(())
+    }
+
     /// Returns an iterator over the deltas in this diff.
     pub fn deltas(&self) -> Deltas {
         let num_deltas = unsafe { raw::git_diff_num_deltas(&*self.raw) };
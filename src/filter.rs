@@ -0,0 +1,436 @@
+use std::c_str::CString;
+use std::kinds::marker;
+use std::mem;
+use std::raw as stdraw;
+use std::str;
+use libc::{c_char, c_int, c_void, size_t};
+
+use {raw, panic, Blob, Buf, Error, Oid, Repository};
+
+/// The direction in which content is being filtered.
+#[deriving(PartialEq, Eq, Clone, Show, Copy)]
+pub enum FilterMode {
+    /// Content is being checked out from the object database into the working
+    /// directory.
+    Smudge,
+    /// Content is being added from the working directory to the object
+    /// database.
+    Clean,
+}
+
+/// The list of filters which apply to a path when its content is moved in
+/// one direction between the working directory and the object database.
+///
+/// Filter lists are loaded with `Repository::filter_list` and contain the
+/// built-in `crlf` and `ident` filters as well as any filter registered with
+/// `filter_register` whose attributes match the path.
+///
+/// libgit2 0.22 cannot report which filters a list contains, so there is no
+/// way to check whether a particular filter will be applied.
+pub struct FilterList<'repo> {
+    raw: *mut raw::git_filter_list,
+    marker1: marker::ContravariantLifetime<'repo>,
+    marker2: marker::NoSend,
+    marker3: marker::NoSync,
+}
+
+/// Information about the content being filtered, passed to a `Filter`.
+pub struct FilterSource<'a> {
+    raw: *const raw::git_filter_source,
+    marker: marker::ContravariantLifetime<'a>,
+}
+
+/// A content filter implemented in Rust, such as a driver for the
+/// `filter=` attribute which stores large files outside of the repository.
+///
+/// Filters are registered globally with `filter_register`, and are invoked
+/// from any thread on which content is checked out or added to the index, so
+/// they must be both `Send` and `Sync`.
+pub trait Filter: Send + Sync {
+    /// Decide whether the filter applies to the content described by `src`.
+    ///
+    /// This is only called for paths whose attributes match those the filter
+    /// was registered with. Returning `Ok(false)` skips the filter for this
+    /// content. By default the filter always applies.
+    fn check(&self, _src: &FilterSource) -> Result<bool, Error> { Ok(true) }
+
+    /// Transform content from the working directory into the form stored in
+    /// the object database.
+    ///
+    /// Returning `Ok(None)` leaves the content unchanged, which is also the
+    /// default.
+    fn clean(&self, _src: &FilterSource, _input: &[u8])
+             -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    /// Transform content from the object database into the form written to
+    /// the working directory.
+    ///
+    /// Returning `Ok(None)` leaves the content unchanged, which is also the
+    /// default.
+    fn smudge(&self, _src: &FilterSource, _input: &[u8])
+              -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+// The structure registered with libgit2. The raw filter must come first so a
+// `*mut git_filter` handed back to the callbacks can be cast to this type.
+#[repr(C)]
+struct FilterData {
+    raw: raw::git_filter,
+    _attributes: CString,
+    filter: Box<Filter + 'static>,
+}
+
+/// Register a filter under `name` so it runs for all paths matching
+/// `attributes`.
+///
+/// `attributes` is a whitespace separated list of the attributes the filter
+/// is interested in. A name alone requires the attribute to be set, and
+/// `name=value` requires it to have that value, so a driver for
+/// `filter=lfs` in `.gitattributes` is registered with `"filter=lfs"`.
+///
+/// Filters are run in order of increasing `priority` when cleaning and in
+/// reverse order when smudging. The built-in `crlf` and `ident` filters have
+/// priorities 0 and 100, and filter drivers conventionally use 200.
+///
+/// An error is returned if a filter is already registered under `name`.
+pub fn filter_register<F>(name: &str, attributes: &str, priority: i32,
+                          filter: F) -> Result<(), Error>
+                          where F: Filter + 'static {
+    ::init();
+    let attributes = attributes.to_c_str();
+    let mut data = box FilterData {
+        raw: raw::git_filter {
+            version: raw::GIT_FILTER_VERSION,
+            attributes: attributes.as_ptr(),
+            initialize: Some(filter_initialize as raw::git_filter_init_fn),
+            shutdown: None,
+            check: Some(filter_check as raw::git_filter_check_fn),
+            apply: Some(filter_apply as raw::git_filter_apply_fn),
+            cleanup: None,
+        },
+        _attributes: attributes,
+        filter: box filter as Box<Filter + 'static>,
+    };
+    unsafe {
+        try_call!(raw::git_filter_register(name.to_c_str(), &mut data.raw,
+                                           priority as c_int));
+        mem::forget(data);
+    }
+    Ok(())
+}
+
+/// Unregister a filter previously registered with `filter_register`.
+///
+/// The filter must not be part of any `FilterList` which is still alive. An
+/// error is returned if no filter was registered from Rust under `name`; the
+/// built-in filters cannot be unregistered.
+pub fn filter_unregister(name: &str) -> Result<(), Error> {
+    ::init();
+    let name = name.to_c_str();
+    unsafe {
+        let filter = raw::git_filter_lookup(name.as_ptr());
+        let ours = !filter.is_null() && match (*filter).initialize {
+            Some(f) => f as uint == filter_initialize as uint,
+            None => false,
+        };
+        if !ours {
+            return Err(Error::from_str("no filter registered with that name"))
+        }
+        try_call!(raw::git_filter_unregister(name));
+        let _data: Box<FilterData> = mem::transmute(filter);
+    }
+    Ok(())
+}
+
+impl<'repo> FilterList<'repo> {
+    /// Creates a new filter list from its raw component.
+    ///
+    /// This method is unsafe as there is no guarantee that `raw` is a valid
+    /// pointer. A null pointer is a valid, empty, filter list.
+    pub unsafe fn from_raw(raw: *mut raw::git_filter_list) -> FilterList<'repo> {
+        FilterList {
+            raw: raw,
+            marker1: marker::ContravariantLifetime,
+            marker2: marker::NoSend,
+            marker3: marker::NoSync,
+        }
+    }
+
+    /// Get access to the underlying raw pointer.
+    pub fn raw(&self) -> *mut raw::git_filter_list { self.raw }
+
+    /// Returns the number of filters in this list.
+    pub fn len(&self) -> uint {
+        unsafe { raw::git_filter_list_length(self.raw as *const _) as uint }
+    }
+
+    /// Apply the filters to a buffer of content.
+    pub fn apply_to_buffer(&self, data: &[u8]) -> Result<Buf, Error> {
+        let mut ret = Buf::new();
+        // A buffer with no allocated size is never written to or freed.
+        let mut input = raw::git_buf {
+            ptr: data.as_ptr() as *mut c_char,
+            asize: 0,
+            size: data.len() as size_t,
+        };
+        unsafe {
+            try_call_panic!(raw::git_filter_list_apply_to_data(ret.raw(),
+                                                               self.raw,
+                                                               &mut input));
+        }
+        Ok(ret)
+    }
+
+    /// Apply the filters to the contents of a file on disk.
+    ///
+    /// A relative `path` is taken relative to the working directory of
+    /// `repo`.
+    pub fn apply_to_file(&self, repo: &Repository, path: &Path)
+                         -> Result<Buf, Error> {
+        let mut ret = Buf::new();
+        let path = path.to_c_str();
+        unsafe {
+            try_call_panic!(raw::git_filter_list_apply_to_file(ret.raw(),
+                                                               self.raw,
+                                                               repo.raw(),
+                                                               path));
+        }
+        Ok(ret)
+    }
+
+    /// Apply the filters to the contents of a blob.
+    pub fn apply_to_blob(&self, blob: &Blob) -> Result<Buf, Error> {
+        let mut ret = Buf::new();
+        unsafe {
+            try_call_panic!(raw::git_filter_list_apply_to_blob(ret.raw(),
+                                                               self.raw,
+                                                               blob.raw()));
+        }
+        Ok(ret)
+    }
+}
+
+#[unsafe_destructor]
+impl<'repo> Drop for FilterList<'repo> {
+    fn drop(&mut self) {
+        unsafe { raw::git_filter_list_free(self.raw) }
+    }
+}
+
+impl<'a> FilterSource<'a> {
+    /// Creates a new filter source from its raw component.
+    ///
+    /// This method is unsafe as there is no guarantee that `raw` is a valid
+    /// pointer.
+    pub unsafe fn from_raw(raw: *const raw::git_filter_source)
+                           -> FilterSource<'a> {
+        FilterSource { raw: raw, marker: marker::ContravariantLifetime }
+    }
+
+    /// Get the path of the file being filtered, relative to the working
+    /// directory.
+    ///
+    /// Returns `None` if the path is not valid utf-8.
+    pub fn path(&self) -> Option<&str> {
+        str::from_utf8(self.path_bytes()).ok()
+    }
+
+    /// Get the path of the file being filtered, in bytes.
+    pub fn path_bytes(&self) -> &[u8] {
+        unsafe {
+            ::opt_bytes(self, raw::git_filter_source_path(self.raw)).unwrap()
+        }
+    }
+
+    /// Get the direction in which the content is being filtered.
+    pub fn mode(&self) -> FilterMode {
+        match unsafe { raw::git_filter_source_mode(self.raw) } {
+            raw::GIT_FILTER_TO_ODB => FilterMode::Clean,
+            _ => FilterMode::Smudge,
+        }
+    }
+
+    /// Get the id of the blob being filtered, if it is known.
+    pub fn id(&self) -> Option<Oid> {
+        unsafe {
+            let ptr = raw::git_filter_source_id(self.raw);
+            if ptr.is_null() { None } else { Some(Oid::from_raw(ptr)) }
+        }
+    }
+}
+
+impl FilterMode {
+    /// Convert this mode to its raw representation.
+    pub fn raw(&self) -> raw::git_filter_mode_t {
+        match *self {
+            FilterMode::Smudge => raw::GIT_FILTER_SMUDGE,
+            FilterMode::Clean => raw::GIT_FILTER_CLEAN,
+        }
+    }
+}
+
+// Report an error returned by a filter back to libgit2.
+unsafe fn filter_error(e: Error) -> c_int {
+    raw::giterr_set_str(raw::GITERR_FILTER, e.message().to_c_str().as_ptr());
+    e.raw_code() as c_int
+}
+
+extern fn filter_initialize(_filter: *mut raw::git_filter) -> c_int { 0 }
+
+extern fn filter_check(filter: *mut raw::git_filter,
+                       _payload: *mut *mut c_void,
+                       src: *const raw::git_filter_source,
+                       _attr_values: *mut *const c_char) -> c_int {
+    unsafe {
+        let data = &*(filter as *const FilterData);
+        let src = FilterSource::from_raw(src);
+        match panic::wrap(|| data.filter.check(&src)) {
+            Some(Ok(true)) => 0,
+            Some(Ok(false)) => raw::GIT_PASSTHROUGH as c_int,
+            Some(Err(e)) => filter_error(e),
+            None => -1,
+        }
+    }
+}
+
+extern fn filter_apply(filter: *mut raw::git_filter,
+                       _payload: *mut *mut c_void,
+                       to: *mut raw::git_buf,
+                       from: *const raw::git_buf,
+                       src: *const raw::git_filter_source) -> c_int {
+    unsafe {
+        let data = &*(filter as *const FilterData);
+        let src = FilterSource::from_raw(src);
+        let input: &[u8] = mem::transmute(stdraw::Slice {
+            data: (*from).ptr as *const u8,
+            len: (*from).size as uint,
+        });
+        let ret = panic::wrap(|| {
+            match src.mode() {
+                FilterMode::Clean => data.filter.clean(&src, input),
+                FilterMode::Smudge => data.filter.smudge(&src, input),
+            }
+        });
+        match ret {
+            Some(Ok(Some(output))) => {
+                raw::git_buf_set(to, output.as_ptr() as *const c_void,
+                                 output.len() as size_t)
+            }
+            Some(Ok(None)) => raw::GIT_PASSTHROUGH as c_int,
+            Some(Err(e)) => filter_error(e),
+            None => -1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::File;
+    use {Error, Filter, FilterSource, FilterMode, FILTER_DEFAULT};
+    use {filter_register, filter_unregister};
+
+    // Swaps the case of ascii letters both ways, so a round-trip through
+    // clean and smudge gives back the original content.
+    struct SwapCase;
+
+    fn swap_case(input: &[u8]) -> Vec<u8> {
+        input.iter().map(|&b| {
+            match b {
+                b'a'...b'z' | b'A'...b'Z' => b ^ 0x20,
+                _ => b,
+            }
+        }).collect()
+    }
+
+    impl Filter for SwapCase {
+        fn check(&self, src: &FilterSource) -> Result<bool, Error> {
+            Ok(!src.path().unwrap().starts_with("skip"))
+        }
+        fn clean(&self, _src: &FilterSource, input: &[u8])
+                 -> Result<Option<Vec<u8>>, Error> {
+            Ok(Some(swap_case(input)))
+        }
+        fn smudge(&self, _src: &FilterSource, input: &[u8])
+                  -> Result<Option<Vec<u8>>, Error> {
+            if input == b"fail" {
+                return Err(Error::from_str("refusing to smudge"))
+            }
+            Ok(Some(swap_case(input)))
+        }
+    }
+
+    #[test]
+    fn builtin() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        File::create(&root.join(".gitattributes"))
+             .write_str("*.txt text eol=crlf\n").unwrap();
+        let path = Path::new("a.txt");
+
+        let list = repo.filter_list(None, &path, FilterMode::Smudge,
+                                    FILTER_DEFAULT).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.apply_to_buffer(b"a\nb\n").unwrap().get(),
+                   b"a\r\nb\r\n");
+        let list = repo.filter_list(None, &path, FilterMode::Clean,
+                                    FILTER_DEFAULT).unwrap();
+        assert_eq!(list.apply_to_buffer(b"a\r\nb\r\n").unwrap().get(),
+                   b"a\nb\n");
+
+        let list = repo.filter_list(None, &Path::new("a.bin"),
+                                    FilterMode::Smudge, FILTER_DEFAULT)
+                       .unwrap();
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.apply_to_buffer(b"a\nb\n").unwrap().get(), b"a\nb\n");
+    }
+
+    #[test]
+    fn custom() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        File::create(&root.join(".gitattributes"))
+             .write_str("*.sw filter=swapcase-test\n").unwrap();
+        File::create(&root.join("a.sw")).write_str("Hello World").unwrap();
+        filter_register("swapcase-test", "filter=swapcase-test", 200,
+                        SwapCase).unwrap();
+        assert!(filter_register("swapcase-test", "filter=swapcase-test", 200,
+                                SwapCase).is_err());
+
+        {
+            let path = Path::new("a.sw");
+            let clean = repo.filter_list(None, &path, FilterMode::Clean,
+                                         FILTER_DEFAULT).unwrap();
+            assert_eq!(clean.len(), 1);
+            assert_eq!(clean.apply_to_file(&repo, &path).unwrap().get(),
+                       b"hELLO wORLD");
+
+            // Adding the file to the index stores the cleaned content.
+            let mut index = repo.index().unwrap();
+            index.add_path(&path).unwrap();
+            let id = index.get_path(&path, 0).unwrap().id;
+            let blob = repo.find_blob(id).unwrap();
+            assert_eq!(blob.content(), b"hELLO wORLD");
+
+            let smudge = repo.filter_list(Some(&blob), &path,
+                                          FilterMode::Smudge,
+                                          FILTER_DEFAULT).unwrap();
+            assert_eq!(smudge.apply_to_blob(&blob).unwrap().get(),
+                       b"Hello World");
+            assert!(smudge.apply_to_buffer(b"fail").is_err());
+
+            let skipped = repo.filter_list(None, &Path::new("skip.sw"),
+                                           FilterMode::Clean,
+                                           FILTER_DEFAULT).unwrap();
+            assert_eq!(skipped.apply_to_buffer(b"Hello").unwrap().get(),
+                       b"Hello");
+        }
+
+        filter_unregister("swapcase-test").unwrap();
+        assert!(filter_unregister("swapcase-test").is_err());
+        assert!(filter_unregister("crlf").is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use std::ascii::AsciiExt;
+use std::io::File;
+use std::io::fs::PathExtensions;
+use std::os;
+
+use {Error, Repository};
+
+/// The ignore rule deciding whether a path is ignored, as printed by
+/// `git check-ignore -v` and returned by `Repository::check_ignore`.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub struct IgnoreMatch {
+    source: Path,
+    line: uint,
+    pattern: String,
+    negated: bool,
+}
+
+// A single pattern read from an ignore file.
+pub struct Pattern {
+    glob: Vec<u8>,
+    line: uint,
+    text: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+// The patterns of an ignore file, along with the directory they apply to,
+// relative to the working directory and empty for the root.
+struct RuleFile {
+    source: Path,
+    base: Vec<u8>,
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreMatch {
+    /// Get the path of the file containing the rule: a `.gitignore` file,
+    /// `$GIT_DIR/info/exclude` or the file named by `core.excludesFile`.
+    pub fn source(&self) -> &Path { &self.source }
+
+    /// Get the line number of the rule in its source file, starting at 1.
+    pub fn line(&self) -> uint { self.line }
+
+    /// Get the pattern as written in the source file, including any leading
+    /// `!`.
+    pub fn pattern(&self) -> &str { self.pattern.as_slice() }
+
+    /// Returns whether the pattern is negated, in which case the path is
+    /// explicitly not ignored.
+    pub fn is_negated(&self) -> bool { self.negated }
+}
+
+/// Find the rule deciding whether `path` is ignored, following the
+/// precedence git uses.
+///
+/// Patterns in `.gitignore` files closer to the path take precedence over
+/// those further up, which take precedence over `$GIT_DIR/info/exclude` and
+/// then `core.excludesFile`. Within a file the last matching pattern wins,
+/// and a path inside an ignored directory is ignored no matter what.
+///
+/// The rule found is checked against libgit2's own verdict, and an error is
+/// returned if they disagree rather than reporting the wrong rule.
+pub fn check(repo: &Repository, path: &Path)
+             -> Result<Option<IgnoreMatch>, Error> {
+    let m = try!(find_rule(repo, path));
+    match m {
+        Some(ref m) if try!(repo.is_path_ignored(path)) == m.negated => {
+            Err(Error::from_str(format!("ignore rule `{}` does not match \
+                                         libgit2", m.pattern).as_slice()))
+        }
+        _ => Ok(m),
+    }
+}
+
+fn find_rule(repo: &Repository, path: &Path)
+             -> Result<Option<IgnoreMatch>, Error> {
+    let workdir = repo.workdir();
+    let path = match workdir {
+        Some(ref dir) if path.is_absolute() => {
+            match path.path_relative_from(dir) {
+                Some(p) => p,
+                None => return Err(Error::from_str("path is outside of the \
+                                                    working directory")),
+            }
+        }
+        _ => path.clone(),
+    };
+    let components = path.components().collect::<Vec<&[u8]>>();
+    let is_dir = match workdir {
+        Some(ref dir) => dir.join(&path).is_dir(),
+        None => false,
+    };
+    let icase = {
+        let mut config = try!(repo.config());
+        let config = try!(config.snapshot());
+        config.get_bool("core.ignorecase").unwrap_or(false)
+    };
+
+    // Rule files in increasing order of precedence.
+    let mut files = Vec::new();
+    match try!(excludes_file(repo)) {
+        Some(p) => files.extend(read(p, Vec::new()).into_iter()),
+        None => {}
+    }
+    files.extend(read(repo.path().join("info/exclude"), Vec::new())
+                     .into_iter());
+    match workdir {
+        Some(ref dir) => {
+            for i in range(0, components.len()) {
+                let base = join(components.slice_to(i));
+                let source = dir.join(base.as_slice()).join(".gitignore");
+                files.extend(read(source, base).into_iter());
+            }
+        }
+        None => {}
+    }
+
+    // Check each leading directory first, as once a directory is ignored
+    // nothing inside it can be re-included.
+    for i in range(1, components.len() + 1) {
+        let prefix = join(components.slice_to(i));
+        let last = i == components.len();
+        let dir = !last || is_dir;
+        let prefix = if icase {prefix.to_ascii_lower()} else {prefix};
+        match find(files.as_slice(), prefix.as_slice(), dir, icase) {
+            Some(m) => {
+                if last || !m.negated { return Ok(Some(m)) }
+            }
+            None => {}
+        }
+    }
+    Ok(None)
+}
+
+fn find(files: &[RuleFile], path: &[u8], is_dir: bool, icase: bool)
+        -> Option<IgnoreMatch> {
+    for file in files.iter().rev() {
+        // Only the .gitignore files of directories above `path` apply to it.
+        let base = if icase {
+            file.base.to_ascii_lower()
+        } else {
+            file.base.clone()
+        };
+        let rel = if base.len() == 0 {
+            path
+        } else if path.starts_with(base.as_slice()) &&
+                  path.get(base.len()) == Some(&b'/') {
+            path.slice_from(base.len() + 1)
+        } else {
+            continue
+        };
+        let found = file.patterns.iter().rev().find(|p| {
+            matches(*p, rel, is_dir, icase)
+        });
+        match found {
+            Some(p) => {
+                return Some(IgnoreMatch {
+                    source: file.source.clone(),
+                    line: p.line,
+                    pattern: p.text.clone(),
+                    negated: p.negated,
+                })
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Find the last of `patterns` matching `path`, returning whether it is
+/// negated.
+///
+/// This is shared with sparse checkouts, whose patterns have the same syntax
+/// as ignore files.
+pub fn last_match(patterns: &[Pattern], path: &[u8], is_dir: bool)
+                  -> Option<bool> {
+    patterns.iter().rev().find(|p| matches(*p, path, is_dir, false))
+            .map(|p| p.negated)
+}
+
+// With `icase`, `path` is expected to be lowercase already.
+fn matches(p: &Pattern, path: &[u8], is_dir: bool, icase: bool) -> bool {
+    if p.dir_only && !is_dir { return false }
+    let text = if p.anchored {
+        path
+    } else {
+        match path.rposition_elem(&b'/') {
+            Some(i) => path.slice_from(i + 1),
+            None => path,
+        }
+    };
+    if icase {
+        wildmatch(p.glob.to_ascii_lower().as_slice(), text)
+    } else {
+        wildmatch(p.glob.as_slice(), text)
+    }
+}
+
+fn join(components: &[&[u8]]) -> Vec<u8> {
+    let mut ret = Vec::new();
+    for (i, c) in components.iter().enumerate() {
+        if i > 0 { ret.push(b'/') }
+        ret.push_all(*c);
+    }
+    ret
+}
+
+// Missing or unreadable ignore files are skipped, as git does.
+fn read(source: Path, base: Vec<u8>) -> Option<RuleFile> {
+    let contents = match File::open(&source).read_to_end() {
+        Ok(contents) => contents,
+        Err(..) => return None,
+    };
+    let contents = String::from_utf8_lossy(contents.as_slice()).into_owned();
+    let patterns = parse_patterns(contents.as_slice());
+    Some(RuleFile { source: source, base: base, patterns: patterns })
+}
+
+/// Parse the patterns of an ignore file.
+pub fn parse_patterns(contents: &str) -> Vec<Pattern> {
+    contents.lines_any().enumerate()
+            .filter_map(|(i, line)| parse(i + 1, line))
+            .collect()
+}
+
+fn parse(line: uint, text: &str) -> Option<Pattern> {
+    let bytes = text.as_bytes();
+
+    // Trailing spaces are ignored unless escaped with a backslash.
+    let mut end = bytes.len();
+    while end > 0 && bytes[end - 1] == b' ' &&
+          !(end > 1 && bytes[end - 2] == b'\\') {
+        end -= 1;
+    }
+    let bytes = bytes.slice_to(end);
+    if bytes.len() == 0 || bytes[0] == b'#' { return None }
+
+    let negated = bytes[0] == b'!';
+    let mut glob = if negated { bytes.slice_from(1) } else { bytes };
+    let dir_only = glob.last() == Some(&b'/');
+    if dir_only { glob = glob.slice_to(glob.len() - 1) }
+    let anchored = glob.contains(&b'/');
+    if glob.first() == Some(&b'/') { glob = glob.slice_from(1) }
+    if glob.len() == 0 { return None }
+
+    Some(Pattern {
+        glob: glob.to_vec(),
+        line: line,
+        text: text.slice_to(end).to_string(),
+        negated: negated,
+        dir_only: dir_only,
+        anchored: anchored,
+    })
+}
+
+fn excludes_file(repo: &Repository) -> Result<Option<Path>, Error> {
+    let mut config = try!(repo.config());
+    let config = try!(config.snapshot());
+    let home = os::homedir();
+    let path = match config.get_bytes("core.excludesfile") {
+        Ok(path) if path.starts_with(b"~/") => {
+            home.map(|h| h.join(path.slice_from(2)))
+        }
+        Ok(path) => Some(Path::new(path)),
+        Err(..) => match os::getenv("XDG_CONFIG_HOME") {
+            Some(ref dir) if dir.len() > 0 => {
+                Some(Path::new(dir.as_slice()).join("git/ignore"))
+            }
+            _ => home.map(|h| h.join(".config/git/ignore")),
+        },
+    };
+    Ok(path)
+}
+
+// Match `text` against a glob with the semantics of gitignore patterns: `*`,
+// `?` and `[...]` never match a `/`, while `**/` matches any number of
+// leading directories and a trailing `/**` everything inside a directory.
+fn wildmatch(p: &[u8], t: &[u8]) -> bool {
+    if p.len() == 0 { return t.len() == 0 }
+    match p[0] {
+        b'*' if p.get(1) == Some(&b'*') => {
+            let rest = p.slice_from(2);
+            if rest.len() == 0 { return true }
+            if rest[0] != b'/' { return star(rest, t) }
+            let rest = rest.slice_from(1);
+            wildmatch(rest, t) || t.iter().enumerate().any(|(i, &c)| {
+                c == b'/' && wildmatch(rest, t.slice_from(i + 1))
+            })
+        }
+        b'*' => star(p.slice_from(1), t),
+        b'?' => {
+            t.len() > 0 && t[0] != b'/' &&
+                wildmatch(p.slice_from(1), t.slice_from(1))
+        }
+        b'[' => class(p, t),
+        b'\\' if p.len() > 1 => {
+            t.len() > 0 && t[0] == p[1] &&
+                wildmatch(p.slice_from(2), t.slice_from(1))
+        }
+        c => {
+            t.len() > 0 && t[0] == c &&
+                wildmatch(p.slice_from(1), t.slice_from(1))
+        }
+    }
+}
+
+fn star(p: &[u8], t: &[u8]) -> bool {
+    let mut i = 0;
+    loop {
+        if wildmatch(p, t.slice_from(i)) { return true }
+        if i == t.len() || t[i] == b'/' { return false }
+        i += 1;
+    }
+}
+
+// Match a bracket expression at the start of `p`, such as `[a-z]` or
+// `[!0-9]`. An unterminated bracket matches a literal `[`.
+fn class(p: &[u8], t: &[u8]) -> bool {
+    if t.len() == 0 || t[0] == b'/' { return false }
+    let c = t[0];
+    let mut i = 1;
+    let negated = p.get(i) == Some(&b'!') || p.get(i) == Some(&b'^');
+    if negated { i += 1 }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        if i >= p.len() {
+            return c == b'[' && wildmatch(p.slice_from(1), t.slice_from(1))
+        }
+        if p[i] == b']' && !first { break }
+        first = false;
+        if p[i] == b'\\' && i + 1 < p.len() { i += 1 }
+        let lo = p[i];
+        if i + 2 < p.len() && p[i + 1] == b'-' && p[i + 2] != b']' {
+            if lo <= c && c <= p[i + 2] { matched = true }
+            i += 3;
+        } else {
+            if lo == c { matched = true }
+            i += 1;
+        }
+    }
+    matched != negated && wildmatch(p.slice_from(i + 1), t.slice_from(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{fs, File};
+    use super::wildmatch;
+
+    #[test]
+    fn globs() {
+        assert!(wildmatch(b"*.log", b"a.log"));
+        assert!(!wildmatch(b"*.log", b"a/b.log"));
+        assert!(wildmatch(b"a?c", b"abc"));
+        assert!(!wildmatch(b"a?c", b"a/c"));
+        assert!(wildmatch(b"[a-c]x", b"bx"));
+        assert!(!wildmatch(b"[!a-c]x", b"bx"));
+        assert!(wildmatch(b"[]]", b"]"));
+        assert!(wildmatch(b"**/foo", b"foo"));
+        assert!(wildmatch(b"**/foo", b"a/b/foo"));
+        assert!(wildmatch(b"a/**/b", b"a/b"));
+        assert!(wildmatch(b"a/**/b", b"a/x/y/b"));
+        assert!(wildmatch(b"a/**", b"a/x/y"));
+        assert!(!wildmatch(b"a/**", b"b/x"));
+        assert!(wildmatch(b"\\#x", b"#x"));
+        assert!(wildmatch(b"a\\ ", b"a "));
+    }
+
+    #[test]
+    fn check_ignore() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        File::create(&root.join(".gitignore"))
+             .write_str("# logs\n*.log\n!keep.log\nbuild/\n").unwrap();
+        fs::mkdir(&root.join("sub"), ::std::io::USER_RWX).unwrap();
+        File::create(&root.join("sub/.gitignore")).write_str("/local\n")
+             .unwrap();
+        let info = repo.path().join("info");
+        fs::mkdir_recursive(&info, ::std::io::USER_RWX).unwrap();
+        File::create(&info.join("exclude")).write_str("secret\n").unwrap();
+
+        let m = repo.check_ignore(&Path::new("a.log")).unwrap().unwrap();
+        assert_eq!(m.source(), &root.join(".gitignore"));
+        assert_eq!(m.line(), 2);
+        assert_eq!(m.pattern(), "*.log");
+        assert!(!m.is_negated());
+
+        let m = repo.check_ignore(&Path::new("keep.log")).unwrap().unwrap();
+        assert_eq!((m.line(), m.pattern()), (3, "!keep.log"));
+        assert!(m.is_negated());
+
+        let m = repo.check_ignore(&Path::new("build/keep.log")).unwrap()
+                    .unwrap();
+        assert_eq!((m.line(), m.pattern()), (4, "build/"));
+        let m = repo.check_ignore(&Path::new("sub/local")).unwrap().unwrap();
+        assert_eq!(m.source(), &root.join("sub/.gitignore"));
+        assert_eq!(m.pattern(), "/local");
+        let m = repo.check_ignore(&Path::new("a/secret")).unwrap().unwrap();
+        assert_eq!(m.source(), &repo.path().join("info/exclude"));
+
+        assert!(repo.check_ignore(&Path::new("local")).unwrap().is_none());
+        assert!(repo.check_ignore(&Path::new("a.txt")).unwrap().is_none());
+        assert!(repo.check_ignore(&Path::new("A.LOG")).unwrap().is_none());
+
+        repo.config().unwrap().set_bool("core.ignorecase", true).unwrap();
+        let repo = ::Repository::open(&repo.path()).unwrap();
+        let m = repo.check_ignore(&Path::new("A.LOG")).unwrap().unwrap();
+        assert_eq!(m.pattern(), "*.log");
+    }
+
+    #[test]
+    fn path_is_ignored() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        File::create(&root.join(".gitignore"))
+             .write_str("*.log\n!keep.log\nbuild/\n").unwrap();
+        assert!(repo.is_path_ignored(&Path::new("a.log")).unwrap());
+        assert!(repo.is_path_ignored(&Path::new("missing/a.log")).unwrap());
+        assert!(repo.is_path_ignored(&Path::new("build/x.c")).unwrap());
+        assert!(!repo.is_path_ignored(&Path::new("keep.log")).unwrap());
+        assert!(!repo.is_path_ignored(&Path::new("a.tmp")).unwrap());
+
+        repo.add_ignore_rule("*.tmp\n/scratch/").unwrap();
+        assert!(repo.is_path_ignored(&Path::new("a.tmp")).unwrap());
+        assert!(repo.is_path_ignored(&Path::new("scratch/x")).unwrap());
+        assert!(repo.check_ignore(&Path::new("a.tmp")).unwrap().is_none());
+        repo.clear_internal_ignore_rules().unwrap();
+        assert!(!repo.is_path_ignored(&Path::new("a.tmp")).unwrap());
+    }
+}
@@ -1,4 +1,6 @@
 use std::c_str::CString;
+use std::io::{mod, fs, File};
+use std::io::fs::PathExtensions;
 use std::iter::Range;
 use std::kinds::marker;
 use std::mem;
@@ -6,7 +8,8 @@ use std::path::PosixPath;
 
 use libc;
 
-use {raw, Repository, Error, Tree, Oid, IndexAddOption, IndexTime};
+use {raw, Repository, Error, Tree, Oid, IndexAddOption, IndexTime, FileMode};
+use ObjectType;
 
 /// A structure to represent a git [index][1]
 ///
@@ -14,6 +17,7 @@ use {raw, Repository, Error, Tree, Oid, IndexAddOption, IndexTime};
 pub struct Index {
     raw: *mut raw::git_index,
     marker: marker::NoSync,
+    checksum: Oid,
 }
 
 /// An iterator over the entries in an index
@@ -84,7 +88,11 @@ impl Index {
     ///
     /// This function is unsafe as it cannot guarantee the validity of `raw`.
     pub unsafe fn from_raw(raw: *mut raw::git_index) -> Index {
-        Index { raw: raw, marker: marker::NoSync }
+        let mut index = Index { raw: raw, marker: marker::NoSync,
+                                checksum: zero_oid() };
+        index.checksum = index.trailer().unwrap_or(None)
+                              .unwrap_or(zero_oid());
+        index
     }
 
     /// Add or update an index entry from an in-memory struct
@@ -101,6 +109,36 @@ impl Index {
         }
     }
 
+    /// Add or update an index entry from a buffer in memory
+    ///
+    /// The content of `data` is written to the object database as a blob and
+    /// the entry is added with the id of that blob, without touching the
+    /// working directory. The `id` and `file_size` of `entry` are ignored.
+    ///
+    /// If a previous index entry exists that has the same path and stage as
+    /// `entry`, it will be replaced.
+    ///
+    /// This method will fail in bare index instances.
+    pub fn add_frombuffer(&mut self, entry: &IndexEntry, data: &[u8])
+                          -> Result<(), Error> {
+        let mut raw: raw::git_index_entry = unsafe { mem::zeroed() };
+        entry.configure(&mut raw);
+        let ptr = data.as_ptr() as *const libc::c_void;
+        let len = data.len() as libc::size_t;
+        unsafe {
+            let repo = raw::git_index_owner(&*self.raw);
+            if repo.is_null() {
+                return Err(Error::from_str("cannot add a buffer to an index \
+                                            without a repository"))
+            }
+            try_call!(raw::git_blob_create_frombuffer(&mut raw.id, repo,
+                                                      ptr, len));
+            raw.file_size = data.len() as raw::git_off_t;
+            try_call!(raw::git_index_add(self.raw, &raw));
+        }
+        Ok(())
+    }
+
     /// Add or update an index entry from a file on disk
     ///
     /// The file path must be relative to the repository's working folder and
@@ -121,7 +159,8 @@ impl Index {
             posix_path.push(comp);
         }
         unsafe {
-            try_call!(raw::git_index_add_bypath(self.raw, posix_path.to_c_str()));
+            try_call_panic!(raw::git_index_add_bypath(self.raw,
+                                                      posix_path.to_c_str()));
             Ok(())
         }
     }
@@ -176,13 +215,13 @@ impl Index {
             index_matched_path_cb as raw::git_index_matched_path_cb
         });
         unsafe {
-            try_call!(raw::git_index_add_all(self.raw,
-                                             &raw_strarray,
-                                             flag.bits() as libc::c_uint,
-                                             callback,
-                                             ptr.map(|p| p as *mut _)
-                                                .unwrap_or(0 as *mut _)
-                                                    as *mut libc::c_void));
+            let payload = ptr.map(|p| p as *mut _).unwrap_or(0 as *mut _)
+                             as *mut libc::c_void;
+            try_call_panic!(raw::git_index_add_all(self.raw,
+                                                   &raw_strarray,
+                                                   flag.bits() as libc::c_uint,
+                                                   callback,
+                                                   payload));
         }
         return Ok(());
     }
@@ -226,6 +265,65 @@ impl Index {
         }
     }
 
+    /// Find the position of the first entry for `path`, at any stage.
+    pub fn find(&self, path: &Path) -> Option<uint> {
+        let mut pos = 0 as libc::size_t;
+        let rc = unsafe {
+            call!(raw::git_index_find(&mut pos, self.raw, path.to_c_str()))
+        };
+        if rc == 0 {Some(pos as uint)} else {None}
+    }
+
+    /// Find the position of the first entry whose path starts with `prefix`.
+    pub fn find_prefix(&self, prefix: &Path) -> Option<uint> {
+        let prefix = prefix.as_vec();
+        // Entries are sorted by path, so binary search for the first path
+        // not ordered before the prefix.
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.path_bytes(mid) < prefix { lo = mid + 1 } else { hi = mid }
+        }
+        if lo < self.len() && self.path_bytes(lo).starts_with(prefix) {
+            Some(lo)
+        } else {
+            None
+        }
+    }
+
+    fn path_bytes(&self, n: uint) -> &[u8] {
+        unsafe {
+            let ptr = raw::git_index_get_byindex(self.raw, n as libc::size_t);
+            ::opt_bytes(self, (*ptr).path).unwrap()
+        }
+    }
+
+    /// Get the version of the on-disk format of the index file, 2, 3 or 4.
+    ///
+    /// The version is read from the header of the file. Returns `None` for
+    /// in-memory indexes and indexes which have not been written yet.
+    /// libgit2 writes version 3 when an entry has extended flags, such as
+    /// skip-worktree, and version 2 otherwise.
+    pub fn version(&self) -> Result<Option<u32>, Error> {
+        let path = match self.path() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind == io::FileNotFound => return Ok(None),
+            Err(e) => return Err(::io_error(e)),
+        };
+        // The header is the signature "DIRC" followed by the version.
+        let header = try!(file.read_exact(8).map_err(::io_error));
+        if header.slice_to(4) != b"DIRC" {
+            return Err(Error::from_str("the index file has no valid header"))
+        }
+        Ok(Some(header.slice_from(4).iter().fold(0u32, |v, b| {
+            (v << 8) | *b as u32
+        })))
+    }
+
     /// Get the full path to the index file on disk.
     ///
     /// Returns `None` if this is an in-memory index.
@@ -248,9 +346,121 @@ impl Index {
     /// changes on disk, unwritten in-memory changes are discarded.
     pub fn read(&mut self, force: bool) -> Result<(), Error> {
         unsafe { try_call!(raw::git_index_read(self.raw, force)); }
+        self.checksum = try!(self.trailer()).unwrap_or(zero_oid());
         Ok(())
     }
 
+    /// Get the checksum of the index file as it was last opened, read or
+    /// written through this index.
+    ///
+    /// The checksum is the trailing SHA-1 of the index file, and is zero if
+    /// the index has never been read from or written to disk.
+    pub fn checksum(&self) -> Oid { self.checksum }
+
+    /// Check whether the index file on disk has changed since it was last
+    /// read or written through this index, for example by a concurrent
+    /// `git add`.
+    ///
+    /// Writing a stale index overwrites those changes, so callers should
+    /// `read` it again and redo their own changes instead. In-memory indexes
+    /// are never stale.
+    pub fn is_stale(&self) -> Result<bool, Error> {
+        let current = try!(self.trailer()).unwrap_or(zero_oid());
+        Ok(current != self.checksum)
+    }
+
+    // Read the checksum at the end of the index file, if there is one.
+    fn trailer(&self) -> Result<Option<Oid>, Error> {
+        let path = match self.path() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind == io::FileNotFound => return Ok(None),
+            Err(e) => return Err(::io_error(e)),
+        };
+        let trailer = match file.seek(-(raw::GIT_OID_RAWSZ as i64),
+                                      io::SeekEnd) {
+            Ok(()) => file.read_exact(raw::GIT_OID_RAWSZ),
+            Err(e) => Err(e),
+        };
+        match trailer {
+            Ok(trailer) => Oid::from_bytes(trailer.as_slice()).map(Some),
+            Err(e) => Err(::io_error(e)),
+        }
+    }
+
+    /// Get the entries which are racily clean.
+    ///
+    /// An entry is racily clean when its file was modified no earlier than
+    /// the index was written, so its stat information cannot tell whether the
+    /// file changed afterwards. Such entries must have their content checked,
+    /// see `refresh_racy`. In-memory indexes have no racy entries.
+    pub fn racy_entries(&self) -> Result<Vec<IndexEntry>, Error> {
+        let path = match self.path() {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let stamp = match fs::stat(&path) {
+            Ok(stat) => stat.modified,
+            Err(ref e) if e.kind == io::FileNotFound => return Ok(Vec::new()),
+            Err(e) => return Err(::io_error(e)),
+        };
+        Ok(self.iter().filter(|e| {
+            let mtime = e.mtime.seconds() as u64 * 1000 +
+                        e.mtime.nanoseconds() as u64 / 1_000_000;
+            mtime >= stamp
+        }).collect())
+    }
+
+    /// Check the content of the racily clean entries against the working
+    /// directory.
+    ///
+    /// Entries whose content is unchanged have their stat information
+    /// refreshed. The paths of the entries whose content did change, or whose
+    /// file was removed, are returned and the entries are left as they are so
+    /// that they show up as modified. Conflicted and skip-worktree entries
+    /// are not checked.
+    ///
+    /// This method will fail in bare index instances.
+    pub fn refresh_racy(&mut self) -> Result<Vec<Path>, Error> {
+        let repo = unsafe { raw::git_index_owner(&*self.raw) };
+        let workdir = unsafe {
+            if repo.is_null() { None }
+            else { ::opt_bytes(self, raw::git_repository_workdir(repo)) }
+        };
+        let workdir = match workdir {
+            Some(dir) => Path::new(dir),
+            None => {
+                return Err(Error::from_str("index has no working directory"))
+            }
+        };
+        let mut changed = Vec::new();
+        for entry in try!(self.racy_entries()).into_iter() {
+            if entry.stage() != 0 || entry.is_skip_worktree() { continue }
+            let path = Path::new(entry.path.as_bytes_no_nul());
+            if !workdir.join(&path).exists() {
+                changed.push(path);
+                continue
+            }
+            let mut id = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+            unsafe {
+                try_call_panic!(raw::git_repository_hashfile(&mut id, repo,
+                                                             entry.path,
+                                                             ObjectType::Blob,
+                                                             entry.path));
+                if Oid::from_raw(&id) != entry.id {
+                    changed.push(path);
+                    continue
+                }
+                try_call_panic!(raw::git_index_add_bypath(self.raw,
+                                                          entry.path));
+            }
+        }
+        Ok(changed)
+    }
+
     /// Read a tree into the index file with stats
     ///
     /// The current index contents will be replaced by the specified tree.
@@ -352,20 +562,36 @@ impl Index {
             index_matched_path_cb as raw::git_index_matched_path_cb
         });
         unsafe {
-            try_call!(raw::git_index_update_all(self.raw,
-                                                &raw_strarray,
-                                                callback,
-                                                ptr.map(|p| p as *mut _)
-                                                   .unwrap_or(0 as *mut _)
-                                                        as *mut libc::c_void));
+            let payload = ptr.map(|p| p as *mut _).unwrap_or(0 as *mut _)
+                             as *mut libc::c_void;
+            try_call_panic!(raw::git_index_update_all(self.raw,
+                                                      &raw_strarray,
+                                                      callback,
+                                                      payload));
         }
         return Ok(());
     }
 
+    /// Update all index entries matching `pathspecs` to match the working
+    /// directory, returning the paths of the entries which were updated or
+    /// removed.
+    ///
+    /// See `update_all` for more information.
+    pub fn update_all_changed<T: ToCStr>(&mut self, pathspecs: &[T])
+                                         -> Result<Vec<Path>, Error> {
+        let mut changed = Vec::new();
+        try!(self.update_all(pathspecs, Some(|path: &[u8], _: &[u8]| {
+            changed.push(Path::new(path));
+            0
+        })));
+        Ok(changed)
+    }
+
     /// Write an existing index object from memory back to disk using an atomic
     /// file lock.
     pub fn write(&mut self) -> Result<(), Error> {
         unsafe { try_call!(raw::git_index_write(self.raw)); }
+        self.checksum = try!(self.trailer()).unwrap_or(zero_oid());
         Ok(())
     }
 
@@ -449,6 +675,84 @@ impl IndexEntry {
         }
     }
 
+    /// Get the stage of this entry: 0 for a normal entry, or 1 to 3 for the
+    /// base, ours and theirs sides of a conflict.
+    pub fn stage(&self) -> uint {
+        ((self.flags & raw::GIT_IDXENTRY_STAGEMASK) >>
+            raw::GIT_IDXENTRY_STAGESHIFT) as uint
+    }
+
+    /// Set the stage of this entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stage` is greater than 3.
+    pub fn set_stage(&mut self, stage: uint) {
+        assert!(stage <= 3, "invalid index entry stage: {}", stage);
+        self.flags = (self.flags & !raw::GIT_IDXENTRY_STAGEMASK) |
+                     ((stage as u16) << raw::GIT_IDXENTRY_STAGESHIFT);
+    }
+
+    /// Returns whether this entry is marked as assume-valid, in which case
+    /// the file in the working directory is not checked for changes.
+    pub fn is_assume_valid(&self) -> bool {
+        self.flags & raw::GIT_IDXENTRY_VALID != 0
+    }
+
+    /// Mark this entry as assume-valid, as `git update-index
+    /// --assume-unchanged` does.
+    pub fn set_assume_valid(&mut self, valid: bool) {
+        set_flag(&mut self.flags, raw::GIT_IDXENTRY_VALID, valid);
+    }
+
+    /// Returns whether this entry is marked as skip-worktree, meaning the file
+    /// is not expected in the working directory, as with sparse checkouts.
+    pub fn is_skip_worktree(&self) -> bool {
+        self.flags_extended & raw::GIT_IDXENTRY_SKIP_WORKTREE != 0
+    }
+
+    /// Mark this entry as skip-worktree.
+    ///
+    /// This is an extended flag, which requires an index of version 3 or
+    /// later to be written.
+    pub fn set_skip_worktree(&mut self, skip: bool) {
+        set_flag(&mut self.flags_extended, raw::GIT_IDXENTRY_SKIP_WORKTREE,
+                 skip);
+        self.update_extended();
+    }
+
+    /// Returns whether this entry is an intent-to-add entry, as created by
+    /// `git add -N`.
+    pub fn is_intent_to_add(&self) -> bool {
+        self.flags_extended & raw::GIT_IDXENTRY_INTENT_TO_ADD != 0
+    }
+
+    /// Mark this entry as an intent-to-add entry.
+    ///
+    /// This is an extended flag, which requires an index of version 3 or
+    /// later to be written.
+    pub fn set_intent_to_add(&mut self, intent: bool) {
+        set_flag(&mut self.flags_extended, raw::GIT_IDXENTRY_INTENT_TO_ADD,
+                 intent);
+        self.update_extended();
+    }
+
+    /// Get the mode of this entry, or `None` if it is not a mode git records.
+    pub fn filemode(&self) -> Option<FileMode> {
+        FileMode::from_raw(self.mode as u32)
+    }
+
+    /// Set the mode of this entry.
+    pub fn set_filemode(&mut self, mode: FileMode) {
+        self.mode = mode.raw() as uint;
+    }
+
+    // The extended flag records whether any extended flags are set at all.
+    fn update_extended(&mut self) {
+        let extended = self.flags_extended != 0;
+        set_flag(&mut self.flags, raw::GIT_IDXENTRY_EXTENDED, extended);
+    }
+
     /// Configures a raw git entry from this entry
     pub fn configure(&self, raw: &mut raw::git_index_entry) {
         *raw = raw::git_index_entry {
@@ -474,12 +778,42 @@ impl IndexEntry {
     }
 }
 
+fn zero_oid() -> Oid {
+    unsafe { Oid::from_raw(&raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] }) }
+}
+
+fn set_flag(flags: &mut u16, flag: u16, on: bool) {
+    if on {
+        *flags |= flag;
+    } else {
+        *flags &= !flag;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::{mod, fs, File, TempDir};
+    use std::io::fs::PathExtensions;
     use url::Url;
 
-    use {Index, Repository, ResetType};
+    use {Index, IndexEntry, IndexTime, Oid, Repository, ResetType, FileMode};
+
+    fn entry(path: &str) -> IndexEntry {
+        IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: Oid::from_bytes(&[0, ..20]).unwrap(),
+            flags: 0,
+            flags_extended: 0,
+            path: path.to_c_str(),
+        }
+    }
 
     #[test]
     fn smoke() {
@@ -565,5 +899,125 @@ mod tests {
         let obj = repo.find_object(commit, None).unwrap();
         repo.reset(&obj, ResetType::Hard, None, None).unwrap();
     }
-}
 
+    #[test]
+    fn entry_flags() {
+        let mut e = entry("a");
+        assert_eq!(e.stage(), 0);
+        e.set_stage(2);
+        assert_eq!(e.stage(), 2);
+        assert!(!e.is_assume_valid());
+        e.set_assume_valid(true);
+        assert!(e.is_assume_valid());
+        assert_eq!(e.stage(), 2);
+
+        e.set_skip_worktree(true);
+        e.set_intent_to_add(true);
+        assert!(e.is_skip_worktree() && e.is_intent_to_add());
+        e.set_skip_worktree(false);
+        assert!(!e.is_skip_worktree() && e.is_intent_to_add());
+        e.set_intent_to_add(false);
+        assert_eq!(e.flags_extended, 0);
+        assert_eq!(e.flags & ::raw::GIT_IDXENTRY_EXTENDED, 0);
+
+        assert_eq!(e.filemode(), Some(FileMode::Blob));
+        e.set_filemode(FileMode::BlobExecutable);
+        assert_eq!(e.mode, 0o100755);
+        e.mode = 0o100600;
+        assert_eq!(e.filemode(), None);
+    }
+
+    #[test]
+    fn add_frombuffer_and_find() {
+        let (_td, repo) = ::test::repo_init();
+        let mut index = repo.index().unwrap();
+        index.add_frombuffer(&entry("dir/b"), b"hello").unwrap();
+        let mut e = entry("dir/a");
+        e.set_filemode(FileMode::BlobExecutable);
+        e.set_skip_worktree(true);
+        index.add_frombuffer(&e, b"world").unwrap();
+        assert!(!repo.workdir().unwrap().join("dir").exists());
+
+        assert_eq!(index.find(&Path::new("dir/a")), Some(0));
+        assert_eq!(index.find(&Path::new("dir/b")), Some(1));
+        assert_eq!(index.find(&Path::new("dir/c")), None);
+        assert_eq!(index.find_prefix(&Path::new("dir")), Some(0));
+        assert_eq!(index.find_prefix(&Path::new("nope")), None);
+
+        let a = index.get(0).unwrap();
+        assert!(a.is_skip_worktree());
+        assert_eq!(a.filemode(), Some(FileMode::BlobExecutable));
+        assert_eq!(repo.find_blob(a.id).unwrap().content(), b"world");
+
+        assert_eq!(index.version().unwrap(), None);
+        index.write().unwrap();
+        assert_eq!(index.version().unwrap(), Some(3));
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        assert!(index.get(0).unwrap().is_skip_worktree());
+        assert_eq!(Index::new().unwrap().version().unwrap(), None);
+        assert!(Index::new().unwrap().add_frombuffer(&entry("a"), b"a")
+                                     .is_err());
+    }
+
+    #[test]
+    fn stale() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        File::create(&root.join("a")).write_str("aaa").unwrap();
+        File::create(&root.join("b")).write_str("bbb").unwrap();
+        index.add_path(&Path::new("a")).unwrap();
+        index.add_path(&Path::new("b")).unwrap();
+        index.write().unwrap();
+        assert!(!index.checksum().is_zero());
+        assert!(!index.is_stale().unwrap());
+
+        let mut other = Index::open(&index.path().unwrap()).unwrap();
+        other.remove(&Path::new("b"), 0).unwrap();
+        other.write().unwrap();
+        assert!(index.is_stale().unwrap());
+        index.read(false).unwrap();
+        assert!(!index.is_stale().unwrap());
+        assert_eq!(index.checksum(), other.checksum());
+        assert_eq!(index.len(), 1);
+        assert!(!Index::new().unwrap().is_stale().unwrap());
+    }
+
+    #[test]
+    fn racy_and_update_all_changed() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        File::create(&root.join("a")).write_str("one").unwrap();
+        File::create(&root.join("b")).write_str("bbb").unwrap();
+        index.add_path(&Path::new("b")).unwrap();
+        index.write().unwrap();
+
+        // Pretend `a` is modified right after the index is written.
+        let path = index.path().unwrap();
+        let future = fs::stat(&path).unwrap().modified + 3600 * 1000;
+        fs::change_file_times(&root.join("a"), future, future).unwrap();
+        index.add_path(&Path::new("a")).unwrap();
+        index.write().unwrap();
+        let racy = index.racy_entries().unwrap();
+        assert!(racy.iter().any(|e| e.path.as_bytes_no_nul() == b"a"));
+        assert_eq!(index.refresh_racy().unwrap(), Vec::<Path>::new());
+
+        File::create(&root.join("a")).write_str("two").unwrap();
+        fs::change_file_times(&root.join("a"), future, future).unwrap();
+        assert_eq!(index.refresh_racy().unwrap(), vec![Path::new("a")]);
+        let id = index.get_path(&Path::new("a"), 0).unwrap().id;
+        assert_eq!(repo.find_blob(id).unwrap().content(), b"one");
+
+        fs::unlink(&root.join("b")).unwrap();
+        let mut changed = index.update_all_changed(&["*"]).unwrap();
+        changed.sort();
+        assert_eq!(changed, vec![Path::new("a"), Path::new("b")]);
+        assert_eq!(index.update_all_changed(&["*"]).unwrap(),
+                   Vec::<Path>::new());
+        let id = index.get_path(&Path::new("a"), 0).unwrap().id;
+        assert_eq!(repo.find_blob(id).unwrap().content(), b"two");
+        assert!(index.refresh_racy().unwrap().is_empty());
+    }
+}
@@ -69,6 +69,7 @@
 
 extern crate libc;
 extern crate url;
+extern crate regex;
 extern crate "libgit2-sys" as raw;
 
 use std::c_str::CString;
@@ -77,34 +78,48 @@ use std::mem;
 use std::str;
 use std::sync::{Once, ONCE_INIT};
 
+pub use attr::AttrValue;
 pub use blob::Blob;
 pub use branch::{Branch, Branches};
 pub use buf::Buf;
+pub use cert::{Cert, CertHostkey, CertX509};
 pub use commit::{Commit, Parents};
 pub use config::{Config, ConfigEntry, ConfigEntries};
-pub use cred::{Cred, CredentialHelper};
+pub use cred::{Cred, CredentialHelper, CredentialRequest, CredentialStore};
 pub use diff::{Diff, DiffDelta, DiffFile, DiffOptions, Deltas};
 pub use diff::{DiffLine, DiffHunk, DiffStats};
 pub use error::Error;
+pub use filter::{Filter, FilterList, FilterMode, FilterSource};
+pub use filter::{filter_register, filter_unregister};
+pub use ignore::IgnoreMatch;
 pub use index::{Index, IndexEntry, IndexEntries, IndexMatchedPath};
+pub use log_filter::LogFilter;
+pub use mailmap::Mailmap;
+pub use message::{message_prettify, message_trailers, message_add_trailers};
+pub use message::{MessageTrailers, MessageTrailersIter};
 pub use note::{Note, Notes};
 pub use object::Object;
 pub use oid::Oid;
+pub use oid_array::{OidArray, OidArrayItems};
 pub use pathspec::{Pathspec, PathspecMatchList, PathspecFailedEntries};
 pub use pathspec::{PathspecDiffEntries, PathspecEntries};
 pub use push::{Push, PushStatus};
 pub use reference::{Reference, References, ReferenceNames};
 pub use refspec::Refspec;
-pub use remote::{Remote, Refspecs, RemoteHead};
+pub use remote::{Remote, Refspecs, RemoteHead, RemoteHeads, RemoteHeadInfo};
 pub use remote_callbacks::{RemoteCallbacks, Credentials, TransferProgress};
 pub use remote_callbacks::{TransportMessage, Progress, UpdateTips};
+pub use remote_callbacks::CertificateCheck;
 pub use repo::{Repository, RepositoryInitOptions};
 pub use revspec::Revspec;
-pub use revwalk::Revwalk;
+pub use revwalk::{Revwalk, Commits, HideCallback};
+pub use rewrite::{Rewrite, TreeFilter, MessageFilter, SignatureFilter};
+pub use rewrite::ParentFilter;
+pub use sign::{Signer, Verifier};
 pub use signature::Signature;
 pub use status::{StatusOptions, Statuses, StatusIter, StatusEntry, StatusShow};
 pub use string_array::{StringArray, StringArrayItems, StringArrayBytes};
-pub use submodule::Submodule;
+pub use submodule::{Submodule, SubmoduleUpdateOptions};
 pub use tag::Tag;
 pub use time::{Time, IndexTime};
 pub use tree::{Tree, TreeEntry};
@@ -168,6 +183,17 @@ pub enum Direction {
     Push,
 }
 
+/// Automatic tag following options for a remote.
+#[deriving(PartialEq, Eq, Show, Copy)]
+pub enum AutotagOption {
+    /// Ask the server for tags pointing to objects we're already downloading
+    Auto,
+    /// Don't ask for any tags beyond the refspecs
+    None,
+    /// Ask for all the tags
+    All,
+}
+
 /// An enumeration of the operations that can be performed for the `reset`
 /// method on a `Repository`.
 #[deriving(Copy)]
@@ -195,6 +221,23 @@ pub enum ObjectType {
     Tag,
 }
 
+/// The mode of an entry in a tree or the index.
+#[deriving(PartialEq, Eq, Show, Copy)]
+pub enum FileMode {
+    /// An entry which cannot be read, as found in an unborn or corrupt tree
+    Unreadable,
+    /// A subdirectory
+    Tree,
+    /// A regular file
+    Blob,
+    /// An executable file
+    BlobExecutable,
+    /// A symbolic link
+    Link,
+    /// A submodule, recorded as the id of a commit in another repository
+    Commit,
+}
+
 /// An enumeration for the possible types of branches
 #[deriving(PartialEq, Eq, Show, Copy)]
 pub enum BranchType {
@@ -224,6 +267,51 @@ pub enum ConfigLevel {
     Highest,
 }
 
+/// How a submodule is updated by `Submodule::update`, as configured by
+/// `submodule.<name>.update`.
+#[deriving(PartialEq, Eq, Show, Copy)]
+pub enum SubmoduleUpdate {
+    /// Check out the commit recorded in the superproject, detaching HEAD
+    Checkout,
+    /// Rebase the current branch of the submodule onto the recorded commit
+    Rebase,
+    /// Merge the recorded commit into the current branch of the submodule
+    Merge,
+    /// Leave the submodule alone
+    None,
+    /// No update rule is configured, which behaves like `Checkout`
+    Default,
+}
+
+/// Which changes to a submodule are ignored when computing its status, as
+/// configured by `submodule.<name>.ignore`.
+#[deriving(PartialEq, Eq, Show, Copy)]
+pub enum SubmoduleIgnore {
+    /// Report any change, including untracked files
+    None,
+    /// Ignore untracked files
+    Untracked,
+    /// Ignore all changes in the working directory of the submodule, only
+    /// reporting a different checked out commit
+    Dirty,
+    /// Ignore the submodule entirely
+    All,
+    /// No ignore rule is configured, which behaves like `None`
+    Default,
+}
+
+/// Whether the submodules of a submodule are fetched along with it, as
+/// configured by `submodule.<name>.fetchRecurseSubmodules`.
+#[deriving(PartialEq, Eq, Show, Copy)]
+pub enum SubmoduleRecurse {
+    /// Never fetch the submodules of the submodule
+    No,
+    /// Always fetch the submodules of the submodule
+    Yes,
+    /// Only fetch the submodules whose recorded commit changed
+    OnDemand,
+}
+
 bitflags! {
     #[doc = "
 Orderings that may be specified for Revwalk iteration.
@@ -273,23 +361,93 @@ Flags for the return value of `Repository::revparse`
     }
 }
 
+bitflags! {
+    #[doc = "
+Flags controlling where attributes are read from, see `Repository::get_attr`.
+
+At most one of the `ATTR_CHECK_*_THEN_*` and `ATTR_CHECK_INDEX_ONLY` flags
+may be given; by default files in the working directory are read before the
+index.
+"]
+    flags AttrCheckFlags: u32 {
+        #[doc = "Read `.gitattributes` from the working directory, then the \
+                 index"]
+        const ATTR_CHECK_FILE_THEN_INDEX =
+                raw::GIT_ATTR_CHECK_FILE_THEN_INDEX as u32,
+        #[doc = "Read `.gitattributes` from the index, then the working \
+                 directory"]
+        const ATTR_CHECK_INDEX_THEN_FILE =
+                raw::GIT_ATTR_CHECK_INDEX_THEN_FILE as u32,
+        #[doc = "Only read `.gitattributes` from the index, as needed in a \
+                 bare repository"]
+        const ATTR_CHECK_INDEX_ONLY = raw::GIT_ATTR_CHECK_INDEX_ONLY as u32,
+        #[doc = "Ignore the system-wide attributes file"]
+        const ATTR_CHECK_NO_SYSTEM = raw::GIT_ATTR_CHECK_NO_SYSTEM as u32,
+    }
+}
+
+bitflags! {
+    #[doc = "
+Flags controlling how a filter list is loaded, see `Repository::filter_list`.
+"]
+    flags FilterFlags: u32 {
+        const FILTER_DEFAULT = raw::GIT_FILTER_DEFAULT as u32,
+        #[doc = "Don't fail when a line ending conversion would not be \
+                 reversible"]
+        const FILTER_ALLOW_UNSAFE = raw::GIT_FILTER_ALLOW_UNSAFE as u32,
+    }
+}
+
+bitflags! {
+    #[doc = "
+Types of notifications emitted during a checkout, see
+`CheckoutBuilder::notify_on`.
+"]
+    flags CheckoutNotificationType: u32 {
+        #[doc = "A file would be overwritten or deleted although it has \
+                 local changes, so the checkout cannot proceed"]
+        const CHECKOUT_NOTIFICATION_CONFLICT =
+                raw::GIT_CHECKOUT_NOTIFY_CONFLICT as u32,
+        #[doc = "A file has local changes but does not need to be updated"]
+        const CHECKOUT_NOTIFICATION_DIRTY =
+                raw::GIT_CHECKOUT_NOTIFY_DIRTY as u32,
+        #[doc = "A file is about to be changed"]
+        const CHECKOUT_NOTIFICATION_UPDATED =
+                raw::GIT_CHECKOUT_NOTIFY_UPDATED as u32,
+        #[doc = "An untracked file was found in the working directory"]
+        const CHECKOUT_NOTIFICATION_UNTRACKED =
+                raw::GIT_CHECKOUT_NOTIFY_UNTRACKED as u32,
+        #[doc = "An ignored file was found in the working directory"]
+        const CHECKOUT_NOTIFICATION_IGNORED =
+                raw::GIT_CHECKOUT_NOTIFY_IGNORED as u32,
+    }
+}
+
 mod call;
 mod panic;
 
 pub mod build;
 
+mod attr;
 mod blob;
 mod branch;
 mod buf;
+mod cert;
 mod commit;
 mod config;
 mod cred;
 mod diff;
 mod error;
+mod filter;
+mod ignore;
 mod index;
+mod log_filter;
+mod mailmap;
+mod message;
 mod note;
 mod object;
 mod oid;
+mod oid_array;
 mod pathspec;
 mod push;
 mod reference;
@@ -299,7 +457,10 @@ mod remote_callbacks;
 mod repo;
 mod revspec;
 mod revwalk;
+mod rewrite;
+mod sign;
 mod signature;
+mod sparse;
 mod status;
 mod string_array;
 mod submodule;
@@ -321,6 +482,10 @@ fn init() {
     extern fn shutdown() { unsafe { raw::git_libgit2_shutdown() } }
 }
 
+fn io_error(e: std::io::IoError) -> Error {
+    Error::from_str(e.to_string().as_slice())
+}
+
 unsafe fn opt_bytes<'a, T>(_: &'a T,
                            c: *const libc::c_char) -> Option<&'a [u8]> {
     if c.is_null() {
@@ -331,6 +496,39 @@ unsafe fn opt_bytes<'a, T>(_: &'a T,
     }
 }
 
+impl FileMode {
+    /// Convert a raw file mode to a `FileMode`, returning `None` if it is not
+    /// one of the modes git records.
+    pub fn from_raw(raw: u32) -> Option<FileMode> {
+        match raw {
+            n if n == raw::GIT_FILEMODE_UNREADABLE as u32 => {
+                Some(FileMode::Unreadable)
+            }
+            n if n == raw::GIT_FILEMODE_TREE as u32 => Some(FileMode::Tree),
+            n if n == raw::GIT_FILEMODE_BLOB as u32 => Some(FileMode::Blob),
+            n if n == raw::GIT_FILEMODE_BLOB_EXECUTABLE as u32 => {
+                Some(FileMode::BlobExecutable)
+            }
+            n if n == raw::GIT_FILEMODE_LINK as u32 => Some(FileMode::Link),
+            n if n == raw::GIT_FILEMODE_COMMIT as u32 => Some(FileMode::Commit),
+            _ => None,
+        }
+    }
+
+    /// Convert this mode into its raw representation.
+    pub fn raw(&self) -> u32 {
+        let raw = match *self {
+            FileMode::Unreadable => raw::GIT_FILEMODE_UNREADABLE,
+            FileMode::Tree => raw::GIT_FILEMODE_TREE,
+            FileMode::Blob => raw::GIT_FILEMODE_BLOB,
+            FileMode::BlobExecutable => raw::GIT_FILEMODE_BLOB_EXECUTABLE,
+            FileMode::Link => raw::GIT_FILEMODE_LINK,
+            FileMode::Commit => raw::GIT_FILEMODE_COMMIT,
+        };
+        raw as u32
+    }
+}
+
 impl ObjectType {
     /// Convert an object type to its string representation.
     pub fn str(&self) -> &'static str {
@@ -379,6 +577,17 @@ impl fmt::Show for ObjectType {
     }
 }
 
+impl AutotagOption {
+    /// Convert a raw autotag option to an AutotagOption
+    pub fn from_raw(raw: raw::git_remote_autotag_option_t) -> AutotagOption {
+        match raw {
+            raw::GIT_REMOTE_DOWNLOAD_TAGS_AUTO => AutotagOption::Auto,
+            raw::GIT_REMOTE_DOWNLOAD_TAGS_NONE => AutotagOption::None,
+            raw::GIT_REMOTE_DOWNLOAD_TAGS_ALL => AutotagOption::All,
+        }
+    }
+}
+
 impl ConfigLevel {
     /// Converts a raw configuration level to a ConfigLevel
     pub fn from_raw(raw: raw::git_config_level_t) -> ConfigLevel {
@@ -393,6 +602,46 @@ impl ConfigLevel {
     }
 }
 
+impl SubmoduleUpdate {
+    /// Converts a raw submodule update rule to a SubmoduleUpdate
+    pub fn from_raw(raw: raw::git_submodule_update_t) -> SubmoduleUpdate {
+        match raw {
+            raw::GIT_SUBMODULE_UPDATE_CHECKOUT => SubmoduleUpdate::Checkout,
+            raw::GIT_SUBMODULE_UPDATE_REBASE => SubmoduleUpdate::Rebase,
+            raw::GIT_SUBMODULE_UPDATE_MERGE => SubmoduleUpdate::Merge,
+            raw::GIT_SUBMODULE_UPDATE_NONE => SubmoduleUpdate::None,
+            raw::GIT_SUBMODULE_UPDATE_DEFAULT |
+            raw::GIT_SUBMODULE_UPDATE_RESET => SubmoduleUpdate::Default,
+        }
+    }
+}
+
+impl SubmoduleIgnore {
+    /// Converts a raw submodule ignore rule to a SubmoduleIgnore
+    pub fn from_raw(raw: raw::git_submodule_ignore_t) -> SubmoduleIgnore {
+        match raw {
+            raw::GIT_SUBMODULE_IGNORE_NONE => SubmoduleIgnore::None,
+            raw::GIT_SUBMODULE_IGNORE_UNTRACKED => SubmoduleIgnore::Untracked,
+            raw::GIT_SUBMODULE_IGNORE_DIRTY => SubmoduleIgnore::Dirty,
+            raw::GIT_SUBMODULE_IGNORE_ALL => SubmoduleIgnore::All,
+            raw::GIT_SUBMODULE_IGNORE_DEFAULT |
+            raw::GIT_SUBMODULE_IGNORE_RESET => SubmoduleIgnore::Default,
+        }
+    }
+}
+
+impl SubmoduleRecurse {
+    /// Converts a raw fetch recursion setting to a SubmoduleRecurse
+    pub fn from_raw(raw: raw::git_submodule_recurse_t) -> SubmoduleRecurse {
+        match raw {
+            raw::GIT_SUBMODULE_RECURSE_YES => SubmoduleRecurse::Yes,
+            raw::GIT_SUBMODULE_RECURSE_ONDEMAND => SubmoduleRecurse::OnDemand,
+            raw::GIT_SUBMODULE_RECURSE_NO |
+            raw::GIT_SUBMODULE_RECURSE_RESET => SubmoduleRecurse::No,
+        }
+    }
+}
+
 bitflags! {
     #[doc = "
 Flags for repository status
@@ -0,0 +1,413 @@
+use std::str;
+use std::collections::HashSet;
+use regex::Regex;
+
+use {Repository, Revwalk, Commit, Signature, Error, Time, Diff, DiffOptions};
+use {Oid, Tree};
+use {Delta, Pathspec, Mailmap, PATHSPEC_NO_MATCH_ERROR};
+
+/// A filter layered on top of a `Revwalk` which limits the commits it yields
+/// in the same way as the options to `git log`.
+///
+/// The order and range of the walk are still configured on the `Revwalk`
+/// itself; this only decides which of the commits it visits are returned.
+pub struct LogFilter<'repo> {
+    repo: &'repo Repository,
+    walk: Revwalk<'repo>,
+    paths: Vec<String>,
+    follow: Option<String>,
+    author: Option<Regex>,
+    committer: Option<Regex>,
+    mailmap: Option<Mailmap>,
+    since: Option<Time>,
+    until: Option<Time>,
+    merges: Option<bool>,
+    skip: uint,
+    max_count: Option<uint>,
+    yielded: uint,
+    parents: HashSet<Oid>,
+    followed: HashSet<Oid>,
+}
+
+impl<'repo> LogFilter<'repo> {
+    /// Creates a new filter over the commits visited by `walk`, which must
+    /// have been created from `repo`.
+    ///
+    /// By default every commit of the walk is yielded.
+    pub fn new(repo: &'repo Repository, walk: Revwalk<'repo>)
+               -> LogFilter<'repo> {
+        LogFilter {
+            repo: repo,
+            walk: walk,
+            paths: Vec::new(),
+            follow: None,
+            author: None,
+            committer: None,
+            mailmap: None,
+            since: None,
+            until: None,
+            merges: None,
+            skip: 0,
+            max_count: None,
+            yielded: 0,
+            parents: HashSet::new(),
+            followed: HashSet::new(),
+        }
+    }
+
+    /// Only yield commits which modify paths matching `spec`, as
+    /// `git log -- <spec>` does.
+    ///
+    /// History is simplified as by default in git: a commit which leaves the
+    /// paths unchanged from one of its parents is skipped and only that parent
+    /// is followed, so side branches whose changes to the paths were discarded
+    /// by a merge are not yielded. Every other commit is yielded, root commits
+    /// only if they contain a matching path.
+    ///
+    /// Simplification requires the walk to visit children before their
+    /// parents, as the `SORT_TOPOLOGICAL` sorting mode guarantees.
+    pub fn path(&mut self, spec: &str) -> &mut LogFilter<'repo> {
+        self.paths.push(spec.to_string());
+        self
+    }
+
+    /// Only yield commits which modify the file at `path`, continuing with its
+    /// previous name when a commit is found to have renamed it, as
+    /// `git log --follow -- <path>` does.
+    ///
+    /// This overrides any pathspecs added with `path`. Renames are detected
+    /// between a commit and its first parent only, and are only followed if
+    /// the walk visits children before their parents.
+    pub fn follow(&mut self, path: &str) -> &mut LogFilter<'repo> {
+        self.follow = Some(path.to_string());
+        self
+    }
+
+    /// Only yield commits whose author, formatted as `Name <email>`, matches
+    /// `re`.
+    pub fn author(&mut self, re: Regex) -> &mut LogFilter<'repo> {
+        self.author = Some(re);
+        self
+    }
+
+    /// Only yield commits whose committer, formatted as `Name <email>`,
+    /// matches `re`.
+    pub fn committer(&mut self, re: Regex) -> &mut LogFilter<'repo> {
+        self.committer = Some(re);
+        self
+    }
+
+    /// Map authors and committers to their canonical identity with `mailmap`
+    /// before matching them, as `git log --use-mailmap` does.
+    pub fn mailmap(&mut self, mailmap: Mailmap) -> &mut LogFilter<'repo> {
+        self.mailmap = Some(mailmap);
+        self
+    }
+
+    /// Only yield commits committed at or after `time`.
+    pub fn since(&mut self, time: Time) -> &mut LogFilter<'repo> {
+        self.since = Some(time);
+        self
+    }
+
+    /// Only yield commits committed at or before `time`.
+    pub fn until(&mut self, time: Time) -> &mut LogFilter<'repo> {
+        self.until = Some(time);
+        self
+    }
+
+    /// Only yield merge commits.
+    pub fn merges_only(&mut self) -> &mut LogFilter<'repo> {
+        self.merges = Some(true);
+        self
+    }
+
+    /// Do not yield merge commits.
+    pub fn no_merges(&mut self) -> &mut LogFilter<'repo> {
+        self.merges = Some(false);
+        self
+    }
+
+    /// Skip the first `n` commits which pass all other filters.
+    pub fn skip(&mut self, n: uint) -> &mut LogFilter<'repo> {
+        self.skip = n;
+        self
+    }
+
+    /// Stop after yielding `n` commits.
+    pub fn max_count(&mut self, n: uint) -> &mut LogFilter<'repo> {
+        self.max_count = Some(n);
+        self
+    }
+
+    /// Gain access to the underlying revwalk.
+    pub fn revwalk(&mut self) -> &mut Revwalk<'repo> { &mut self.walk }
+
+    fn matches(&mut self, commit: &Commit<'repo>) -> Result<bool, Error> {
+        // Renames must be tracked through every commit touching the followed
+        // path, and history simplified through every commit visited, so those
+        // checks come before any other filter.
+        if self.follow.is_some() && !try!(self.follow_matches(commit)) {
+            return Ok(false)
+        }
+        if self.follow.is_none() && self.paths.len() > 0 &&
+           !try!(self.simplify(commit)) {
+            return Ok(false)
+        }
+
+        let parents = commit.parents().len();
+        match self.merges {
+            Some(true) if parents < 2 => return Ok(false),
+            Some(false) if parents > 1 => return Ok(false),
+            _ => {}
+        }
+        let when = commit.time().seconds();
+        match self.since {
+            Some(ref t) if when < t.seconds() => return Ok(false),
+            _ => {}
+        }
+        match self.until {
+            Some(ref t) if when > t.seconds() => return Ok(false),
+            _ => {}
+        }
+        let (author, committer) = match self.mailmap {
+            Some(ref mm) => (try!(commit.author_with_mailmap(mm)),
+                             try!(commit.committer_with_mailmap(mm))),
+            None => (commit.author(), commit.committer()),
+        };
+        Ok(sig_matches(&self.author, author) &&
+           sig_matches(&self.committer, committer))
+    }
+
+    // Decide whether `commit` is part of the history of the configured paths,
+    // recording which of its parents the simplified history continues with.
+    //
+    // A commit whose children all dropped it as a parent is pruned, along with
+    // any of its own parents not reached through another child.
+    fn simplify(&mut self, commit: &Commit<'repo>) -> Result<bool, Error> {
+        let id = commit.id();
+        let pruned = self.parents.contains(&id) && !self.followed.contains(&id);
+        for parent in commit.parent_ids() {
+            self.parents.insert(parent);
+        }
+        if pruned { return Ok(false) }
+
+        let tree = try!(commit.tree());
+        if commit.parents().len() == 0 {
+            let ps = try!(Pathspec::new(self.paths.iter()));
+            return Ok(ps.match_tree(&tree, PATHSPEC_NO_MATCH_ERROR).is_ok())
+        }
+        for parent in commit.parents() {
+            let old = try!(parent.tree());
+            if !try!(differs(self.repo, &old, &tree, self.paths.as_slice())) {
+                self.followed.insert(parent.id());
+                return Ok(false)
+            }
+        }
+        for parent in commit.parent_ids() {
+            self.followed.insert(parent);
+        }
+        Ok(true)
+    }
+
+    // Check whether `commit` touches the followed path, switching to the
+    // path's previous name if `commit` renamed it.
+    fn follow_matches(&mut self, commit: &Commit<'repo>) -> Result<bool, Error> {
+        let path = self.follow.clone().unwrap();
+        if !try!(touches(self.repo, commit, &[path.clone()])) {
+            return Ok(false)
+        }
+        if commit.parents().len() != 1 { return Ok(true) }
+
+        let old = try!(try!(commit.parent(0)).tree());
+        let new = try!(commit.tree());
+        let mut diff = try!(Diff::tree_to_tree(self.repo, Some(&old),
+                                               Some(&new), None));
+        try!(diff.find_renames());
+        for delta in diff.deltas() {
+            match delta.status() {
+                Delta::Renamed => {}
+                _ => continue,
+            }
+            if delta.new_file().path_bytes() != Some(path.as_bytes()) {
+                continue
+            }
+            let from = delta.old_file();
+            match from.path_bytes().and_then(|p| str::from_utf8(p).ok()) {
+                Some(p) => self.follow = Some(p.to_string()),
+                None => {}
+            }
+            break
+        }
+        Ok(true)
+    }
+}
+
+impl<'repo> Iterator<Result<Commit<'repo>, Error>> for LogFilter<'repo> {
+    fn next(&mut self) -> Option<Result<Commit<'repo>, Error>> {
+        match self.max_count {
+            Some(n) if self.yielded >= n => return None,
+            _ => {}
+        }
+        loop {
+            let id = match self.walk.next() {
+                Some(id) => id,
+                None => return None,
+            };
+            let commit = match self.repo.find_commit(id) {
+                Ok(commit) => commit,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.matches(&commit) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue
+            }
+            self.yielded += 1;
+            return Some(Ok(commit))
+        }
+    }
+}
+
+// Test whether `commit` modifies any path matching `specs` relative to each of
+// its parents.
+fn touches(repo: &Repository, commit: &Commit, specs: &[String])
+           -> Result<bool, Error> {
+    let tree = try!(commit.tree());
+    if commit.parents().len() == 0 {
+        let ps = try!(Pathspec::new(specs.iter()));
+        return Ok(ps.match_tree(&tree, PATHSPEC_NO_MATCH_ERROR).is_ok())
+    }
+    for parent in commit.parents() {
+        let parent = try!(parent.tree());
+        if !try!(differs(repo, &parent, &tree, specs)) { return Ok(false) }
+    }
+    Ok(true)
+}
+
+// Test whether any path matching `specs` differs between `old` and `new`.
+fn differs(repo: &Repository, old: &Tree, new: &Tree, specs: &[String])
+           -> Result<bool, Error> {
+    let mut opts = DiffOptions::new();
+    for spec in specs.iter() {
+        opts.pathspec(spec.as_slice());
+    }
+    let diff = try!(Diff::tree_to_tree(repo, Some(old), Some(new),
+                                       Some(&mut opts)));
+    Ok(diff.deltas().len() > 0)
+}
+
+fn sig_matches(re: &Option<Regex>, sig: Signature) -> bool {
+    match *re {
+        Some(ref re) => {
+            let s = format!("{} <{}>", String::from_utf8_lossy(sig.name_bytes()),
+                            String::from_utf8_lossy(sig.email_bytes()));
+            re.is_match(s.as_slice())
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{fs, File};
+    use regex::Regex;
+    use {Repository, Signature, Time, Oid, LogFilter, Mailmap, ResetType};
+    use {SORT_TOPOLOGICAL, SORT_TIME};
+    use test::commit_files_as;
+
+    fn sig(author: &str, time: u64) -> Signature<'static> {
+        Signature::new(author, "email", time, 0).unwrap()
+    }
+
+    fn log(repo: &Repository, f: |&mut LogFilter|) -> Vec<Oid> {
+        let mut walk = repo.revwalk().unwrap();
+        walk.set_sorting(SORT_TOPOLOGICAL | SORT_TIME);
+        walk.push_head().unwrap();
+        let mut filter = LogFilter::new(repo, walk);
+        f(&mut filter);
+        filter.map(|c| c.unwrap().id()).collect()
+    }
+
+    #[test]
+    fn smoke() {
+        let (_td, repo) = ::test::repo_init();
+        let a = commit_files_as(&repo, &[("a", "a")], &sig("alice", 1000));
+        let b = commit_files_as(&repo, &[("b", "b")], &sig("bob", 2000));
+        let a2 = commit_files_as(&repo, &[("a", "aa")], &sig("bob", 3000));
+
+        assert_eq!(log(&repo, |_| {}).len(), 4);
+        assert_eq!(log(&repo, |f| { f.path("a"); }), vec![a2, a]);
+        assert_eq!(log(&repo, |f| { f.author(Regex::new("^bob ").unwrap()); }),
+                   vec![a2, b]);
+        assert_eq!(log(&repo, |f| {
+            f.since(Time::new(1500, 0)).until(Time::new(2500, 0));
+        }), vec![b]);
+        assert_eq!(log(&repo, |f| { f.skip(1).max_count(2); }), vec![b, a]);
+        assert_eq!(log(&repo, |f| {
+            let mm = Mailmap::from_buffer("bob <email> alice <email>").unwrap();
+            f.author(Regex::new("^bob ").unwrap()).mailmap(mm);
+        }), vec![a2, b, a]);
+        assert_eq!(log(&repo, |f| { f.merges_only(); }), vec![]);
+        assert_eq!(log(&repo, |f| { f.no_merges(); }).len(), 4);
+    }
+
+    #[test]
+    fn follow() {
+        let (td, repo) = ::test::repo_init();
+        let contents = "a file with enough content to be detected as a rename\n";
+        let a = commit_files_as(&repo, &[("old", contents)], &sig("a", 1000));
+        commit_files_as(&repo, &[("other", "x")], &sig("a", 2000));
+        {
+            fs::unlink(&td.path().join("old")).unwrap();
+            let mut index = repo.index().unwrap();
+            index.remove_path(&Path::new("old")).unwrap();
+            index.write().unwrap();
+        }
+        let b = commit_files_as(&repo, &[("new", contents)], &sig("a", 3000));
+        let c = commit_files_as(&repo, &[("new", "changed\n")],
+                                &sig("a", 4000));
+
+        assert_eq!(log(&repo, |f| { f.path("new"); }), vec![c, b]);
+        assert_eq!(log(&repo, |f| { f.follow("new"); }), vec![c, b, a]);
+    }
+
+    #[test]
+    fn simplify_merges() {
+        let (td, repo) = ::test::repo_init();
+        let base = commit_files_as(&repo, &[("a", "1")], &sig("a", 1000));
+        let side = commit_files_as(&repo, &[("a", "side")], &sig("a", 2000));
+        let obj = repo.find_object(base, None).unwrap();
+        repo.reset(&obj, ResetType::Hard, None, None).unwrap();
+        let main = commit_files_as(&repo, &[("b", "main")], &sig("a", 3000));
+
+        // A merge keeping `a` from `main` discards the change made on `side`,
+        // so `side` is not part of the history of `a`.
+        let main = repo.find_commit(main).unwrap();
+        let side = repo.find_commit(side).unwrap();
+        let tree = main.tree().unwrap();
+        let s = sig("a", 4000);
+        let merge = repo.commit(Some("HEAD"), &s, &s, "merge", &tree,
+                                &[&main, &side]).unwrap();
+        assert_eq!(log(&repo, |f| { f.path("a"); }), vec![base]);
+        assert_eq!(log(&repo, |f| { f.path("b"); }), vec![main.id()]);
+
+        // A merge resolving `a` differently from both parents is kept along
+        // with the history of both sides.
+        File::create(&td.path().join("a")).write_str("3").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(&Path::new("a")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let merge = repo.find_commit(merge).unwrap();
+        let s = sig("a", 5000);
+        let remerge = repo.commit(Some("HEAD"), &s, &s, "remerge", &tree,
+                                  &[&merge, &side]).unwrap();
+        assert_eq!(log(&repo, |f| { f.path("a"); }),
+                   vec![remerge, side.id(), base]);
+    }
+}
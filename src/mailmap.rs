@@ -0,0 +1,200 @@
+use std::ascii::AsciiExt;
+
+use {Error, Signature};
+
+/// A mailmap, used to map the names and emails found in commits to the
+/// canonical identities of their authors.
+///
+/// See `git help mailmap` for the format of the entries.
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    real_name: Option<String>,
+    real_email: Option<String>,
+    replace_name: Option<String>,
+    replace_email: String,
+}
+
+impl Mailmap {
+    /// Creates a new empty mailmap.
+    pub fn new() -> Mailmap {
+        Mailmap { entries: Vec::new() }
+    }
+
+    /// Creates a new mailmap from the contents of a `.mailmap` file.
+    ///
+    /// Lines which are not valid entries are ignored, as git does. When
+    /// several entries match the same identity, the last one wins.
+    pub fn from_buffer(buf: &str) -> Result<Mailmap, Error> {
+        let mut ret = Mailmap::new();
+        for line in buf.lines_any() {
+            if line.trim_left().starts_with("#") { continue }
+            let (name1, email1, rest) = match parse_name_and_email(line) {
+                Some(parts) => parts,
+                None => continue,
+            };
+            match parse_name_and_email(rest) {
+                Some((name2, email2, _)) => {
+                    try!(ret.add_entry(name1, Some(email1), name2, email2));
+                }
+                // A lone email maps nothing.
+                None if name1.is_none() => continue,
+                None => try!(ret.add_entry(name1, None, None, email1)),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Add a single entry to the mailmap.
+    ///
+    /// Identities with the email `replace_email`, and the name `replace_name`
+    /// if one is given, are mapped to `real_name` and `real_email`. A `None`
+    /// real name or email leaves that part of the identity unchanged.
+    pub fn add_entry(&mut self, real_name: Option<&str>,
+                     real_email: Option<&str>, replace_name: Option<&str>,
+                     replace_email: &str) -> Result<(), Error> {
+        if real_name.is_none() && real_email.is_none() {
+            return Err(Error::from_str("mailmap entry maps to nothing"))
+        }
+        self.entries.push(Entry {
+            real_name: real_name.map(|s| s.to_string()),
+            real_email: real_email.map(|s| s.to_string()),
+            replace_name: replace_name.map(|s| s.to_string()),
+            replace_email: replace_email.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Resolve a name and email to the canonical identity they map to.
+    ///
+    /// The name and email are returned unchanged if the mailmap has no entry
+    /// for them. Names and emails are compared without regard to case, and an
+    /// entry naming both the name and the email takes precedence over one
+    /// naming only the email.
+    pub fn resolve(&self, name: &str, email: &str)
+                   -> Result<(String, String), Error> {
+        let matches = |e: &&Entry| {
+            e.replace_email.as_slice().eq_ignore_ascii_case(email)
+        };
+        let entry = self.entries.iter().rev().filter(|e| matches(e)).find(|e| {
+            match e.replace_name {
+                Some(ref n) => n.as_slice().eq_ignore_ascii_case(name),
+                None => false,
+            }
+        }).or_else(|| {
+            self.entries.iter().rev().filter(|e| matches(e))
+                .find(|e| e.replace_name.is_none())
+        });
+        Ok(match entry {
+            Some(e) => {
+                (e.real_name.as_ref().map(|s| s.as_slice()).unwrap_or(name)
+                  .to_string(),
+                 e.real_email.as_ref().map(|s| s.as_slice()).unwrap_or(email)
+                  .to_string())
+            }
+            None => (name.to_string(), email.to_string()),
+        })
+    }
+
+    /// Resolve a signature to the canonical identity it maps to, keeping its
+    /// timestamp.
+    pub fn resolve_signature(&self, sig: &Signature)
+                             -> Result<Signature<'static>, Error> {
+        let name = String::from_utf8_lossy(sig.name_bytes());
+        let email = String::from_utf8_lossy(sig.email_bytes());
+        let (name, email) = try!(self.resolve(name.as_slice(),
+                                              email.as_slice()));
+        let when = sig.when();
+        Signature::new(name.as_slice(), email.as_slice(),
+                       when.seconds() as u64, when.offset_minutes())
+    }
+}
+
+// Parse an optional name followed by an email in angle brackets, returning
+// them along with the rest of the line.
+fn parse_name_and_email(s: &str) -> Option<(Option<&str>, &str, &str)> {
+    let start = match s.find('<') {
+        Some(i) => i,
+        None => return None,
+    };
+    let end = match s.slice_from(start).find('>') {
+        Some(i) => start + i,
+        None => return None,
+    };
+    let name = s.slice_to(start).trim();
+    let name = if name.len() == 0 {None} else {Some(name)};
+    Some((name, s.slice(start + 1, end), s.slice_from(end + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::File;
+    use {Mailmap, Signature};
+
+    #[test]
+    fn smoke() {
+        let mm = Mailmap::from_buffer("\
+Real Name <real@example.com> <old@example.com>
+<fixed@example.com> Some Name <typo@example.com>
+").unwrap();
+        assert_eq!(mm.resolve("whoever", "old@example.com").unwrap(),
+                   ("Real Name".to_string(), "real@example.com".to_string()));
+        assert_eq!(mm.resolve("Some Name", "typo@example.com").unwrap(),
+                   ("Some Name".to_string(), "fixed@example.com".to_string()));
+        assert_eq!(mm.resolve("Other Name", "typo@example.com").unwrap(),
+                   ("Other Name".to_string(), "typo@example.com".to_string()));
+
+        let sig = Signature::new("x", "old@example.com", 1234, 60).unwrap();
+        let sig = mm.resolve_signature(&sig).unwrap();
+        assert_eq!(sig.name(), Some("Real Name"));
+        assert_eq!(sig.email(), Some("real@example.com"));
+        assert_eq!(sig.when().seconds(), 1234);
+
+        let mut mm = Mailmap::new();
+        mm.add_entry(Some("New"), None, None, "a@example.com").unwrap();
+        assert_eq!(mm.resolve("Old", "A@Example.com").unwrap(),
+                   ("New".to_string(), "A@Example.com".to_string()));
+        mm.add_entry(Some("Newer"), None, Some("old"), "a@example.com")
+          .unwrap();
+        mm.add_entry(Some("Newest"), None, None, "a@example.com").unwrap();
+        assert_eq!(mm.resolve("Old", "a@example.com").unwrap().0.as_slice(),
+                   "Newer");
+        assert_eq!(mm.resolve("Other", "a@example.com").unwrap().0.as_slice(),
+                   "Newest");
+        assert!(mm.add_entry(None, None, None, "a@example.com").is_err());
+
+        let mm = Mailmap::from_buffer("# comment\nno email\n<a@example.com>\n\
+                                       Name <b@example.com>\n").unwrap();
+        assert_eq!(mm.resolve("a", "b").unwrap(),
+                   ("a".to_string(), "b".to_string()));
+        assert_eq!(mm.resolve("x", "b@example.com").unwrap(),
+                   ("Name".to_string(), "b@example.com".to_string()));
+    }
+
+    #[test]
+    fn repository() {
+        let (_td, repo) = ::test::repo_init();
+        File::create(&repo.workdir().unwrap().join(".mailmap"))
+             .write_str("Canonical <canonical@example.com> <email>\n").unwrap();
+        let mm = repo.mailmap().unwrap();
+        let head = repo.head().unwrap();
+        let commit = repo.find_commit(head.target().unwrap()).unwrap();
+        let author = commit.author_with_mailmap(&mm).unwrap();
+        assert_eq!(author.name(), Some("Canonical"));
+        assert_eq!(author.email(), Some("canonical@example.com"));
+        let committer = commit.committer_with_mailmap(&mm).unwrap();
+        assert_eq!(committer.email(), Some("canonical@example.com"));
+        assert_eq!(commit.author().email(), Some("email"));
+
+        let file = repo.path().join("mailmap");
+        File::create(&file).write_str("Other <other@example.com> <email>\n")
+                           .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("mailmap.file", file.as_str().unwrap()).unwrap();
+        let mm = repo.mailmap().unwrap();
+        let author = commit.author_with_mailmap(&mm).unwrap();
+        assert_eq!(author.name(), Some("Other"));
+    }
+}
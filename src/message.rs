@@ -0,0 +1,215 @@
+use std::ascii::AsciiExt;
+use libc::c_char;
+
+use {raw, Buf, Error};
+
+/// The trailers of a message, such as `Signed-off-by: Name <email>`, as
+/// returned by `message_trailers`.
+pub struct MessageTrailers {
+    trailers: Vec<(String, String)>,
+}
+
+/// An iterator over the key/value pairs of `MessageTrailers`.
+pub struct MessageTrailersIter<'a> {
+    trailers: &'a MessageTrailers,
+    idx: uint,
+}
+
+/// Clean up a message the same way `git commit` does before it is stored.
+///
+/// Trailing whitespace is removed from every line, runs of empty lines are
+/// collapsed, leading and trailing empty lines are removed and a final newline
+/// is added. If `strip_comments` is true, lines starting with `comment_char`,
+/// `#` by default, are removed as well.
+pub fn message_prettify(message: &str, strip_comments: bool,
+                        comment_char: Option<u8>) -> Result<String, Error> {
+    let mut ret = Buf::new();
+    let comment_char = comment_char.unwrap_or(b'#') as c_char;
+    unsafe {
+        try_call!(raw::git_message_prettify(ret.raw(), message.to_c_str(),
+                                            strip_comments, comment_char));
+    }
+    Ok(ret.as_str().unwrap().to_string())
+}
+
+/// Parse the trailers at the end of a message, as `git interpret-trailers`
+/// does.
+///
+/// Trailers are the `Key: value` lines forming the last paragraph of a
+/// message, such as `Signed-off-by` or `Co-authored-by`. That paragraph is
+/// only taken as trailers if it is not the summary and every line in it is a
+/// trailer, apart from indented lines continuing the previous value. Lines
+/// starting with `#` are ignored.
+pub fn message_trailers(message: &str) -> Result<MessageTrailers, Error> {
+    let lines = message.lines_any().filter(|l| !l.starts_with("#"))
+                       .collect::<Vec<&str>>();
+    let end = lines.iter().rposition(|l| l.trim().len() > 0)
+                   .map(|i| i + 1).unwrap_or(0);
+    let start = lines.slice_to(end).iter().rposition(|l| l.trim().len() == 0)
+                     .map(|i| i + 1).unwrap_or(0);
+
+    // The summary paragraph never holds trailers.
+    let mut trailers = Vec::new();
+    if start == 0 {
+        return Ok(MessageTrailers { trailers: trailers })
+    }
+    for line in lines.slice(start, end).iter() {
+        if line.starts_with(" ") || line.starts_with("\t") {
+            match trailers.last_mut() {
+                Some(&mut (_, ref mut value)) => {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                    continue
+                }
+                None => return Ok(MessageTrailers { trailers: Vec::new() }),
+            }
+        }
+        match parse_trailer(*line) {
+            Some((key, value)) => {
+                trailers.push((key.to_string(), value.to_string()))
+            }
+            None => return Ok(MessageTrailers { trailers: Vec::new() }),
+        }
+    }
+    Ok(MessageTrailers { trailers: trailers })
+}
+
+// Split a `Key: value` line, where the key is made of letters, digits and
+// dashes.
+fn parse_trailer(line: &str) -> Option<(&str, &str)> {
+    let i = match line.find(':') {
+        Some(i) => i,
+        None => return None,
+    };
+    let key = line.slice_to(i).trim_right();
+    if key.len() == 0 ||
+       !key.chars().all(|c| c.is_alphanumeric() || c == '-') {
+        return None
+    }
+    Some((key, line.slice_from(i + 1).trim()))
+}
+
+/// Append trailers to a message, as `git interpret-trailers --trailer` does.
+///
+/// The trailers are added to the existing trailer block of the message, or in
+/// a new paragraph if it has none. A trailer whose key and value are already
+/// present is not added again, so signing off on a message twice is harmless.
+pub fn message_add_trailers(message: &str, trailers: &[(&str, &str)])
+                            -> Result<String, Error> {
+    let existing = try!(message_trailers(message));
+    let mut ret = message.trim_right().to_string();
+    let mut added: Vec<(&str, &str)> = Vec::new();
+    for &(key, value) in trailers.iter() {
+        if existing.iter().any(|(k, v)| same_trailer(k, v, key, value)) ||
+           added.iter().any(|&(k, v)| same_trailer(k, v, key, value)) {
+            continue
+        }
+        if existing.len() == 0 && added.len() == 0 && ret.len() > 0 {
+            ret.push_str("\n");
+        }
+        ret.push_str(format!("\n{}: {}", key, value).as_slice());
+        added.push((key, value));
+    }
+    ret.push_str("\n");
+    Ok(ret)
+}
+
+fn same_trailer(k1: &str, v1: &str, k2: &str, v2: &str) -> bool {
+    k1.eq_ignore_ascii_case(k2) && v1 == v2
+}
+
+impl MessageTrailers {
+    /// Returns the number of trailers.
+    pub fn len(&self) -> uint { self.trailers.len() }
+
+    /// Returns the key and value of the `i`th trailer, or `None` if `i` is out
+    /// of bounds.
+    pub fn get(&self, i: uint) -> Option<(&str, &str)> {
+        self.trailers.get(i).map(|&(ref k, ref v)| {
+            (k.as_slice(), v.as_slice())
+        })
+    }
+
+    /// Returns an iterator over the key/value pairs of the trailers, in the
+    /// order they appear in the message.
+    pub fn iter(&self) -> MessageTrailersIter {
+        MessageTrailersIter { trailers: self, idx: 0 }
+    }
+
+    /// Returns the values of all trailers whose key is `key`, compared without
+    /// regard to case.
+    pub fn values(&self, key: &str) -> Vec<&str> {
+        self.iter().filter(|&(k, _)| k.eq_ignore_ascii_case(key))
+                   .map(|(_, v)| v).collect()
+    }
+}
+
+impl<'a> Iterator<(&'a str, &'a str)> for MessageTrailersIter<'a> {
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let ret = self.trailers.get(self.idx);
+        if ret.is_some() { self.idx += 1 }
+        ret
+    }
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        let n = self.trailers.len() - self.idx;
+        (n, Some(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {message_prettify, message_trailers, message_add_trailers};
+
+    #[test]
+    fn prettify() {
+        assert_eq!(message_prettify("\n\nsummary  \n\n\n\nbody\n# comment\n",
+                                    false, None).unwrap().as_slice(),
+                   "summary\n\nbody\n# comment\n");
+        assert_eq!(message_prettify("summary\n# comment\n", true, None)
+                       .unwrap().as_slice(),
+                   "summary\n");
+        assert_eq!(message_prettify("summary\n; comment\n# kept\n", true,
+                                    Some(b';')).unwrap().as_slice(),
+                   "summary\n# kept\n");
+    }
+
+    #[test]
+    fn trailers() {
+        let msg = "summary\n\nbody\n\nSigned-off-by: a <a@example.com>\n\
+                   Co-authored-by: b <b@example.com>\n";
+        let trailers = message_trailers(msg).unwrap();
+        assert_eq!(trailers.len(), 2);
+        assert_eq!(trailers.get(0),
+                   Some(("Signed-off-by", "a <a@example.com>")));
+        assert_eq!(trailers.iter().collect::<Vec<_>>(),
+                   vec![("Signed-off-by", "a <a@example.com>"),
+                        ("Co-authored-by", "b <b@example.com>")]);
+        assert_eq!(trailers.values("signed-off-by"),
+                   vec!["a <a@example.com>"]);
+        assert!(trailers.get(2).is_none());
+        assert_eq!(message_trailers("summary\n\nbody\n").unwrap().len(), 0);
+        assert_eq!(message_trailers("Key: value\n").unwrap().len(), 0);
+        assert_eq!(message_trailers("summary\n\nKey: value\nprose\n")
+                       .unwrap().len(), 0);
+
+        let msg = "summary\n\nReviewed-by: a\n  and b\n# comment\n\n";
+        let trailers = message_trailers(msg).unwrap();
+        assert_eq!(trailers.iter().collect::<Vec<_>>(),
+                   vec![("Reviewed-by", "a and b")]);
+    }
+
+    #[test]
+    fn add_trailers() {
+        let sob = ("Signed-off-by", "a <a@example.com>");
+        assert_eq!(message_add_trailers("summary\n", &[sob]).unwrap()
+                       .as_slice(),
+                   "summary\n\nSigned-off-by: a <a@example.com>\n");
+        let msg = "summary\n\nSigned-off-by: a <a@example.com>\n";
+        assert_eq!(message_add_trailers(msg, &[sob]).unwrap().as_slice(), msg);
+        assert_eq!(message_add_trailers(msg, &[("Co-authored-by", "b"),
+                                               ("Co-authored-by", "b")])
+                       .unwrap().as_slice(),
+                   "summary\n\nSigned-off-by: a <a@example.com>\n\
+                    Co-authored-by: b\n");
+    }
+}
@@ -0,0 +1,66 @@
+use std::mem;
+use std::raw as stdraw;
+use std::slice;
+
+use {raw, Oid};
+
+/// An oid array structure used by libgit2
+///
+/// Some apis return arrays of oids which originate from libgit2. This wrapper
+/// type behaves a little like `Vec<Oid>` but does so without copying the
+/// underlying oids until necessary.
+pub struct OidArray {
+    raw: raw::git_oidarray,
+}
+
+/// A forward iterator over the oids of an array.
+pub struct OidArrayItems<'a> {
+    inner: slice::Items<'a, raw::git_oid>,
+}
+
+impl OidArray {
+    /// Creates a new oid array from the raw representation.
+    ///
+    /// This is unsafe because it consumes ownership of the array and there is
+    /// no guarantee that the array itself is valid or that no one else is using
+    /// it.
+    pub unsafe fn from_raw(raw: raw::git_oidarray) -> OidArray {
+        OidArray { raw: raw }
+    }
+
+    /// Returns None if `i` is out of bounds.
+    pub fn get(&self, i: uint) -> Option<Oid> {
+        self.raw_oids().get(i).map(|oid| unsafe { Oid::from_raw(oid) })
+    }
+
+    /// Returns an iterator over the oids contained within this array.
+    pub fn iter(&self) -> OidArrayItems {
+        OidArrayItems { inner: self.raw_oids().iter() }
+    }
+
+    /// Returns the number of oids in this array.
+    pub fn len(&self) -> uint { self.raw.count as uint }
+
+    fn raw_oids(&self) -> &[raw::git_oid] {
+        if self.raw.ids.is_null() { return &[] }
+        unsafe {
+            mem::transmute(stdraw::Slice {
+                data: self.raw.ids as *const raw::git_oid,
+                len: self.raw.count as uint,
+            })
+        }
+    }
+}
+
+impl<'a> Iterator<Oid> for OidArrayItems<'a> {
+    fn next(&mut self) -> Option<Oid> {
+        self.inner.next().map(|oid| unsafe { Oid::from_raw(oid) })
+    }
+    fn size_hint(&self) -> (uint, Option<uint>) { self.inner.size_hint() }
+}
+
+impl Drop for OidArray {
+    fn drop(&mut self) {
+        unsafe { raw::git_oidarray_free(&mut self.raw) }
+    }
+}
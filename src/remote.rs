@@ -5,8 +5,8 @@ use std::slice;
 use std::str;
 use libc;
 
-use {raw, Direction, Error, Refspec, Oid};
-use {Signature, Push, RemoteCallbacks, Progress};
+use {raw, Direction, Error, Refspec, Oid, AutotagOption};
+use {Signature, Push, RemoteCallbacks, Progress, Repository};
 
 /// A structure representing a [remote][1] of a git repository.
 ///
@@ -38,6 +38,28 @@ pub struct RemoteHead<'remote> {
     marker3: marker::NoSync,
 }
 
+/// An owned copy of the references advertised by a remote server, returned
+/// from `Remote::list_owned` and `Remote::ls`.
+///
+/// Unlike `RemoteHead`, this remains valid after the remote has been
+/// disconnected or freed. The capabilities advertised by the server are not
+/// included, as libgit2 0.22 does not expose them.
+pub struct RemoteHeads {
+    heads: Vec<RemoteHeadInfo>,
+    default_branch: Option<String>,
+}
+
+/// An owned description of a reference advertised by a remote server.
+#[deriving(Clone)]
+pub struct RemoteHeadInfo {
+    name: String,
+    oid: Oid,
+    loid: Oid,
+    local: bool,
+    symref_target: Option<String>,
+    peeled: Option<Oid>,
+}
+
 impl<'repo, 'cb> Remote<'repo, 'cb> {
     /// Creates a new remote from its raw pointer.
     ///
@@ -53,6 +75,9 @@ impl<'repo, 'cb> Remote<'repo, 'cb> {
         }
     }
 
+    /// Get access to the underlying raw pointer.
+    pub fn raw(&self) -> *mut raw::git_remote { self.raw }
+
     /// Ensure the remote name is well-formed.
     pub fn is_valid_name(remote_name: &str) -> bool {
         ::init();
@@ -60,6 +85,32 @@ impl<'repo, 'cb> Remote<'repo, 'cb> {
         unsafe { raw::git_remote_is_valid_name(remote_name.as_ptr()) == 1 }
     }
 
+    /// List the references advertised by the remote at `url`.
+    ///
+    /// This is the equivalent of `git ls-remote <url>`. An anonymous remote
+    /// is created in `repo` for the duration of the call, so its
+    /// `url.<base>.insteadOf` settings apply but nothing is written to its
+    /// configuration.
+    pub fn ls(repo: &Repository, url: &str,
+              callbacks: Option<&'cb mut RemoteCallbacks<'cb>>)
+              -> Result<RemoteHeads, Error> {
+        let mut raw = 0 as *mut raw::git_remote;
+        let mut remote = unsafe {
+            try_call!(raw::git_remote_create_anonymous(&mut raw, repo.raw(),
+                                              url.to_c_str(),
+                                              0 as *const libc::c_char));
+            Remote::from_raw(raw)
+        };
+        match callbacks {
+            Some(cbs) => remote.set_callbacks(cbs),
+            None => {}
+        }
+        try!(remote.connect(Direction::Fetch));
+        let heads = remote.list_owned();
+        remote.disconnect();
+        heads
+    }
+
     /// Get the remote's name.
     ///
     /// Returns `None` if this remote has not yet been named or if the name is
@@ -173,6 +224,19 @@ impl<'repo, 'cb> Remote<'repo, 'cb> {
         }
     }
 
+    /// Retrieve the tag auto-follow setting.
+    pub fn download_tags(&self) -> AutotagOption {
+        unsafe { AutotagOption::from_raw(raw::git_remote_autotag(&*self.raw)) }
+    }
+
+    /// Set the tag auto-follow setting for this in-memory remote.
+    ///
+    /// The configuration is not changed; see `Repository::remote_set_autotag`
+    /// to persist the setting.
+    pub fn set_download_tags(&mut self, value: AutotagOption) {
+        unsafe { call!(raw::git_remote_set_autotag(self.raw, value)) }
+    }
+
     /// Set the remote's list of fetch refspecs
     pub fn set_fetch_refspecs<T: ToCStr, I: Iterator<T>>(&mut self, i: I)
                                                          -> Result<(), Error> {
@@ -333,6 +397,42 @@ impl<'repo, 'cb> Remote<'repo, 'cb> {
                                 &[RemoteHead]>(slice))
         }
     }
+
+    /// Get an owned copy of the remote repository's reference advertisement.
+    ///
+    /// This is the same information as `list`, but it is copied out of the
+    /// connection so it may be held after the remote is disconnected. Peeled
+    /// `^{}` entries are folded into the tags they belong to, and the remote's
+    /// default branch is resolved from its `HEAD`.
+    ///
+    /// The remote must have connected to the remote repository.
+    pub fn list_owned(&self) -> Result<RemoteHeads, Error> {
+        let mut heads: Vec<RemoteHeadInfo> = Vec::new();
+        for head in try!(self.list()).iter() {
+            let name = head.name();
+            if name.ends_with("^{}") {
+                let tag = name.slice_to(name.len() - 3);
+                match heads.iter_mut().rev().find(|h| h.name.as_slice() == tag) {
+                    Some(h) => { h.peeled = Some(head.oid()); continue }
+                    None => {}
+                }
+            }
+            heads.push(RemoteHeadInfo {
+                name: name.to_string(),
+                oid: head.oid(),
+                loid: head.loid(),
+                local: head.is_local(),
+                symref_target: head.symref_target().map(|s| s.to_string()),
+                peeled: None,
+            });
+        }
+
+        let default_branch = RemoteHeads::resolve_default_branch(heads.as_slice());
+        Ok(RemoteHeads {
+            heads: heads,
+            default_branch: default_branch,
+        })
+    }
 }
 
 impl<'a, 'b> Iterator<Refspec<'a>> for Refspecs<'a, 'b> {
@@ -393,12 +493,85 @@ impl<'remote> RemoteHead<'remote> {
     }
 }
 
+impl RemoteHeads {
+    /// Returns an iterator over the advertised references, in the order the
+    /// server sent them.
+    pub fn iter(&self) -> slice::Items<RemoteHeadInfo> { self.heads.iter() }
+
+    /// Returns the number of advertised references, not counting peeled
+    /// entries.
+    pub fn len(&self) -> uint { self.heads.len() }
+
+    /// Look up an advertised reference by its full name.
+    pub fn find(&self, name: &str) -> Option<&RemoteHeadInfo> {
+        self.heads.iter().find(|h| h.name.as_slice() == name)
+    }
+
+    /// The name of the branch the remote's `HEAD` points to.
+    ///
+    /// If the server did not advertise `HEAD` as a symbolic reference, this is
+    /// guessed from the branches pointing at the same commit as `HEAD`,
+    /// preferring `refs/heads/master`. Returns `None` if the remote has no
+    /// `HEAD` or no branch matches it.
+    pub fn default_branch(&self) -> Option<&str> {
+        self.default_branch.as_ref().map(|s| s.as_slice())
+    }
+
+    fn resolve_default_branch(heads: &[RemoteHeadInfo]) -> Option<String> {
+        let head = match heads.iter().find(|h| h.name.as_slice() == "HEAD") {
+            Some(h) => h,
+            None => return None,
+        };
+        match head.symref_target {
+            Some(ref target) => return Some(target.clone()),
+            None => {}
+        }
+        let mut guess = None;
+        for h in heads.iter() {
+            if !h.name.as_slice().starts_with("refs/heads/") || h.oid != head.oid {
+                continue
+            }
+            if h.name.as_slice() == "refs/heads/master" {
+                return Some(h.name.clone())
+            }
+            if guess.is_none() {
+                guess = Some(h.name.clone());
+            }
+        }
+        guess
+    }
+}
+
+impl RemoteHeadInfo {
+    /// The full name of the reference, e.g. `refs/heads/master`.
+    pub fn name(&self) -> &str { self.name.as_slice() }
+
+    /// The object the reference points to on the remote.
+    pub fn oid(&self) -> Oid { self.oid }
+
+    /// The object the reference points to locally, if it is available.
+    pub fn loid(&self) -> Oid { self.loid }
+
+    /// Flag if the object is available locally.
+    pub fn is_local(&self) -> bool { self.local }
+
+    /// The target of the reference if it was advertised as a symbolic
+    /// reference, such as `HEAD`.
+    pub fn symref_target(&self) -> Option<&str> {
+        self.symref_target.as_ref().map(|s| s.as_slice())
+    }
+
+    /// For annotated tags, the object the tag ultimately points to, as
+    /// advertised in the corresponding `^{}` entry.
+    pub fn peeled(&self) -> Option<Oid> { self.peeled }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::TempDir;
     use std::cell::Cell;
     use url::Url;
-    use {Repository, Remote, RemoteCallbacks, Direction};
+    use {Repository, Remote, RemoteCallbacks, Direction, AutotagOption};
 
     #[test]
     fn smoke() {
@@ -476,8 +649,41 @@ mod tests {
     fn rename_remote() {
         let (_td, repo) = ::test::repo_init();
         repo.remote("origin", "foo").unwrap();
-        repo.remote_rename("origin", "foo").unwrap();
-        repo.remote_delete("foo").unwrap();
+        let problems = repo.remote_rename("origin", "foo").unwrap();
+        assert_eq!(problems.len(), 0);
+        repo.remote_add_fetch("foo", "refs/heads/*:refs/custom/*").unwrap();
+        let problems = repo.remote_rename("foo", "bar").unwrap();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems.get(0), Some("refs/heads/*:refs/custom/*"));
+        repo.remote_delete("bar").unwrap();
+    }
+
+    #[test]
+    fn config_helpers() {
+        let (_td, repo) = ::test::repo_init();
+        repo.remote("origin", "foo").unwrap();
+        repo.remote_add_fetch("origin", "refs/tags/*:refs/tags/*").unwrap();
+        repo.remote_add_push("origin", "refs/heads/master").unwrap();
+        repo.remote_set_url("origin", "bar").unwrap();
+        repo.remote_set_pushurl("origin", Some("baz")).unwrap();
+        repo.remote_set_autotag("origin", AutotagOption::All).unwrap();
+        repo.remote_set_prune("origin", true).unwrap();
+
+        let origin = repo.find_remote("origin").unwrap();
+        assert_eq!(origin.url(), Some("bar"));
+        assert_eq!(origin.pushurl(), Some("baz"));
+        assert_eq!(origin.refspecs().count(), 3);
+        assert_eq!(origin.download_tags(), AutotagOption::All);
+        let cfg = repo.config().unwrap();
+        assert!(cfg.get_bool("remote.origin.prune").unwrap());
+
+        repo.remote_set_pushurl("origin", None).unwrap();
+        repo.remote_set_autotag("origin", AutotagOption::Auto).unwrap();
+        let origin = repo.find_remote("origin").unwrap();
+        assert_eq!(origin.pushurl(), None);
+        assert_eq!(origin.download_tags(), AutotagOption::Auto);
+
+        assert!(repo.remote_add_fetch("bad name", "refs/*:refs/*").is_err());
     }
 
     #[test]
@@ -517,4 +723,50 @@ mod tests {
         origin.fetch(&[], None, None).unwrap();
         assert!(progress_hit.get());
     }
+
+    #[test]
+    fn list_owned() {
+        let (td, repo) = ::test::repo_init();
+        let head = repo.revparse_single("HEAD").unwrap();
+        let sig = repo.signature().unwrap();
+        let tag = repo.tag("v1", &head, &sig, "v1", false).unwrap();
+        let url = Url::from_file_path(td.path()).unwrap().to_string();
+
+        let heads = {
+            let mut origin = repo.remote_anonymous(url.as_slice(), None).unwrap();
+            origin.connect(Direction::Fetch).unwrap();
+            let heads = origin.list_owned().unwrap();
+            origin.disconnect();
+            heads
+        };
+        assert_eq!(heads.default_branch(), Some("refs/heads/master"));
+        assert!(heads.iter().any(|h| h.name() == "HEAD"));
+        let master = heads.find("refs/heads/master").unwrap();
+        assert_eq!(master.oid(), head.id());
+        assert!(master.peeled().is_none());
+        let v1 = heads.find("refs/tags/v1").unwrap();
+        assert_eq!(v1.oid(), tag);
+        assert_eq!(v1.peeled(), Some(head.id()));
+        assert!(heads.find("refs/tags/v1^{}").is_none());
+    }
+
+    #[test]
+    fn ls() {
+        let (td, repo) = ::test::repo_init();
+        let head = repo.revparse_single("HEAD").unwrap();
+        let url = Url::from_file_path(td.path()).unwrap().to_string();
+
+        let heads = Remote::ls(&repo, url.as_slice(), None).unwrap();
+        assert_eq!(heads.find("refs/heads/master").unwrap().oid(), head.id());
+        assert_eq!(heads.default_branch(), Some("refs/heads/master"));
+        assert!(Remote::ls(&repo, "/path/to/nowhere", None).is_err());
+
+        // The url is rewritten by the configuration of the repository.
+        let (_td2, repo2) = ::test::repo_init();
+        let mut cfg = repo2.config().unwrap();
+        cfg.set_str(format!("url.{}.insteadOf", url).as_slice(),
+                    "origin:").unwrap();
+        let heads = Remote::ls(&repo2, "origin:", None).unwrap();
+        assert_eq!(heads.find("refs/heads/master").unwrap().oid(), head.id());
+    }
 }
@@ -5,6 +5,7 @@ use std::slice;
 use libc::{c_void, c_int, c_char, c_uint};
 
 use {raw, panic, Error, Cred, CredentialType, Oid};
+use cert::Cert;
 
 /// A structure to contain the callbacks which are invoked when a repository is
 /// being updated or downloaded.
@@ -16,6 +17,7 @@ pub struct RemoteCallbacks<'a> {
     credentials: Option<Box<Credentials<'a>>>,
     sideband_progress: Option<Box<TransportMessage<'a>>>,
     update_tips: Option<Box<UpdateTips<'a>>>,
+    certificate_check: Option<Box<CertificateCheck<'a>>>,
 }
 
 /// Struct representing the progress by an in-flight transfer.
@@ -57,6 +59,17 @@ pub type TransportMessage<'a> = FnMut(&[u8]) -> bool + 'a;
 /// Callback for whenever a reference is updated locally.
 pub type UpdateTips<'a> = FnMut(&str, Oid, Oid) -> bool + 'a;
 
+/// Callback for a custom certificate check.
+///
+/// The first argument is the certificate received on the connection, the
+/// second is the hostname of the server being connected to, and the third
+/// indicates whether libgit2 considers the certificate to be valid.
+///
+/// The return value indicates whether the connection should proceed. A return
+/// value of `false` will abort the connection, even if the certificate was
+/// considered valid.
+pub type CertificateCheck<'a> = FnMut(&Cert, &str, bool) -> bool + 'a;
+
 impl<'a> RemoteCallbacks<'a> {
     /// Creates a new set of empty callbacks
     pub fn new() -> RemoteCallbacks<'a> {
@@ -65,6 +78,7 @@ impl<'a> RemoteCallbacks<'a> {
             progress: None,
             sideband_progress: None,
             update_tips: None,
+            certificate_check: None,
         }
     }
 
@@ -102,6 +116,20 @@ impl<'a> RemoteCallbacks<'a> {
         self
     }
 
+    /// If certificate verification fails, then this callback will be invoked
+    /// to let the caller make the final decision of whether to allow the
+    /// connection to proceed.
+    ///
+    /// This callback is also the place to pin certificates or the MD5/SHA-1
+    /// fingerprints of SSH host keys, as it is invoked for every connection
+    /// regardless of whether libgit2 trusts the certificate.
+    pub fn certificate_check<F>(&mut self, cb: F) -> &mut RemoteCallbacks<'a>
+                                where F: FnMut(&Cert, &str, bool) -> bool + 'a
+    {
+        self.certificate_check = Some(box cb as Box<CertificateCheck<'a>>);
+        self
+    }
+
     /// Convert this set of callbacks to a raw callbacks structure.
     ///
     /// This function is unsafe as the callbacks returned have a reference to
@@ -128,6 +156,11 @@ impl<'a> RemoteCallbacks<'a> {
                             = update_tips_cb;
             callbacks.update_tips = Some(f);
         }
+        if self.certificate_check.is_some() {
+            let f: raw::git_transport_certificate_check_cb =
+                certificate_check_cb;
+            callbacks.certificate_check = Some(f);
+        }
         callbacks.payload = self as *mut _ as *mut _;
         return callbacks;
     }
@@ -300,3 +333,26 @@ extern fn update_tips_cb(refname: *const c_char,
         if ok {0} else {-1}
     }
 }
+
+extern fn certificate_check_cb(cert: *mut raw::git_cert,
+                               valid: c_int,
+                               hostname: *const c_char,
+                               data: *mut c_void) -> c_int {
+    unsafe {
+        let payload: &mut RemoteCallbacks = &mut *(data as *mut RemoteCallbacks);
+        let callback = match payload.certificate_check {
+            Some(ref mut c) => c,
+            None => return 0,
+        };
+        let hostname = CString::new(hostname, false);
+        let hostname = match hostname.as_str() {
+            Some(s) => s,
+            None => return -1,
+        };
+        let cert = Cert::from_raw(cert);
+        let ok = panic::wrap(|| {
+            callback.call_mut((&cert, hostname, valid != 0))
+        }).unwrap_or(false);
+        if ok {0} else {-1}
+    }
+}
@@ -1,4 +1,5 @@
 use std::c_str::CString;
+use std::io::{mod, File};
 use std::kinds::marker;
 use std::mem;
 use std::str;
@@ -8,8 +9,11 @@ use {raw, Revspec, Error, init, Object, RepositoryState, Remote, Buf};
 use {StringArray, ResetType, Signature, Reference, References, Submodule};
 use {Branches, BranchType, Index, Config, Oid, Blob, Branch, Commit, Tree};
 use {ObjectType, Tag, Note, Notes, StatusOptions, Statuses, Status, Revwalk};
-use {RevparseMode, RepositoryInitMode};
-use build::{RepoBuilder, CheckoutBuilder};
+use {RevparseMode, RepositoryInitMode, AutotagOption, OidArray};
+use {AttrValue, AttrCheckFlags, FilterList, FilterMode, FilterFlags};
+use {Signer, Mailmap, sign, tag, panic};
+use {IgnoreMatch, ignore, sparse};
+use build::{RepoBuilder, CheckoutBuilder, CommitBuilder};
 
 /// An owned git repository, representing all state associated with the
 /// underlying filesystem.
@@ -296,8 +300,12 @@ impl Repository {
     /// are updated.
     ///
     /// A temporary in-memory remote cannot be given a name with this method.
+    ///
+    /// Fetch refspecs which do not follow the default
+    /// `refs/remotes/<name>/*` layout cannot be rewritten automatically; they
+    /// are left untouched and returned so the caller can fix them up.
     pub fn remote_rename(&self, name: &str,
-                         new_name: &str) -> Result<(), Error> {
+                         new_name: &str) -> Result<StringArray, Error> {
         let mut problems = raw::git_strarray {
             count: 0,
             strings: 0 as *mut *mut c_char,
@@ -307,9 +315,8 @@ impl Repository {
                                              self.raw,
                                              name.to_c_str(),
                                              new_name.to_c_str()));
-            let _s = StringArray::from_raw(problems);
+            Ok(StringArray::from_raw(problems))
         }
-        Ok(())
     }
 
     /// Delete an existing persisted remote.
@@ -321,6 +328,113 @@ impl Repository {
         Ok(())
     }
 
+    /// Add a fetch refspec to the remote's configuration.
+    ///
+    /// The refspec is appended to `remote.<name>.fetch` directly; no loaded
+    /// remote instances will be affected.
+    pub fn remote_add_fetch(&self, name: &str, spec: &str) -> Result<(), Error> {
+        self.remote_add_refspec(name, "fetch", spec)
+    }
+
+    /// Add a push refspec to the remote's configuration.
+    ///
+    /// The refspec is appended to `remote.<name>.push` directly; no loaded
+    /// remote instances will be affected.
+    pub fn remote_add_push(&self, name: &str, spec: &str) -> Result<(), Error> {
+        self.remote_add_refspec(name, "push", spec)
+    }
+
+    fn remote_add_refspec(&self, name: &str, kind: &str, spec: &str)
+                          -> Result<(), Error> {
+        let key = try!(Repository::remote_key(name, kind));
+        let mut config = try!(self.config());
+        config.set_multivar(key.as_slice(), "$^", spec)
+    }
+
+    /// Set the remote's url in the configuration.
+    ///
+    /// No loaded remote instances will be affected.
+    pub fn remote_set_url(&self, name: &str, url: &str) -> Result<(), Error> {
+        let key = try!(Repository::remote_key(name, "url"));
+        let mut config = try!(self.config());
+        config.set_str(key.as_slice(), url)
+    }
+
+    /// Set the remote's pushurl in the configuration.
+    ///
+    /// `None` removes the pushurl so that pushes go to the remote's url. No
+    /// loaded remote instances will be affected.
+    pub fn remote_set_pushurl(&self, name: &str, pushurl: Option<&str>)
+                              -> Result<(), Error> {
+        let key = try!(Repository::remote_key(name, "pushurl"));
+        let mut config = try!(self.config());
+        match pushurl {
+            Some(url) => config.set_str(key.as_slice(), url),
+            None => Repository::remove_if_present(&mut config, key.as_slice()),
+        }
+    }
+
+    /// Set the remote's tag following setting, `remote.<name>.tagOpt`, in the
+    /// configuration.
+    ///
+    /// `Auto` removes the setting, as automatic tag following is git's
+    /// default. No loaded remote instances will be affected.
+    pub fn remote_set_autotag(&self, name: &str, value: AutotagOption)
+                              -> Result<(), Error> {
+        let key = try!(Repository::remote_key(name, "tagopt"));
+        let mut config = try!(self.config());
+        match value {
+            AutotagOption::None => config.set_str(key.as_slice(), "--no-tags"),
+            AutotagOption::All => config.set_str(key.as_slice(), "--tags"),
+            AutotagOption::Auto => {
+                Repository::remove_if_present(&mut config, key.as_slice())
+            }
+        }
+    }
+
+    /// Set whether remote-tracking references which no longer exist on the
+    /// remote are removed when fetching, `remote.<name>.prune`, in the
+    /// configuration.
+    ///
+    /// No loaded remote instances will be affected.
+    pub fn remote_set_prune(&self, name: &str, prune: bool)
+                            -> Result<(), Error> {
+        let key = try!(Repository::remote_key(name, "prune"));
+        let mut config = try!(self.config());
+        config.set_bool(key.as_slice(), prune)
+    }
+
+    /// Check whether fetching from the remote named `name` removes
+    /// remote-tracking references which no longer exist on the remote.
+    ///
+    /// This reads `remote.<name>.prune`, falling back to `fetch.prune`, and
+    /// is `false` if neither is set, as with `git fetch`.
+    pub fn remote_prune(&self, name: &str) -> Result<bool, Error> {
+        let key = try!(Repository::remote_key(name, "prune"));
+        let config = try!(self.config());
+        if config.get_entry(key.as_slice()).is_ok() {
+            return config.get_bool(key.as_slice())
+        }
+        if config.get_entry("fetch.prune").is_ok() {
+            return config.get_bool("fetch.prune")
+        }
+        Ok(false)
+    }
+
+    fn remote_key(name: &str, var: &str) -> Result<String, Error> {
+        if Remote::is_valid_name(name) {
+            Ok(format!("remote.{}.{}", name, var))
+        } else {
+            Err(Error::from_str(format!("'{}' is not a valid remote name",
+                                        name).as_slice()))
+        }
+    }
+
+    fn remove_if_present(config: &mut Config, key: &str) -> Result<(), Error> {
+        if config.get_entry(key).is_err() { return Ok(()) }
+        config.remove(key)
+    }
+
     /// Get the underlying raw repository
     pub fn raw(&self) -> *mut raw::git_repository { self.raw }
 
@@ -339,11 +453,12 @@ impl Repository {
                      sig: Option<&Signature>, msg: Option<&str>)
                      -> Result<(), Error> {
         unsafe {
-            try_call!(raw::git_reset(self.raw, target.raw(), kind,
-                                     // FIXME: expose git_checkout_options_t
-                                     0 as *mut _,
-                                     sig.map(|s| s.raw()).unwrap_or(0 as *mut _),
-                                     msg.map(|s| s.to_c_str())));
+            // FIXME: expose git_checkout_options_t
+            try_call_panic!(raw::git_reset(self.raw, target.raw(), kind,
+                                           0 as *mut _,
+                                           sig.map(|s| s.raw())
+                                              .unwrap_or(0 as *mut _),
+                                           msg.map(|s| s.to_c_str())));
         }
         Ok(())
     }
@@ -403,6 +518,115 @@ impl Repository {
         }
     }
 
+    /// Load the list of filters which apply to `path` when its content is
+    /// filtered in the direction given by `mode`.
+    ///
+    /// The filters are selected by the attributes of `path`, which need not
+    /// exist. If the content comes from a blob it can be given as `blob`,
+    /// which the `ident` filter uses to find the id to substitute.
+    pub fn filter_list(&self, blob: Option<&Blob>, path: &Path,
+                       mode: FilterMode, flags: FilterFlags)
+                       -> Result<FilterList, Error> {
+        let mut ret = 0 as *mut raw::git_filter_list;
+        let blob = blob.map(|b| b.raw());
+        unsafe {
+            try_call!(raw::git_filter_list_load(&mut ret, self.raw, blob,
+                                                path.to_c_str(), mode.raw(),
+                                                flags.bits()));
+            Ok(FilterList::from_raw(ret))
+        }
+    }
+
+    /// Get the value of the git attribute `name` for `path`.
+    ///
+    /// `path` is relative to the root of the working directory and need not
+    /// exist. Attributes are read from `.gitattributes` files,
+    /// `$GIT_DIR/info/attributes` and the file named by
+    /// `core.attributesFile`, as selected by `flags`.
+    pub fn get_attr(&self, path: &Path, name: &str, flags: AttrCheckFlags)
+                    -> Result<AttrValue, Error> {
+        let mut ret = 0 as *const c_char;
+        unsafe {
+            try_call!(raw::git_attr_get(&mut ret, self.raw, flags.bits(),
+                                        path.to_c_str(), name.to_c_str()));
+            Ok(AttrValue::from_raw(ret))
+        }
+    }
+
+    /// Get the values of several git attributes for `path` at once.
+    ///
+    /// The values are returned in the same order as `names`. See `get_attr`
+    /// for more information.
+    pub fn get_attr_many(&self, path: &Path, names: &[&str],
+                         flags: AttrCheckFlags) -> Result<Vec<AttrValue>, Error> {
+        let names = names.iter().map(|s| s.to_c_str()).collect::<Vec<_>>();
+        let mut ptrs = names.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        let mut ret = Vec::from_elem(names.len(), 0 as *const c_char);
+        unsafe {
+            try_call!(raw::git_attr_get_many(ret.as_mut_ptr(), self.raw,
+                                             flags.bits(), path.to_c_str(),
+                                             names.len() as size_t,
+                                             ptrs.as_mut_ptr()));
+            Ok(ret.iter().map(|p| AttrValue::from_raw(*p)).collect())
+        }
+    }
+
+    /// Iterate over all git attributes which are specified for `path`.
+    ///
+    /// The callback is given the name and the value of each attribute.
+    /// Returning `false` from the callback stops the iteration.
+    pub fn attr_foreach<F>(&self, path: &Path, flags: AttrCheckFlags,
+                           mut cb: F) -> Result<(), Error>
+                           where F: FnMut(&[u8], AttrValue) -> bool {
+        unsafe {
+            try_call_panic!(raw::git_attr_foreach(self.raw, flags.bits(),
+                                                  path.to_c_str(),
+                                                  foreach::<F>,
+                                                  &mut cb as *mut _ as *mut _));
+            return Ok(())
+        }
+        extern fn foreach<F>(name: *const c_char, value: *const c_char,
+                             data: *mut c_void) -> c_int
+                             where F: FnMut(&[u8], AttrValue) -> bool
+        {
+            unsafe {
+                let name = CString::new(name, false);
+                let value = AttrValue::from_raw(value);
+                let data = data as *mut F;
+                let ok = panic::wrap(move || {
+                    (*data)(name.as_bytes_no_nul(), value)
+                });
+                match ok {
+                    Some(true) => 0,
+                    Some(false) => 1,
+                    None => -1,
+                }
+            }
+        }
+    }
+
+    /// Add a macro for git attributes, as `[attr]name values` does in a
+    /// `.gitattributes` file.
+    ///
+    /// For example `add_attr_macro("binary", "-diff -merge -text")` defines
+    /// the built-in `binary` macro.
+    pub fn add_attr_macro(&self, name: &str, values: &str)
+                          -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_attr_add_macro(self.raw, name.to_c_str(),
+                                              values.to_c_str()));
+        }
+        Ok(())
+    }
+
+    /// Flush the cache of git attribute files, so that they are read again
+    /// the next time attributes are queried.
+    ///
+    /// This also removes any macros added with `add_attr_macro`.
+    pub fn clear_attr_cache(&self) {
+        unsafe { raw::git_attr_cache_flush(self.raw) }
+    }
+
     /// Load all submodules for this repository and return them.
     pub fn submodules(&self) -> Result<Vec<Submodule>, Error> {
         struct Data<'a, 'b:'a> {
@@ -438,6 +662,36 @@ impl Repository {
         }
     }
 
+    /// Call `cb` for each submodule of this repository, along with its path
+    /// relative to the working directory of this repository.
+    ///
+    /// If `recurse` is `true`, the submodules of each checked out submodule
+    /// are visited as well, right after it. Returning `false` from the
+    /// callback stops the iteration.
+    pub fn submodule_foreach<F>(&self, recurse: bool, mut cb: F)
+                                -> Result<(), Error>
+                                where F: FnMut(&Submodule, &Path) -> bool {
+        return foreach(self, &Path::new("."), recurse, &mut cb).map(|_| ());
+
+        fn foreach<F>(repo: &Repository, prefix: &Path, recurse: bool,
+                      cb: &mut F) -> Result<bool, Error>
+                      where F: FnMut(&Submodule, &Path) -> bool {
+            for submodule in try!(repo.submodules()).iter() {
+                let path = prefix.join(submodule.path());
+                if !(*cb)(submodule, &path) { return Ok(false) }
+                if !recurse { continue }
+                let repo = match submodule.open() {
+                    Ok(repo) => repo,
+                    Err(..) => continue,
+                };
+                if !try!(foreach(&repo, &path, recurse, cb)) {
+                    return Ok(false)
+                }
+            }
+            Ok(true)
+        }
+    }
+
     /// Gather file status information and populate the returned structure.
     ///
     /// Note that if a pathspec is given in the options to filter the
@@ -451,8 +705,12 @@ impl Repository {
             try_call!(raw::git_status_list_new(&mut ret, self.raw,
                                                options.map(|s| s.raw())
                                                       .unwrap_or(0 as *const _)));
-            Ok(Statuses::from_raw(ret))
         }
+        let mut statuses = unsafe { Statuses::from_raw(ret) };
+        if try!(sparse::enabled(self)) {
+            try!(sparse::filter_statuses(self, &mut statuses));
+        }
+        Ok(statuses)
     }
 
     /// Test if the ignore rules apply to a given file.
@@ -472,6 +730,129 @@ impl Repository {
         Ok(ret != 0)
     }
 
+    /// Add ignore rules for this repository.
+    ///
+    /// The rules are given in the format of a `.gitignore` file, one pattern
+    /// per line, and are matched relative to the root of the working
+    /// directory. They only live in memory, in addition to the rules read
+    /// from ignore files, until `clear_internal_ignore_rules` is called or
+    /// the repository is freed.
+    pub fn add_ignore_rule(&self, rules: &str) -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_ignore_add_rule(self.raw, rules.to_c_str()));
+        }
+        Ok(())
+    }
+
+    /// Clear the ignore rules added with `add_ignore_rule`.
+    ///
+    /// The default internal rules, which ignore `.`, `..` and `.git`, are
+    /// kept.
+    pub fn clear_internal_ignore_rules(&self) -> Result<(), Error> {
+        unsafe { try_call!(raw::git_ignore_clear_internal_rules(self.raw)); }
+        Ok(())
+    }
+
+    /// Test whether `path` is ignored by the ignore rules of the repository,
+    /// including those added with `add_ignore_rule`.
+    ///
+    /// Unlike `status_should_ignore`, `path` does not need to exist. Its
+    /// leading components are taken to be directories, so a path inside an
+    /// ignored directory is always ignored.
+    pub fn is_path_ignored(&self, path: &Path) -> Result<bool, Error> {
+        let mut ret = 0 as c_int;
+        unsafe {
+            try_call!(raw::git_ignore_path_is_ignored(&mut ret, self.raw,
+                                                      path.to_c_str()));
+        }
+        Ok(ret != 0)
+    }
+
+    /// Find the rule deciding whether `path` is ignored, as
+    /// `git check-ignore -v` does.
+    ///
+    /// The rule is looked up in the `.gitignore` files of the working
+    /// directory, `$GIT_DIR/info/exclude` and the file named by
+    /// `core.excludesFile`, and `None` is returned if no rule matches. A
+    /// negated match means the path is explicitly not ignored. Rules added
+    /// with `add_ignore_rule` are not taken into account, as libgit2 does not
+    /// expose them; use `is_path_ignored` to include them. The rule found is
+    /// checked against `is_path_ignored`, and an error is returned if they
+    /// disagree. `core.ignoreCase` is honored.
+    pub fn check_ignore(&self, path: &Path)
+                        -> Result<Option<IgnoreMatch>, Error> {
+        ignore::check(self, path)
+    }
+
+    /// Enable sparse checkout and replace its patterns, updating the working
+    /// directory to match.
+    ///
+    /// In cone mode each of `patterns` is a directory whose contents are
+    /// checked out recursively, in addition to the files at the root and
+    /// directly inside the parents of each directory. Otherwise the patterns
+    /// have the syntax of `.gitignore` files, matching the paths to check
+    /// out.
+    ///
+    /// Files no longer checked out are removed from the working directory and
+    /// marked skip-worktree in the index, except those with local
+    /// modifications which are left in place. Their entries stay in the
+    /// index, and they do not show up as deleted in `statuses`.
+    ///
+    /// The `checkout_*` methods only write the files included in the sparse
+    /// checkout, but `reset` and `RepoBuilder::clone` ignore its patterns and
+    /// check out every file.
+    pub fn sparse_checkout_set(&self, patterns: &[&str], cone: bool)
+                               -> Result<(), Error> {
+        let lines = if cone {
+            sparse::cone_patterns(sparse::cone_dirs(patterns).as_slice())
+        } else {
+            patterns.iter().map(|p| p.to_string()).collect()
+        };
+        let mut config = try!(self.config());
+        try!(config.set_bool("core.sparseCheckout", true));
+        try!(config.set_bool("core.sparseCheckoutCone", cone));
+        try!(sparse::write(self, lines.as_slice()));
+        sparse::apply(self)
+    }
+
+    /// Add patterns to an enabled sparse checkout, in the mode it was set up
+    /// with, and update the working directory to match.
+    pub fn sparse_checkout_add(&self, patterns: &[&str]) -> Result<(), Error> {
+        if !try!(sparse::enabled(self)) {
+            return Err(Error::from_str("sparse checkout is not enabled"))
+        }
+        let mut existing = try!(self.sparse_checkout_list());
+        existing.extend(patterns.iter().map(|p| p.to_string()));
+        let patterns = existing.iter().map(|p| p.as_slice())
+                               .collect::<Vec<&str>>();
+        let cone = try!(sparse::cone_enabled(self));
+        self.sparse_checkout_set(patterns.as_slice(), cone)
+    }
+
+    /// List the patterns of the sparse checkout, as read from
+    /// `$GIT_DIR/info/sparse-checkout`.
+    ///
+    /// In cone mode the directories checked out recursively are returned
+    /// instead.
+    pub fn sparse_checkout_list(&self) -> Result<Vec<String>, Error> {
+        let lines = try!(sparse::read(self));
+        if try!(sparse::cone_enabled(self)) {
+            Ok(sparse::cone_list(lines.as_slice()))
+        } else {
+            Ok(lines)
+        }
+    }
+
+    /// Disable sparse checkout, checking out every file again.
+    ///
+    /// The patterns are kept, so a later `sparse_checkout_list` still returns
+    /// them.
+    pub fn sparse_checkout_disable(&self) -> Result<(), Error> {
+        let mut config = try!(self.config());
+        try!(config.set_bool("core.sparseCheckout", false));
+        sparse::apply(self)
+    }
+
     /// Get file status for a single file.
     ///
     /// This tries to get status for the filename that you give. If no files
@@ -640,6 +1021,69 @@ impl Repository {
         }
     }
 
+    /// Create the contents of a commit object without writing it.
+    ///
+    /// The arguments are the same as for `commit`. The returned buffer holds
+    /// the exact bytes which would be written for the commit, which is the data
+    /// to sign for `commit_signed`.
+    pub fn commit_create_buffer<'a>(&'a self,
+                                    author: &Signature,
+                                    committer: &Signature,
+                                    message: &str,
+                                    tree: &Tree<'a>,
+                                    parents: &[&Commit<'a>])
+                                    -> Result<Buf, Error> {
+        let mut builder = CommitBuilder::new();
+        builder.tree(tree).author(author).committer(committer)
+               .message(message);
+        for parent in parents.iter() {
+            builder.parent(*parent);
+        }
+        sign::buf(try!(builder.buffer()).as_slice())
+    }
+
+    /// Write a commit object with the given signature.
+    ///
+    /// `commit_content` is the unsigned commit, as created by
+    /// `commit_create_buffer`, and `signature` is stored in the header named
+    /// by `signature_field`, `gpgsig` by default. No reference is updated.
+    pub fn commit_signed(&self,
+                         commit_content: &str,
+                         signature: &str,
+                         signature_field: Option<&str>) -> Result<Oid, Error> {
+        let field = signature_field.unwrap_or("gpgsig");
+        let content = sign::commit_add_signature(commit_content.as_bytes(),
+                                                 field, signature.as_bytes());
+        let content = match content {
+            Some(content) => content,
+            None => return Err(Error::from_str("invalid commit content")),
+        };
+        unsafe {
+            sign::write_object(self.raw, ObjectType::Commit,
+                               content.as_slice())
+        }
+    }
+
+    /// Create a commit signed by `signer`.
+    ///
+    /// This combines `commit_create_buffer` and `commit_signed`, so no
+    /// reference is updated.
+    pub fn commit_with_signer<'a>(&'a self,
+                                  signer: &mut Signer,
+                                  author: &Signature,
+                                  committer: &Signature,
+                                  message: &str,
+                                  tree: &Tree<'a>,
+                                  parents: &[&Commit<'a>]) -> Result<Oid, Error> {
+        let buf = try!(self.commit_create_buffer(author, committer, message,
+                                                 tree, parents));
+        let content = match buf.as_str() {
+            Some(s) => s,
+            None => return Err(Error::from_str("commit is not valid utf-8")),
+        };
+        let signature = try!(signer.sign(buf.get()));
+        self.commit_signed(content, signature.as_slice(), None)
+    }
 
     /// Lookup a reference to one of the commits in a repository.
     pub fn find_commit(&self, oid: Oid) -> Result<Commit, Error> {
@@ -741,6 +1185,50 @@ impl Repository {
         }
     }
 
+    /// Load the mailmap of the repository.
+    ///
+    /// Entries are read from the blob named by the `mailmap.blob`
+    /// configuration, `HEAD:.mailmap` by default in a bare repository, then
+    /// from the `.mailmap` file at the root of the working directory and the
+    /// file named by the `mailmap.file` configuration. Later entries take
+    /// precedence, and missing sources are skipped.
+    pub fn mailmap(&self) -> Result<Mailmap, Error> {
+        let config = try!(self.config());
+        let mut buf = String::new();
+        let blob = match config.get_str("mailmap.blob") {
+            Ok(spec) => Some(spec.to_string()),
+            Err(..) if self.is_bare() => Some("HEAD:.mailmap".to_string()),
+            Err(..) => None,
+        };
+        match blob.and_then(|spec| self.revparse_single(spec.as_slice()).ok()) {
+            Some(obj) => {
+                let blob = try!(self.find_blob(obj.id()));
+                let content = String::from_utf8_lossy(blob.content());
+                buf.push_str(content.as_slice());
+                buf.push('\n');
+            }
+            None => {}
+        }
+
+        let mut paths = Vec::new();
+        match self.workdir() {
+            Some(dir) => paths.push(dir.join(".mailmap")),
+            None => {}
+        }
+        match config.get_str("mailmap.file") {
+            Ok(path) => paths.push(Path::new(path)),
+            Err(..) => {}
+        }
+        for path in paths.iter() {
+            match File::open(path).read_to_string() {
+                Ok(s) => { buf.push_str(s.as_slice()); buf.push('\n'); }
+                Err(ref e) if e.kind == io::FileNotFound => {}
+                Err(e) => return Err(::io_error(e)),
+            }
+        }
+        Mailmap::from_buffer(buf.as_slice())
+    }
+
     /// Set up a new git submodule for checkout.
     ///
     /// This does "git submodule add" up to the fetch and checkout of the
@@ -810,6 +1298,138 @@ impl Repository {
         }
     }
 
+    /// Create a new lightweight tag pointing at a target object
+    ///
+    /// A new direct reference will be created pointing to this target object.
+    /// If `force` is true and a reference already exists with the given name,
+    /// it'll be replaced.
+    ///
+    /// Returns the id of the target object. See `tag` for the rules about
+    /// valid tag names.
+    pub fn tag_lightweight<'a>(&'a self, name: &str, target: &Object<'a>,
+                               force: bool) -> Result<Oid, Error> {
+        let mut raw = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+        unsafe {
+            try_call!(raw::git_tag_create_lightweight(&mut raw, self.raw,
+                                                      name.to_c_str(),
+                                                      &*target.raw(), force));
+            Ok(Oid::from_raw(&raw))
+        }
+    }
+
+    /// Create a new annotated tag object without creating a reference for it.
+    ///
+    /// The arguments are the same as for `tag`, and the id of the new tag
+    /// object is returned.
+    pub fn tag_annotation_create<'a>(&'a self, name: &str, target: &Object<'a>,
+                                     tagger: &Signature, message: &str)
+                                     -> Result<Oid, Error> {
+        let mut raw = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+        unsafe {
+            try_call!(raw::git_tag_annotation_create(&mut raw, self.raw,
+                                                     name.to_c_str(),
+                                                     &*target.raw(),
+                                                     &*tagger.raw(),
+                                                     message.to_c_str()));
+            Ok(Oid::from_raw(&raw))
+        }
+    }
+
+    /// Iterate over all tags in the repository, both annotated and
+    /// lightweight.
+    ///
+    /// The callback is given the id the tag reference points at, which is the
+    /// tag object for an annotated tag and the target itself for a lightweight
+    /// one, and the full name of the reference, such as `refs/tags/v1.0`.
+    /// Returning `false` from the callback stops the iteration.
+    pub fn tag_foreach<F>(&self, mut cb: F) -> Result<(), Error>
+                          where F: FnMut(Oid, &[u8]) -> bool {
+        unsafe {
+            try_call_panic!(raw::git_tag_foreach(self.raw, foreach::<F>,
+                                                 &mut cb as *mut _ as *mut _));
+            return Ok(())
+        }
+        extern fn foreach<F>(name: *const c_char, oid: *mut raw::git_oid,
+                             data: *mut c_void) -> c_int
+                             where F: FnMut(Oid, &[u8]) -> bool
+        {
+            unsafe {
+                let id = Oid::from_raw(&*oid);
+                let name = CString::new(name, false);
+                let data = data as *mut F;
+                let ok = panic::wrap(move || {
+                    (*data)(id, name.as_bytes_no_nul())
+                });
+                match ok {
+                    Some(true) => 0,
+                    Some(false) => 1,
+                    None => -1,
+                }
+            }
+        }
+    }
+
+    /// Create the contents of an annotated tag object without writing it.
+    ///
+    /// The arguments are the same as for `tag`. The returned buffer holds the
+    /// exact bytes which would be written for the tag, which is the data to
+    /// sign for `tag_signed`.
+    pub fn tag_create_buffer<'a>(&'a self, name: &str, target: &Object<'a>,
+                                 tagger: &Signature, message: &str)
+                                 -> Result<Buf, Error> {
+        let kind = match target.kind() {
+            Some(kind) => kind,
+            None => return Err(Error::from_str("invalid tag target")),
+        };
+        let mut data = format!("object {}\ntype {}\ntag {}\ntagger ",
+                               target.id(), kind.str(), name).into_bytes();
+        data.push_all(sign::header_signature(tagger).as_slice());
+        data.push_all(b"\n\n");
+        data.push_all(message.as_bytes());
+        sign::buf(data.as_slice())
+    }
+
+    /// Write an annotated tag object with the given signature appended, and
+    /// create a reference for it as `tag` does.
+    ///
+    /// `tag_content` is the unsigned tag, as created by `tag_create_buffer`.
+    /// As the signature starts on a line of its own, the tag message must end
+    /// with a newline, otherwise an error is returned.
+    pub fn tag_signed(&self, tag_content: &str, signature: &str,
+                      force: bool) -> Result<Oid, Error> {
+        if !tag_content.ends_with("\n") {
+            return Err(Error::from_str("tag message must end with a newline"))
+        }
+        let mut content = tag_content.to_string();
+        content.push_str(signature);
+        if !signature.ends_with("\n") {
+            content.push('\n');
+        }
+        let mut raw = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+        unsafe {
+            try_call!(raw::git_tag_create_frombuffer(&mut raw, self.raw,
+                                                     content.to_c_str(),
+                                                     force));
+            Ok(Oid::from_raw(&raw))
+        }
+    }
+
+    /// Create an annotated tag signed by `signer`.
+    ///
+    /// This combines `tag_create_buffer` and `tag_signed`.
+    pub fn tag_with_signer<'a>(&'a self, signer: &mut Signer, name: &str,
+                               target: &Object<'a>, tagger: &Signature,
+                               message: &str, force: bool)
+                               -> Result<Oid, Error> {
+        let buf = try!(self.tag_create_buffer(name, target, tagger, message));
+        let content = match buf.as_str() {
+            Some(s) => s,
+            None => return Err(Error::from_str("tag is not valid utf-8")),
+        };
+        let signature = try!(signer.sign(buf.get()));
+        self.tag_signed(content, signature.as_slice(), force)
+    }
+
     /// Lookup a tag object from the repository.
     pub fn find_tag(&self, id: Oid) -> Result<Tag, Error> {
         let mut raw = 0 as *mut raw::git_tag;
@@ -850,11 +1470,27 @@ impl Repository {
         Ok(unsafe { StringArray::from_raw(arr) })
     }
 
+    /// Get a list with all the tags in the repository, sorted as versions.
+    ///
+    /// Numbers within the names are compared by value, a leading `v` is
+    /// ignored and a pre-release suffix such as `-rc.1` sorts before the
+    /// release itself, so `v1.2.0-rc.1` comes before `v1.2.0`, which comes
+    /// before `v1.10.0`. An optional fnmatch pattern can also be specified.
+    pub fn tag_names_sorted(&self, pattern: Option<&str>)
+                            -> Result<Vec<String>, Error> {
+        let names = try!(self.tag_names(pattern));
+        let mut ret = names.iter().filter_map(|s| s).map(|s| s.to_string())
+                           .collect::<Vec<_>>();
+        ret.sort_by(|a, b| tag::version_cmp(a.as_slice(), b.as_slice()));
+        Ok(ret)
+    }
+
     /// Updates files in the index and the working tree to match the content of
     /// the commit pointed at by HEAD.
     pub fn checkout_head(&self, opts: Option<&mut CheckoutBuilder>)
                          -> Result<(), Error> {
-        unsafe {
+        let target = try!(sparse::target_index(self, None));
+        let restricted = unsafe {
             let mut raw_opts = mem::zeroed();
             try_call!(raw::git_checkout_init_options(&mut raw_opts,
                                 raw::GIT_CHECKOUT_OPTIONS_VERSION));
@@ -863,8 +1499,15 @@ impl Repository {
                 None => {}
             }
 
-            try_call!(raw::git_checkout_head(self.raw, &raw_opts));
-        }
+            let paths = match target {
+                Some(ref t) => try!(sparse::prepare(self, &mut raw_opts, t)),
+                None => None,
+            };
+            try_call_panic!(raw::git_checkout_head(self.raw, &raw_opts));
+            panic::check();
+            paths.is_some()
+        };
+        if restricted { try!(sparse::finish(self, target.as_ref())) }
         Ok(())
     }
 
@@ -874,7 +1517,7 @@ impl Repository {
     pub fn checkout_index(&self,
                           index: Option<&mut Index>,
                           opts: Option<&mut CheckoutBuilder>) -> Result<(), Error> {
-        unsafe {
+        let restricted = unsafe {
             let mut raw_opts = mem::zeroed();
             try_call!(raw::git_checkout_init_options(&mut raw_opts,
                                 raw::GIT_CHECKOUT_OPTIONS_VERSION));
@@ -883,10 +1526,20 @@ impl Repository {
                 None => {}
             }
 
-            try_call!(raw::git_checkout_index(self.raw,
-                                              index.map(|i| &mut *i.raw()),
-                                              &raw_opts));
-        }
+            let paths = match index {
+                Some(ref i) => try!(sparse::prepare(self, &mut raw_opts, &**i)),
+                None => {
+                    let i = try!(self.index());
+                    try!(sparse::prepare(self, &mut raw_opts, &i))
+                }
+            };
+            try_call_panic!(raw::git_checkout_index(self.raw,
+                                                    index.map(|i| &mut *i.raw()),
+                                                    &raw_opts));
+            panic::check();
+            paths.is_some()
+        };
+        if restricted { try!(sparse::finish(self, None)) }
         Ok(())
     }
 
@@ -895,7 +1548,8 @@ impl Repository {
     pub fn checkout_tree(&self,
                          treeish: &Object,
                          opts: Option<&mut CheckoutBuilder>) -> Result<(), Error> {
-        unsafe {
+        let target = try!(sparse::target_index(self, Some(treeish)));
+        let restricted = unsafe {
             let mut raw_opts = mem::zeroed();
             try_call!(raw::git_checkout_init_options(&mut raw_opts,
                                 raw::GIT_CHECKOUT_OPTIONS_VERSION));
@@ -904,9 +1558,16 @@ impl Repository {
                 None => {}
             }
 
-            try_call!(raw::git_checkout_tree(self.raw, &*treeish.raw(),
-                                             &raw_opts));
-        }
+            let paths = match target {
+                Some(ref t) => try!(sparse::prepare(self, &mut raw_opts, t)),
+                None => None,
+            };
+            try_call_panic!(raw::git_checkout_tree(self.raw, &*treeish.raw(),
+                                                   &raw_opts));
+            panic::check();
+            paths.is_some()
+        };
+        if restricted { try!(sparse::finish(self, target.as_ref())) }
         Ok(())
     }
 
@@ -1018,6 +1679,116 @@ impl Repository {
             Ok(Oid::from_raw(&raw))
         }
     }
+
+    /// Find all merge bases between two commits
+    pub fn merge_bases(&self, one: Oid, two: Oid) -> Result<OidArray, Error> {
+        let mut arr = raw::git_oidarray {
+            ids: 0 as *mut raw::git_oid,
+            count: 0,
+        };
+        unsafe {
+            try_call!(raw::git_merge_bases(&mut arr, self.raw,
+                                           one.raw(), two.raw()));
+            Ok(OidArray::from_raw(arr))
+        }
+    }
+
+    /// Find a merge base given a list of commits
+    ///
+    /// The merge base is computed as if the first commit were being merged
+    /// with all of the others at once.
+    pub fn merge_base_many(&self, oids: &[Oid]) -> Result<Oid, Error> {
+        let oids = raw_oids(oids);
+        let mut raw = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+        unsafe {
+            try_call!(raw::git_merge_base_many(&mut raw, self.raw,
+                                               oids.len() as size_t,
+                                               oids.as_ptr()));
+            Ok(Oid::from_raw(&raw))
+        }
+    }
+
+    /// Find all merge bases given a list of commits
+    ///
+    /// See `merge_base_many` for how the bases are computed.
+    pub fn merge_bases_many(&self, oids: &[Oid]) -> Result<OidArray, Error> {
+        let oids = raw_oids(oids);
+        let mut arr = raw::git_oidarray {
+            ids: 0 as *mut raw::git_oid,
+            count: 0,
+        };
+        unsafe {
+            try_call!(raw::git_merge_bases_many(&mut arr, self.raw,
+                                                oids.len() as size_t,
+                                                oids.as_ptr()));
+            Ok(OidArray::from_raw(arr))
+        }
+    }
+
+    /// Find a merge base in preparation for an octopus merge
+    ///
+    /// Unlike `merge_base_many`, the result is a common ancestor of every
+    /// commit in the list.
+    pub fn merge_base_octopus(&self, oids: &[Oid]) -> Result<Oid, Error> {
+        let oids = raw_oids(oids);
+        let mut raw = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+        unsafe {
+            try_call!(raw::git_merge_base_octopus(&mut raw, self.raw,
+                                                  oids.len() as size_t,
+                                                  oids.as_ptr()));
+            Ok(Oid::from_raw(&raw))
+        }
+    }
+
+    /// Count the number of unique commits between two commit objects
+    ///
+    /// Returns `(ahead, behind)`: the number of commits reachable from `local`
+    /// but not `upstream`, and vice versa. There is no need for branches
+    /// containing the commits to have any upstream relationship, but it helps
+    /// to think of one as a branch and the other as its upstream.
+    pub fn graph_ahead_behind(&self, local: Oid, upstream: Oid)
+                              -> Result<(uint, uint), Error> {
+        let mut ahead: size_t = 0;
+        let mut behind: size_t = 0;
+        unsafe {
+            try_call!(raw::git_graph_ahead_behind(&mut ahead, &mut behind,
+                                                  self.raw, local.raw(),
+                                                  upstream.raw()));
+        }
+        Ok((ahead as uint, behind as uint))
+    }
+
+    /// Determine if a commit is the descendant of another commit
+    ///
+    /// Note that a commit is not considered a descendant of itself.
+    pub fn graph_descendant_of(&self, commit: Oid, ancestor: Oid)
+                               -> Result<bool, Error> {
+        unsafe {
+            let rv = try_call!(raw::git_graph_descendant_of(self.raw,
+                                                            commit.raw(),
+                                                            ancestor.raw()));
+            Ok(rv != 0)
+        }
+    }
+
+    /// Determine if `commit` is reachable from any of the `descendants`
+    ///
+    /// A commit is considered reachable from itself, so this is also true if
+    /// `commit` appears in `descendants`.
+    pub fn graph_reachable_from_any(&self, commit: Oid, descendants: &[Oid])
+                                    -> Result<bool, Error> {
+        for &descendant in descendants.iter() {
+            if descendant == commit ||
+               try!(self.graph_descendant_of(descendant, commit)) {
+                return Ok(true)
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn raw_oids(oids: &[Oid]) -> Vec<raw::git_oid> {
+    oids.iter().map(|oid| unsafe { *oid.raw() }).collect()
 }
 
 #[unsafe_destructor]
@@ -1185,7 +1956,7 @@ impl RepositoryInitOptions {
 #[cfg(test)]
 mod tests {
     use std::io::TempDir;
-    use {Repository, ObjectType, ResetType};
+    use {Repository, ObjectType, ResetType, Oid};
 
     #[test]
     fn smoke_init() {
@@ -1259,6 +2030,44 @@ mod tests {
         Repository::init(&td.path().join("a/b/c/d")).unwrap();
     }
 
+    #[test]
+    fn graph() {
+        let (_td, repo) = ::test::repo_init();
+        let sig = repo.signature().unwrap();
+        let base = repo.head().unwrap().target().unwrap();
+        let commit = |&: msg: &str, parents: &[Oid]| {
+            let parents = parents.iter().map(|id| {
+                repo.find_commit(*id).unwrap()
+            }).collect::<Vec<_>>();
+            let parents = parents.iter().collect::<Vec<_>>();
+            let tree = parents[0].tree().unwrap();
+            repo.commit(None, &sig, &sig, msg, &tree,
+                        parents.as_slice()).unwrap()
+        };
+        let a1 = commit("a1", &[base]);
+        let a2 = commit("a2", &[a1]);
+        let b1 = commit("b1", &[base]);
+        let c1 = commit("c1", &[base]);
+        let merge = commit("merge", &[a2, b1]);
+
+        assert_eq!(repo.graph_ahead_behind(a2, b1).unwrap(), (2, 1));
+        assert_eq!(repo.graph_ahead_behind(merge, a2).unwrap(), (2, 0));
+        assert!(repo.graph_descendant_of(a2, base).unwrap());
+        assert!(!repo.graph_descendant_of(base, a2).unwrap());
+        assert!(!repo.graph_descendant_of(a2, a2).unwrap());
+        assert!(repo.graph_reachable_from_any(b1, &[c1, merge]).unwrap());
+        assert!(!repo.graph_reachable_from_any(a1, &[b1, c1]).unwrap());
+
+        assert_eq!(repo.merge_base(a2, b1).unwrap(), base);
+        let bases = repo.merge_bases(a2, b1).unwrap();
+        assert_eq!(bases.len(), 1);
+        assert_eq!(bases.iter().collect::<Vec<_>>(), vec![base]);
+        assert_eq!(repo.merge_base_many(&[merge, a1, c1]).unwrap(), base);
+        assert_eq!(repo.merge_bases_many(&[merge, a1]).unwrap().get(0),
+                   Some(a1));
+        assert_eq!(repo.merge_base_octopus(&[a2, b1, c1]).unwrap(), base);
+    }
+
     #[test]
     fn smoke_discover() {
         let td = TempDir::new("test").unwrap();
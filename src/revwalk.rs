@@ -1,23 +1,38 @@
 extern crate libc;
 
 use std::kinds::marker;
+use libc::{c_int, c_void};
 
-use {raw, Error, Sort, Oid};
+use {raw, panic, Error, ErrorCode, Sort, Oid, OidArray, Commit, Revspec};
+use {REVPARSE_SINGLE, REVPARSE_MERGE_BASE};
 
 /// A revwalk allows traversal of the commit graph defined by including one or
 /// more leaves and excluding one or more roots.
 pub struct Revwalk<'repo> {
     raw: *mut raw::git_revwalk,
+    hide_cb: Option<Box<Box<HideCallback<'repo>>>>,
     marker1: marker::ContravariantLifetime<'repo>,
     marker2: marker::NoSend,
     marker3: marker::NoSync,
 }
 
+/// An iterator over the commits of a revwalk, created by `Revwalk::commits`.
+pub struct Commits<'a, 'repo: 'a> {
+    walk: &'a mut Revwalk<'repo>,
+}
+
+/// Callback deciding whether a commit, and its ancestors, should be hidden
+/// from a revwalk.
+///
+/// Returning `true` hides the commit.
+pub type HideCallback<'a> = FnMut(Oid) -> bool + 'a;
+
 impl<'repo> Revwalk<'repo> {
     /// Creates a new revwalk from its raw pointer.
     pub unsafe fn from_raw(raw: *mut raw::git_revwalk) -> Revwalk<'repo> {
         Revwalk {
             raw: raw,
+            hide_cb: None,
             marker1: marker::ContravariantLifetime,
             marker2: marker::NoSend,
             marker3: marker::NoSync,
@@ -110,6 +125,51 @@ impl<'repo> Revwalk<'repo> {
         Ok(())
     }
 
+    /// Push the commits described by a revspec from `Repository::revparse`.
+    ///
+    /// This is the counterpart of `push_ref` and `push_range` for an already
+    /// parsed revspec. A single revision is pushed. For a `<from>..<to>`
+    /// range, `to` is pushed and `from` hidden. For a `<from>...<to>` range,
+    /// both ends are pushed and all of their merge bases hidden, yielding the
+    /// commits reachable from either but not both; if the two ends have no
+    /// common history, nothing is hidden.
+    pub fn push_revspec(&mut self, revspec: &Revspec) -> Result<(), Error> {
+        let from = match revspec.from() {
+            Some(from) => from.id(),
+            None => return Err(Error::from_str("revspec has no revision")),
+        };
+        if revspec.mode().contains(REVPARSE_SINGLE) {
+            return self.push(from)
+        }
+        let to = match revspec.to() {
+            Some(to) => to.id(),
+            None => return Err(Error::from_str("revspec range has no end")),
+        };
+        try!(self.push(to));
+        if !revspec.mode().contains(REVPARSE_MERGE_BASE) {
+            return self.hide(from)
+        }
+        try!(self.push(from));
+        let mut arr = raw::git_oidarray {
+            ids: 0 as *mut raw::git_oid,
+            count: 0,
+        };
+        let bases = unsafe {
+            let repo = raw::git_revwalk_repository(self.raw);
+            match ::call::try(call!(raw::git_merge_bases(&mut arr, repo,
+                                                         from.raw(),
+                                                         to.raw()))) {
+                Ok(..) => OidArray::from_raw(arr),
+                Err(ref e) if e.code() == ErrorCode::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        };
+        for base in bases.iter() {
+            try!(self.hide(base));
+        }
+        Ok(())
+    }
+
     /// Mark a commit as not of interest to this revwalk.
     pub fn hide(&mut self, oid: Oid) -> Result<(), Error> {
         unsafe {
@@ -154,6 +214,52 @@ impl<'repo> Revwalk<'repo> {
         }
         Ok(())
     }
+
+    /// Set a callback which is consulted for each commit reached by the walk.
+    ///
+    /// Returning `true` from the callback hides the commit and stops the walk
+    /// from descending into its parents, as `hide` does. Any previously set
+    /// callback is replaced.
+    pub fn set_hide_callback<F>(&mut self, cb: F) -> Result<(), Error>
+                                where F: FnMut(Oid) -> bool + 'repo
+    {
+        let mut cb: Box<Box<HideCallback<'repo>>> =
+            box (box cb as Box<HideCallback<'repo>>);
+        unsafe {
+            let f: raw::git_revwalk_hide_cb = hide_cb;
+            try_call!(raw::git_revwalk_add_hide_cb(self.raw, Some(f),
+                                        &mut *cb as *mut _ as *mut c_void));
+        }
+        self.hide_cb = Some(cb);
+        Ok(())
+    }
+
+    /// Remove the callback set with `set_hide_callback`.
+    pub fn reset_hide_callback(&mut self) -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_revwalk_add_hide_cb(self.raw, None,
+                                                   0 as *mut c_void));
+        }
+        self.hide_cb = None;
+        Ok(())
+    }
+
+    /// Iterate over the commits of this walk, rather than their ids.
+    pub fn commits<'a>(&'a mut self) -> Commits<'a, 'repo> {
+        Commits { walk: self }
+    }
+}
+
+extern fn hide_cb(commit_id: *const raw::git_oid, payload: *mut c_void)
+                  -> c_int {
+    unsafe {
+        let callback = &mut *(payload as *mut Box<HideCallback>);
+        let id = Oid::from_raw(commit_id);
+        // Hide everything after a panic so the walk winds down quickly; the
+        // panic is resumed once `git_revwalk_next` returns.
+        let hide = panic::wrap(|| callback.call_mut((id,))).unwrap_or(true);
+        hide as c_int
+    }
 }
 
 #[unsafe_destructor]
@@ -167,7 +273,9 @@ impl<'repo> Iterator<Oid> for Revwalk<'repo> {
     fn next(&mut self) -> Option<Oid> {
         let mut out: raw::git_oid = raw::git_oid{ id: [0, ..raw::GIT_OID_RAWSZ] };
         unsafe {
-            match raw::git_revwalk_next(&mut out, self.raw()) {
+            let rc = raw::git_revwalk_next(&mut out, self.raw());
+            panic::check();
+            match rc {
                 0 => (),
                 _ => return None,
             }
@@ -177,6 +285,23 @@ impl<'repo> Iterator<Oid> for Revwalk<'repo> {
     }
 }
 
+impl<'a, 'repo> Iterator<Result<Commit<'repo>, Error>> for Commits<'a, 'repo> {
+    fn next(&mut self) -> Option<Result<Commit<'repo>, Error>> {
+        self.walk.next().map(|id| self.find(id))
+    }
+}
+
+impl<'a, 'repo> Commits<'a, 'repo> {
+    fn find(&self, id: Oid) -> Result<Commit<'repo>, Error> {
+        let mut raw = 0 as *mut raw::git_commit;
+        unsafe {
+            let repo = raw::git_revwalk_repository(self.walk.raw);
+            try_call!(raw::git_commit_lookup(&mut raw, repo, id.raw()));
+            Ok(Commit::from_raw(raw))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate std;
@@ -205,4 +330,62 @@ mod tests {
         walk.hide_head().unwrap();
         assert_eq!(walk.by_ref().count(), 0);
     }
+
+    #[test]
+    fn hide_callback_and_commits() {
+        let (_td, repo) = ::test::repo_init();
+        let sig = repo.signature().unwrap();
+        let mut ids = vec![repo.head().unwrap().target().unwrap()];
+        for msg in ["a", "b", "c"].iter() {
+            let parent = repo.find_commit(*ids.last().unwrap()).unwrap();
+            let tree = parent.tree().unwrap();
+            ids.push(repo.commit(Some("HEAD"), &sig, &sig, *msg, &tree,
+                                 &[&parent]).unwrap());
+        }
+
+        let hidden = ids[1];
+        let mut walk = repo.revwalk().unwrap();
+        walk.set_hide_callback(move |id| id == hidden).unwrap();
+        walk.push_head().unwrap();
+        let msgs = walk.commits().map(|c| {
+            c.unwrap().message().unwrap().to_string()
+        }).collect::<Vec<_>>();
+        assert_eq!(msgs, vec!["c".to_string(), "b".to_string()]);
+
+        walk.reset_hide_callback().unwrap();
+        walk.push_head().unwrap();
+        assert_eq!(walk.by_ref().count(), 4);
+    }
+
+    #[test]
+    fn push_revspec() {
+        let (_td, repo) = ::test::repo_init();
+        let sig = repo.signature().unwrap();
+        let base = repo.head().unwrap().target().unwrap();
+        let parent = repo.find_commit(base).unwrap();
+        let tree = parent.tree().unwrap();
+        let a = repo.commit(None, &sig, &sig, "a", &tree, &[&parent]).unwrap();
+        let b = repo.commit(None, &sig, &sig, "b", &tree, &[&parent]).unwrap();
+        let range = |spec: &str| {
+            let mut walk = repo.revwalk().unwrap();
+            walk.push_revspec(&repo.revparse(spec).unwrap()).unwrap();
+            let mut ids = walk.collect::<Vec<_>>();
+            ids.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+            ids
+        };
+        let sorted = |mut ids: Vec<::Oid>| {
+            ids.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+            ids
+        };
+
+        assert_eq!(range(format!("{}", a).as_slice()), sorted(vec![a, base]));
+        assert_eq!(range(format!("{}..{}", base, a).as_slice()), vec![a]);
+        assert_eq!(range(format!("{}...{}", a, b).as_slice()),
+                   sorted(vec![a, b]));
+
+        let orphan = repo.commit(None, &sig, &sig, "orphan", &tree,
+                                 &[]).unwrap();
+        assert_eq!(range(format!("{}...{}", a, orphan).as_slice()),
+                   sorted(vec![a, base, orphan]));
+    }
 }
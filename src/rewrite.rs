@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+
+use {Repository, Revwalk, Commit, Tree, Signature, Oid, Error, Index};
+use {ObjectType, SORT_TOPOLOGICAL, SORT_REVERSE};
+
+/// A history rewriting engine in the spirit of `git filter-branch`.
+///
+/// Every commit reachable from the references passed to `run`, and not hidden
+/// on the underlying revwalk, is replayed from the oldest to the newest
+/// through the configured filters. The rewritten commits form a new history
+/// whose ids are recorded in an old to new map, after which the references are
+/// moved to their rewritten commits and backups of their old values are
+/// written under `refs/original/`.
+pub struct Rewrite<'repo> {
+    repo: &'repo Repository,
+    walk: Revwalk<'repo>,
+    remove: Vec<String>,
+    tree_filter: Option<Box<TreeFilter<'repo>>>,
+    message_filter: Option<Box<MessageFilter<'repo>>>,
+    author_filter: Option<Box<SignatureFilter<'repo>>>,
+    committer_filter: Option<Box<SignatureFilter<'repo>>>,
+    parent_filter: Option<Box<ParentFilter<'repo>>>,
+    prune_empty: bool,
+    tags: bool,
+    backup: Option<String>,
+    force: bool,
+    map: HashMap<Oid, Oid>,
+}
+
+/// Callback producing the tree of a rewritten commit.
+///
+/// The arguments are the original commit and its tree, after any paths given
+/// to `Rewrite::remove_path` have been removed, and the id of the tree to
+/// use instead is returned.
+pub type TreeFilter<'a> = FnMut(&Commit, &Tree) -> Result<Oid, Error> + 'a;
+
+/// Callback producing the message of a rewritten commit from the original
+/// commit and its raw message.
+pub type MessageFilter<'a> = FnMut(&Commit, &[u8]) -> Vec<u8> + 'a;
+
+/// Callback mapping the author or committer of a commit.
+///
+/// Returning `None` keeps the signature unchanged.
+pub type SignatureFilter<'a> = FnMut(&Signature) -> Option<Signature<'static>>
+                                    + 'a;
+
+/// Callback producing the parents of a rewritten commit.
+///
+/// The arguments are the original commit and the ids of its parents, already
+/// mapped to their rewritten commits.
+pub type ParentFilter<'a> = FnMut(&Commit, Vec<Oid>) -> Vec<Oid> + 'a;
+
+impl<'repo> Rewrite<'repo> {
+    /// Creates a new rewrite of the history of `repo` with no filters, which
+    /// would reproduce every unsigned commit unchanged.
+    pub fn new(repo: &'repo Repository) -> Result<Rewrite<'repo>, Error> {
+        Ok(Rewrite {
+            repo: repo,
+            walk: try!(repo.revwalk()),
+            remove: Vec::new(),
+            tree_filter: None,
+            message_filter: None,
+            author_filter: None,
+            committer_filter: None,
+            parent_filter: None,
+            prune_empty: false,
+            tags: false,
+            backup: Some("refs/original/".to_string()),
+            force: false,
+            map: HashMap::new(),
+        })
+    }
+
+    /// Remove the file or directory at `path` from every rewritten commit, as
+    /// `git filter-branch --index-filter 'git rm -r --cached <path>'` does.
+    pub fn remove_path(&mut self, path: &str) -> &mut Rewrite<'repo> {
+        self.remove.push(path.to_string());
+        self
+    }
+
+    /// Set a callback producing the tree of each rewritten commit.
+    pub fn tree_filter<F>(&mut self, cb: F) -> &mut Rewrite<'repo>
+                          where F: FnMut(&Commit, &Tree) -> Result<Oid, Error>
+                                         + 'repo
+    {
+        self.tree_filter = Some(box cb as Box<TreeFilter<'repo>>);
+        self
+    }
+
+    /// Set a callback producing the message of each rewritten commit.
+    pub fn message_filter<F>(&mut self, cb: F) -> &mut Rewrite<'repo>
+                             where F: FnMut(&Commit, &[u8]) -> Vec<u8> + 'repo
+    {
+        self.message_filter = Some(box cb as Box<MessageFilter<'repo>>);
+        self
+    }
+
+    /// Set a callback mapping the author of each rewritten commit.
+    pub fn author_filter<F>(&mut self, cb: F) -> &mut Rewrite<'repo>
+                            where F: FnMut(&Signature)
+                                           -> Option<Signature<'static>> + 'repo
+    {
+        self.author_filter = Some(box cb as Box<SignatureFilter<'repo>>);
+        self
+    }
+
+    /// Set a callback mapping the committer of each rewritten commit.
+    pub fn committer_filter<F>(&mut self, cb: F) -> &mut Rewrite<'repo>
+                               where F: FnMut(&Signature)
+                                              -> Option<Signature<'static>>
+                                              + 'repo
+    {
+        self.committer_filter = Some(box cb as Box<SignatureFilter<'repo>>);
+        self
+    }
+
+    /// Set a callback producing the parents of each rewritten commit.
+    pub fn parent_filter<F>(&mut self, cb: F) -> &mut Rewrite<'repo>
+                            where F: FnMut(&Commit, Vec<Oid>) -> Vec<Oid>
+                                           + 'repo
+    {
+        self.parent_filter = Some(box cb as Box<ParentFilter<'repo>>);
+        self
+    }
+
+    /// Drop non-merge commits whose rewritten tree is the same as that of
+    /// their rewritten parent, mapping them to that parent instead.
+    ///
+    /// Root commits are always kept, even when their rewritten tree is empty,
+    /// as there is no parent to map them to.
+    pub fn prune_empty(&mut self, prune: bool) -> &mut Rewrite<'repo> {
+        self.prune_empty = prune;
+        self
+    }
+
+    /// Also move every tag under `refs/tags/` pointing at a rewritten commit,
+    /// whether or not it was passed to `run`.
+    ///
+    /// Annotated tags are recreated with their original name, tagger and
+    /// message under the same reference; any signature they carried is lost.
+    pub fn tags(&mut self, tags: bool) -> &mut Rewrite<'repo> {
+        self.tags = tags;
+        self
+    }
+
+    /// Set the namespace under which the old value of each rewritten
+    /// reference is saved, `refs/original/` by default.
+    ///
+    /// `None` disables the backups.
+    pub fn backup(&mut self, namespace: Option<&str>) -> &mut Rewrite<'repo> {
+        self.backup = namespace.map(|s| s.to_string());
+        self
+    }
+
+    /// Overwrite existing backups instead of failing before anything is
+    /// rewritten.
+    pub fn force(&mut self, force: bool) -> &mut Rewrite<'repo> {
+        self.force = force;
+        self
+    }
+
+    /// Gain access to the underlying revwalk, for example to hide commits
+    /// which should be kept as they are.
+    ///
+    /// The sorting of the walk is overridden by `run`.
+    pub fn revwalk(&mut self) -> &mut Revwalk<'repo> { &mut self.walk }
+
+    /// The map from the id of each rewritten commit to its new id.
+    ///
+    /// Commits dropped by `prune_empty` map to the commit replacing them.
+    pub fn map(&self) -> &HashMap<Oid, Oid> { &self.map }
+
+    /// Rewrite the history reachable from the references named by `refs`,
+    /// such as `refs/heads/master` or `HEAD`, and move them to the rewritten
+    /// commits.
+    ///
+    /// Symbolic references are resolved, so passing `HEAD` moves the branch
+    /// it points at. References whose commit is unchanged are left alone.
+    pub fn run(&mut self, refs: &[&str]) -> Result<(), Error> {
+        let mut targets = Vec::new();
+        for name in refs.iter() {
+            let reference = try!(try!(self.repo.find_reference(*name))
+                                     .resolve());
+            let name = match reference.name() {
+                Some(name) => name.to_string(),
+                None => return Err(Error::from_str("reference name is not \
+                                                    valid utf-8")),
+            };
+            let target = reference.target().unwrap();
+            let commit = try!(self.peel(target));
+            try!(self.walk.push(commit));
+            targets.push((name, target, commit));
+        }
+        if self.tags {
+            for reference in try!(self.repo.references_glob("refs/tags/*")) {
+                let name = match reference.name() {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                if targets.iter().any(|&(ref n, _, _)| *n == name) { continue }
+                let target = match reference.target() {
+                    Some(target) => target,
+                    None => continue,
+                };
+                match self.peel(target) {
+                    Ok(commit) => targets.push((name, target, commit)),
+                    Err(..) => {}
+                }
+            }
+        }
+        try!(self.check_backups(targets.as_slice()));
+
+        self.walk.set_sorting(SORT_TOPOLOGICAL | SORT_REVERSE);
+        let ids = self.walk.by_ref().collect::<Vec<Oid>>();
+        for id in ids.into_iter() {
+            try!(self.rewrite_commit(id));
+        }
+        for &(ref name, target, commit) in targets.iter() {
+            try!(self.update_ref(name.as_slice(), target, commit));
+        }
+        Ok(())
+    }
+
+    // Peel the target of a reference to the commit it designates.
+    fn peel(&self, target: Oid) -> Result<Oid, Error> {
+        let object = try!(self.repo.find_object(target, None));
+        let commit = try!(object.peel(ObjectType::Commit));
+        Ok(commit.id())
+    }
+
+    // Make sure no backup would be overwritten before anything is rewritten.
+    fn check_backups(&self, targets: &[(String, Oid, Oid)])
+                     -> Result<(), Error> {
+        let namespace = match self.backup {
+            Some(ref namespace) if !self.force => namespace,
+            _ => return Ok(()),
+        };
+        for &(ref name, _, _) in targets.iter() {
+            let backup = format!("{}{}", namespace, name);
+            if self.repo.find_reference(backup.as_slice()).is_ok() {
+                return Err(Error::from_str(format!("backup `{}` already \
+                                                    exists", backup)
+                                               .as_slice()))
+            }
+        }
+        Ok(())
+    }
+
+    fn rewrite_commit(&mut self, id: Oid) -> Result<(), Error> {
+        let commit = try!(self.repo.find_commit(id));
+        let mut parents = Vec::new();
+        for parent in commit.parent_ids() {
+            let parent = self.map.get(&parent).map(|p| *p).unwrap_or(parent);
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+        }
+        let parents = match self.parent_filter {
+            Some(ref mut f) => f.call_mut((&commit, parents)),
+            None => parents,
+        };
+
+        let mut tree = try!(commit.tree());
+        if self.remove.len() > 0 {
+            let mut index = try!(Index::new());
+            try!(index.read_tree(&tree));
+            for path in self.remove.iter() {
+                let path = Path::new(path.as_slice());
+                if index.get_path(&path, 0).is_some() {
+                    try!(index.remove(&path, 0));
+                }
+                try!(index.remove_dir(&path, 0));
+            }
+            let id = try!(index.write_tree_to(self.repo));
+            tree = try!(self.repo.find_tree(id));
+        }
+        let tree = match self.tree_filter {
+            Some(ref mut f) => try!(f.call_mut((&commit, &tree))),
+            None => tree.id(),
+        };
+
+        if self.prune_empty && parents.len() == 1 {
+            let parent = try!(self.repo.find_commit(parents[0]));
+            if parent.tree_id() == tree {
+                self.map.insert(id, parent.id());
+                return Ok(())
+            }
+        }
+
+        let mut builder = commit.with_changes();
+        builder.tree_id(tree).parent_ids(parents.as_slice());
+        match self.author_filter {
+            Some(ref mut f) => {
+                match f.call_mut((&commit.author(),)) {
+                    Some(sig) => { builder.author(&sig); }
+                    None => {}
+                }
+            }
+            None => {}
+        }
+        match self.committer_filter {
+            Some(ref mut f) => {
+                match f.call_mut((&commit.committer(),)) {
+                    Some(sig) => { builder.committer(&sig); }
+                    None => {}
+                }
+            }
+            None => {}
+        }
+        match self.message_filter {
+            Some(ref mut f) => {
+                let message = f.call_mut((&commit, commit.message_raw_bytes()));
+                builder.message_bytes(message.as_slice());
+            }
+            None => {}
+        }
+        let new = try!(builder.create(self.repo));
+        self.map.insert(id, new);
+        Ok(())
+    }
+
+    // Move `name`, which points at `target` peeling to `commit`, to the
+    // rewritten commit, saving its old value first.
+    fn update_ref(&self, name: &str, target: Oid, commit: Oid)
+                  -> Result<(), Error> {
+        let new = match self.map.get(&commit) {
+            Some(&new) if new != commit => new,
+            _ => return Ok(()),
+        };
+        match self.backup {
+            Some(ref namespace) => {
+                let backup = format!("{}{}", namespace, name);
+                try!(self.repo.reference(backup.as_slice(), target, true, None,
+                                         "rewrite: backup"));
+            }
+            None => {}
+        }
+        if target == commit {
+            try!(self.repo.reference(name, new, true, None, "rewrite"));
+            return Ok(())
+        }
+
+        // An annotated tag, which is recreated pointing at the new commit.
+        let tag = try!(self.repo.find_tag(target));
+        if tag.target_id() != commit {
+            return Err(Error::from_str(format!("cannot rewrite nested tag `{}`",
+                                               name).as_slice()))
+        }
+        let tagger = match tag.tagger() {
+            Some(sig) => try!(Signature::new(sig.name().unwrap_or(""),
+                                             sig.email().unwrap_or(""),
+                                             sig.when().seconds() as u64,
+                                             sig.when().offset_minutes())),
+            None => try!(self.repo.signature()),
+        };
+        let object = try!(self.repo.find_object(new, None));
+        let tag_name = match tag.name() {
+            Some(tag_name) => tag_name,
+            None if name.starts_with("refs/tags/") => {
+                name.slice_from("refs/tags/".len())
+            }
+            None => name,
+        };
+        let id = try!(self.repo.tag_annotation_create(tag_name, &object,
+                                                      &tagger,
+                                                      tag.message()
+                                                         .unwrap_or("")));
+        try!(self.repo.reference(name, id, true, None, "rewrite"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Signature, Rewrite, ObjectType};
+    use test::commit_files;
+
+    #[test]
+    fn remove_path() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.head().unwrap().target().unwrap();
+        let a = commit_files(&repo, &[("secret", "password")]);
+        let b = commit_files(&repo, &[("public", "hello")]);
+        {
+            let obj = repo.find_object(b, None).unwrap();
+            let sig = repo.signature().unwrap();
+            repo.tag("v1", &obj, &sig, "release", false).unwrap();
+            let id = repo.tag_annotation_create("v2", &obj, &sig,
+                                                "other").unwrap();
+            repo.reference("refs/other/v2", id, false, None, "tag").unwrap();
+        }
+
+        let mut rewrite = Rewrite::new(&repo).unwrap();
+        rewrite.remove_path("secret").prune_empty(true).tags(true);
+        rewrite.run(&["HEAD", "refs/other/v2"]).unwrap();
+
+        assert_eq!(rewrite.map().get(&root), Some(&root));
+        assert_eq!(rewrite.map().get(&a), Some(&root));
+        let new_b = *rewrite.map().get(&b).unwrap();
+        assert!(new_b != b);
+
+        let head = repo.find_commit(repo.head().unwrap().target().unwrap())
+                       .unwrap();
+        assert_eq!(head.id(), new_b);
+        assert_eq!(head.parent_id(0).unwrap(), root);
+        let tree = head.tree().unwrap();
+        assert!(tree.get_name("secret").is_none());
+        assert!(tree.get_name("public").is_some());
+
+        let backup = repo.find_reference("refs/original/refs/heads/master")
+                         .unwrap();
+        assert_eq!(backup.target(), Some(b));
+        let tag = repo.find_reference("refs/tags/v1").unwrap();
+        let obj = repo.find_object(tag.target().unwrap(), None).unwrap();
+        assert_eq!(obj.kind(), Some(ObjectType::Tag));
+        assert_eq!(obj.peel(ObjectType::Commit).unwrap().id(), new_b);
+        assert!(repo.find_reference("refs/original/refs/tags/v1").is_ok());
+        let other = repo.find_reference("refs/other/v2").unwrap();
+        let other = repo.find_tag(other.target().unwrap()).unwrap();
+        assert_eq!(other.name(), Some("v2"));
+        assert_eq!(other.target_id(), new_b);
+        assert!(repo.find_reference("refs/tags/refs/other/v2").is_err());
+        assert!(repo.find_reference("refs/tags/v2").is_err());
+
+        // A second rewrite refuses to clobber the backups.
+        let mut rewrite = Rewrite::new(&repo).unwrap();
+        assert!(rewrite.run(&["HEAD"]).is_err());
+        assert_eq!(repo.head().unwrap().target(), Some(new_b));
+    }
+
+    #[test]
+    fn filters() {
+        let (_td, repo) = ::test::repo_init();
+        let root = repo.head().unwrap().target().unwrap();
+        let a = commit_files(&repo, &[("a", "a")]);
+
+        let mut rewrite = Rewrite::new(&repo).unwrap();
+        rewrite.backup(None)
+               .message_filter(|_, msg| {
+                   let mut msg = msg.to_vec();
+                   msg.push_all(b"\nrewritten\n");
+                   msg
+               })
+               .author_filter(|sig| {
+                   if sig.email() == Some("email") {
+                       Some(Signature::new("name", "new@example.com",
+                                           sig.when().seconds() as u64,
+                                           sig.when().offset_minutes())
+                                      .unwrap())
+                   } else {
+                       None
+                   }
+               });
+        rewrite.revwalk().hide(root).unwrap();
+        rewrite.run(&["refs/heads/master"]).unwrap();
+        assert!(rewrite.map().get(&root).is_none());
+
+        let new = repo.find_commit(*rewrite.map().get(&a).unwrap()).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(new.id()));
+        assert_eq!(new.parent_id(0).unwrap(), root);
+        assert_eq!(new.author().email(), Some("new@example.com"));
+        assert_eq!(new.committer().email(), Some("email"));
+        assert_eq!(new.message(), Some("a\nrewritten\n"));
+        assert!(repo.find_reference("refs/original/refs/heads/master")
+                    .is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::mem;
+use std::raw as stdraw;
+use libc::{c_void, size_t};
+
+use {raw, Buf, Error, Oid, Signature, ObjectType};
+
+/// A pluggable signing backend, such as GPG or SSH, used to sign commits and
+/// tags.
+pub trait Signer {
+    /// Produce a signature for `data`, the exact contents of the commit or tag
+    /// object being signed.
+    ///
+    /// The returned signature is stored verbatim in the object, so it is
+    /// typically an ASCII-armored block such as
+    /// `-----BEGIN PGP SIGNATURE-----`.
+    fn sign(&mut self, data: &[u8]) -> Result<String, Error>;
+}
+
+/// A pluggable verification backend matching a `Signer`.
+pub trait Verifier {
+    /// Check that `signature` is a valid signature of `data`.
+    ///
+    /// An `Err` should be returned if the verification could not be carried
+    /// out at all, and `Ok(false)` if the signature does not match.
+    fn verify(&mut self, data: &[u8], signature: &[u8]) -> Result<bool, Error>;
+}
+
+// The armor headers which start the signature appended to a signed tag.
+static TAG_SIGNATURE_HEADERS: [&'static [u8], ..3] = [
+    b"-----BEGIN PGP SIGNATURE-----",
+    b"-----BEGIN PGP MESSAGE-----",
+    b"-----BEGIN SSH SIGNATURE-----",
+];
+
+// Copy `data` into a new libgit2 buffer.
+pub fn buf(data: &[u8]) -> Result<Buf, Error> {
+    let mut buf = Buf::new();
+    unsafe {
+        try_call!(raw::git_buf_set(buf.raw(), data.as_ptr() as *const c_void,
+                                   data.len() as size_t));
+    }
+    Ok(buf)
+}
+
+// Format a signature as it appears in an object header, e.g.
+// `Name <email> 1234567890 +0100`.
+pub fn header_signature(sig: &Signature) -> Vec<u8> {
+    let when = sig.when();
+    let (offset, sign) = match when.offset_minutes() {
+        n if n < 0 => (-n, '-'),
+        n => (n, '+'),
+    };
+    let mut ret = Vec::new();
+    ret.push_all(sig.name_bytes());
+    ret.push_all(b" <");
+    ret.push_all(sig.email_bytes());
+    ret.push_all(format!("> {} {}{:02}{:02}", when.seconds(), sign,
+                         offset / 60, offset % 60).as_bytes());
+    ret
+}
+
+// Find the offset at which the signature of a signed tag starts, if any.
+pub fn tag_signature_start(content: &[u8]) -> Option<uint> {
+    let mut start = 0;
+    let mut found = None;
+    for line in content.split(|b| *b == b'\n') {
+        if TAG_SIGNATURE_HEADERS.iter().any(|h| line == *h) {
+            found = Some(start);
+        }
+        start += line.len() + 1;
+    }
+    found
+}
+
+// Split a commit object into the value of its `field` header, one line per
+// line of the header, and the commit without that header. Returns `None` if
+// the commit has no such header.
+pub fn commit_signature_split(content: &[u8],
+                              field: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let field = field.as_bytes();
+    let mut signature = Vec::new();
+    let mut data = Vec::new();
+    let mut found = false;
+    let mut in_field = false;
+    let mut pos = 0;
+    while pos < content.len() {
+        let end = match content.slice_from(pos).position_elem(&b'\n') {
+            Some(i) => pos + i + 1,
+            None => content.len(),
+        };
+        let line = content.slice(pos, end);
+        pos = end;
+        if line == b"\n" {
+            data.push_all(line);
+            data.push_all(content.slice_from(pos));
+            break
+        }
+        if in_field && line[0] == b' ' {
+            signature.push_all(line.slice_from(1));
+            continue
+        }
+        in_field = line.len() > field.len() &&
+                   line.slice_to(field.len()) == field &&
+                   line[field.len()] == b' ';
+        if in_field {
+            found = true;
+            signature.push_all(line.slice_from(field.len() + 1));
+        } else {
+            data.push_all(line);
+        }
+    }
+    if found {Some((signature, data))} else {None}
+}
+
+// Add `signature` to a commit object as the last header, named `field`.
+// Returns `None` if `content` does not look like a commit.
+pub fn commit_add_signature(content: &[u8], field: &str,
+                            signature: &[u8]) -> Option<Vec<u8>> {
+    if !content.starts_with(b"tree ") { return None }
+    let end = match content.windows(2).position(|w| w == b"\n\n") {
+        Some(i) => i + 1,
+        None => return None,
+    };
+    let signature = match signature.iter().rposition(|b| *b != b'\n') {
+        Some(i) => signature.slice_to(i + 1),
+        None => return None,
+    };
+    let mut ret = content.slice_to(end).to_vec();
+    ret.push_all(field.as_bytes());
+    ret.push(b' ');
+    for (i, line) in signature.split(|b| *b == b'\n').enumerate() {
+        if i > 0 { ret.push_all(b"\n ") }
+        ret.push_all(line);
+    }
+    ret.push(b'\n');
+    ret.push_all(content.slice_from(end));
+    Some(ret)
+}
+
+// Read the raw contents of an object from the object database.
+pub unsafe fn read_object(repo: *mut raw::git_repository,
+                          id: Oid) -> Result<Vec<u8>, Error> {
+    let mut odb = 0 as *mut raw::git_odb;
+    try_call!(raw::git_repository_odb(&mut odb, repo));
+    let mut obj = 0 as *mut raw::git_odb_object;
+    let rc = raw::git_odb_read(&mut obj, odb, id.raw());
+    raw::git_odb_free(odb);
+    try_call!(rc);
+    let data: &[u8] = mem::transmute(stdraw::Slice {
+        data: raw::git_odb_object_data(obj) as *const u8,
+        len: raw::git_odb_object_size(obj) as uint,
+    });
+    let ret = data.to_vec();
+    raw::git_odb_object_free(obj);
+    Ok(ret)
+}
+
+// Write `data` to the object database as an object of type `kind`.
+pub unsafe fn write_object(repo: *mut raw::git_repository, kind: ObjectType,
+                           data: &[u8]) -> Result<Oid, Error> {
+    let mut odb = 0 as *mut raw::git_odb;
+    try_call!(raw::git_repository_odb(&mut odb, repo));
+    let mut id = raw::git_oid { id: [0, ..raw::GIT_OID_RAWSZ] };
+    let rc = raw::git_odb_write(&mut id, odb, data.as_ptr() as *const c_void,
+                                data.len() as size_t, kind.raw());
+    raw::git_odb_free(odb);
+    try_call!(rc);
+    Ok(Oid::from_raw(&id))
+}
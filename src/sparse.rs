@@ -0,0 +1,503 @@
+use std::c_str::CString;
+use std::io::{fs, File, USER_DIR};
+use std::io::fs::PathExtensions;
+use std::mem;
+use libc::{c_char, c_uint, size_t};
+
+use {raw, Error, Repository, Statuses, Index, Object, ObjectType, Pathspec};
+use PATHSPEC_DEFAULT;
+use build::CheckoutBuilder;
+use ignore;
+
+// The file listing the patterns of a sparse checkout, one per line.
+fn file(repo: &Repository) -> Path {
+    repo.path().join("info/sparse-checkout")
+}
+
+/// Returns whether `core.sparseCheckout` is enabled for `repo`.
+pub fn enabled(repo: &Repository) -> Result<bool, Error> {
+    config_bool(repo, "core.sparsecheckout")
+}
+
+/// Returns whether the patterns of the sparse checkout are in cone mode.
+pub fn cone_enabled(repo: &Repository) -> Result<bool, Error> {
+    config_bool(repo, "core.sparsecheckoutcone")
+}
+
+fn config_bool(repo: &Repository, name: &str) -> Result<bool, Error> {
+    let mut config = try!(repo.config());
+    let config = try!(config.snapshot());
+    Ok(config.get_bool(name).unwrap_or(false))
+}
+
+/// Read the patterns of the sparse checkout, skipping blank lines and
+/// comments. A missing file has no patterns.
+pub fn read(repo: &Repository) -> Result<Vec<String>, Error> {
+    let path = file(repo);
+    if !path.exists() { return Ok(Vec::new()) }
+    let contents = try!(File::open(&path).read_to_end().map_err(::io_error));
+    let contents = String::from_utf8_lossy(contents.as_slice());
+    Ok(contents.lines_any()
+               .map(|l| l.trim())
+               .filter(|l| l.len() > 0 && !l.starts_with("#"))
+               .map(|l| l.to_string())
+               .collect())
+}
+
+/// Replace the patterns of the sparse checkout.
+pub fn write(repo: &Repository, lines: &[String]) -> Result<(), Error> {
+    let path = file(repo);
+    try!(fs::mkdir_recursive(&path.dir_path(), USER_DIR)
+             .map_err(::io_error));
+    let mut contents = String::new();
+    for line in lines.iter() {
+        contents.push_str(line.as_slice());
+        contents.push('\n');
+    }
+    File::create(&path).write(contents.as_bytes()).map_err(::io_error)
+}
+
+/// Normalize the directories given to a cone mode sparse checkout, removing
+/// leading and trailing slashes and duplicates.
+pub fn cone_dirs(dirs: &[&str]) -> Vec<String> {
+    let mut ret = dirs.iter()
+                      .map(|d| d.trim_chars('/').to_string())
+                      .filter(|d| d.len() > 0)
+                      .collect::<Vec<String>>();
+    ret.sort();
+    ret.dedup();
+    ret
+}
+
+/// Generate the patterns of a cone mode sparse checkout including the files
+/// at the root, everything inside `dirs`, and the files directly inside each
+/// of their parents.
+pub fn cone_patterns(dirs: &[String]) -> Vec<String> {
+    let dirs = dirs.iter().filter(|d| !inside_any(dirs, d.as_slice()))
+                   .map(|d| d.clone())
+                   .collect::<Vec<String>>();
+    let mut parents = Vec::new();
+    for dir in dirs.iter() {
+        for (i, c) in dir.as_slice().char_indices() {
+            if c == '/' { parents.push(dir.as_slice().slice_to(i)) }
+        }
+    }
+    parents.sort();
+    parents.dedup();
+
+    let mut ret = vec!["/*".to_string(), "!/*/".to_string()];
+    for parent in parents.iter() {
+        // Everything inside a recursive directory is already included.
+        if dirs.iter().any(|d| d.as_slice() == *parent) ||
+           inside_any(dirs.as_slice(), *parent) {
+            continue
+        }
+        ret.push(format!("/{}/", parent));
+        ret.push(format!("!/{}/*/", parent));
+    }
+    for dir in dirs.iter() {
+        ret.push(format!("/{}/", dir));
+    }
+    ret
+}
+
+// Returns whether `path` is strictly inside one of `dirs`.
+fn inside_any(dirs: &[String], path: &str) -> bool {
+    dirs.iter().any(|d| {
+        let d = d.as_slice();
+        path.len() > d.len() && path.starts_with(d) &&
+            path.as_bytes()[d.len()] == b'/'
+    })
+}
+
+/// Recover the directories of a cone mode sparse checkout from its
+/// patterns: those included recursively, without a `!/dir/*/` pattern
+/// excluding their subdirectories.
+pub fn cone_list(patterns: &[String]) -> Vec<String> {
+    patterns.iter().filter_map(|p| {
+        let p = p.as_slice();
+        if p.len() < 3 || !p.starts_with("/") || !p.ends_with("/") {
+            return None
+        }
+        let dir = p.slice(1, p.len() - 1);
+        let exclude = format!("!/{}/*/", dir);
+        if patterns.iter().any(|p| *p == exclude) {
+            None
+        } else {
+            Some(dir.to_string())
+        }
+    }).collect()
+}
+
+/// Returns whether `path`, relative to the working directory, is included
+/// in the sparse checkout.
+///
+/// The path itself is matched first and then each of its parents, deepest
+/// first, until a pattern decides. Paths matched by no pattern are excluded.
+pub fn included(patterns: &[ignore::Pattern], path: &[u8]) -> bool {
+    match ignore::last_match(patterns, path, false) {
+        Some(negated) => return !negated,
+        None => {}
+    }
+    let mut end = path.len();
+    loop {
+        end = match path.slice_to(end).rposition_elem(&b'/') {
+            Some(i) => i,
+            None => return false,
+        };
+        match ignore::last_match(patterns, path.slice_to(end), true) {
+            Some(negated) => return !negated,
+            None => {}
+        }
+    }
+}
+
+/// Bring the skip-worktree bits of the index and the files of the working
+/// directory in line with the sparse checkout.
+///
+/// Excluded files are removed and marked skip-worktree, unless they have
+/// local modifications, in which case they are kept. Included files which
+/// are missing are checked out again. When sparse checkout is disabled
+/// every file is included.
+pub fn apply(repo: &Repository) -> Result<(), Error> {
+    let workdir = match repo.workdir() {
+        Some(dir) => dir,
+        None => return Err(Error::from_str("cannot use sparse checkout in \
+                                            a bare repository")),
+    };
+    let patterns = if try!(enabled(repo)) {
+        Some(try!(patterns(repo)))
+    } else {
+        None
+    };
+
+    let mut index = try!(repo.index());
+    let mut changed = Vec::new();
+    let mut restore = Vec::new();
+    for mut entry in index.iter() {
+        if entry.stage() != 0 { continue }
+        let path = entry.path.as_bytes_no_nul().to_vec();
+        let file = workdir.join(path.as_slice());
+        let include = match patterns {
+            Some(ref p) => included(p.as_slice(), path.as_slice()),
+            None => true,
+        };
+        let skip = if include {
+            if !file.exists() { restore.push(path.clone()) }
+            false
+        } else if file.exists() {
+            let status = try!(repo.status_file(&Path::new(path.as_slice())));
+            if status.intersects(::STATUS_WT_MODIFIED |
+                                 ::STATUS_WT_TYPECHANGE) {
+                false
+            } else {
+                try!(fs::unlink(&file).map_err(::io_error));
+                remove_empty_dirs(&workdir, &file.dir_path());
+                true
+            }
+        } else {
+            true
+        };
+        if skip != entry.is_skip_worktree() {
+            entry.set_skip_worktree(skip);
+            changed.push(entry);
+        }
+    }
+    for entry in changed.iter() {
+        try!(index.add(entry));
+    }
+    try!(index.write());
+
+    // An empty list of paths would check out every file.
+    if restore.len() == 0 { return Ok(()) }
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    for path in restore.iter() {
+        checkout.path(path.as_slice());
+    }
+    unsafe {
+        let mut opts = mem::zeroed();
+        try_call!(raw::git_checkout_init_options(&mut opts,
+                            raw::GIT_CHECKOUT_OPTIONS_VERSION));
+        checkout.configure(&mut opts);
+        opts.checkout_strategy |=
+            raw::GIT_CHECKOUT_DISABLE_PATHSPEC_MATCH as c_uint;
+        try_call_panic!(raw::git_checkout_index(repo.raw(), 0 as *mut _,
+                                                &opts));
+    }
+    Ok(())
+}
+
+// Remove `dir` and its parents while they are empty, stopping at the
+// working directory.
+fn remove_empty_dirs(workdir: &Path, dir: &Path) {
+    let mut dir = dir.clone();
+    while dir != *workdir && workdir.is_ancestor_of(&dir) {
+        match fs::readdir(&dir) {
+            Ok(ref entries) if entries.len() == 0 => {}
+            _ => break,
+        }
+        if fs::rmdir(&dir).is_err() { break }
+        dir = dir.dir_path();
+    }
+}
+
+/// The paths a checkout was restricted to by `prepare`, which must outlive
+/// the checkout.
+pub struct Paths {
+    _strs: Vec<CString>,
+    _ptrs: Vec<*const c_char>,
+}
+
+/// Load the index of the tree a checkout is about to check out, if sparse
+/// checkout is enabled, for `prepare` and `finish`. A `treeish` of `None`
+/// stands for `HEAD`.
+pub fn target_index(repo: &Repository, treeish: Option<&Object>)
+                    -> Result<Option<Index>, Error> {
+    if !try!(enabled(repo)) { return Ok(None) }
+    let tree = match treeish {
+        Some(obj) => try!(obj.peel(ObjectType::Tree)).id(),
+        None => try!(repo.revparse_single("HEAD^{tree}")).id(),
+    };
+    let mut index = try!(Index::new());
+    try!(index.read_tree(&try!(repo.find_tree(tree))));
+    Ok(Some(index))
+}
+
+/// Restrict a checkout of `target` to the paths included in the sparse
+/// checkout, keeping the strategy and any paths set by the caller.
+///
+/// Returns `None` if sparse checkout is disabled or the checkout does not
+/// update the working directory, in which case `finish` need not be called.
+pub fn prepare(repo: &Repository, opts: &mut raw::git_checkout_options,
+               target: &Index) -> Result<Option<Paths>, Error> {
+    if !try!(enabled(repo)) { return Ok(None) }
+    let updates = (raw::GIT_CHECKOUT_SAFE |
+                   raw::GIT_CHECKOUT_SAFE_CREATE |
+                   raw::GIT_CHECKOUT_FORCE) as c_uint;
+    if opts.checkout_strategy & updates == 0 { return Ok(None) }
+    let patterns = try!(patterns(repo));
+
+    let exact = raw::GIT_CHECKOUT_DISABLE_PATHSPEC_MATCH as c_uint;
+    let wanted = range(0, opts.paths.count as uint).map(|i| unsafe {
+        let ptr = *opts.paths.strings.offset(i as int);
+        CString::new(ptr as *const c_char, false).as_bytes_no_nul().to_vec()
+    }).collect::<Vec<Vec<u8>>>();
+    let pathspec = if wanted.len() > 0 && opts.checkout_strategy & exact == 0 {
+        Some(try!(Pathspec::new(wanted.iter().map(|p| p.as_slice()))))
+    } else {
+        None
+    };
+
+    // Files about to be deleted are only in the current index.
+    let index = try!(repo.index());
+    let mut paths = Vec::new();
+    for entry in target.iter().chain(index.iter()) {
+        let path = entry.path.as_bytes_no_nul().to_vec();
+        if !included(patterns.as_slice(), path.as_slice()) { continue }
+        let keep = match pathspec {
+            Some(ref ps) => {
+                ps.matches_path(&Path::new(path.as_slice()), PATHSPEC_DEFAULT)
+            }
+            None => wanted.len() == 0 || wanted.contains(&path),
+        };
+        if keep { paths.push(path) }
+    }
+    paths.sort();
+    paths.dedup();
+
+    let strs = paths.iter().map(|p| p.as_slice().to_c_str())
+                    .collect::<Vec<CString>>();
+    let ptrs = strs.iter().map(|c| c.as_ptr()).collect::<Vec<_>>();
+    if ptrs.len() == 0 {
+        // An empty list of paths would check out every file.
+        opts.checkout_strategy = raw::GIT_CHECKOUT_NONE as c_uint;
+    } else {
+        opts.paths.strings = ptrs.as_ptr() as *mut _;
+        opts.paths.count = ptrs.len() as size_t;
+        opts.checkout_strategy |= exact;
+    }
+    Ok(Some(Paths { _strs: strs, _ptrs: ptrs }))
+}
+
+/// Complete a checkout restricted by `prepare`.
+///
+/// The entries of the files excluded from the sparse checkout are updated
+/// to those of `target`, when checking out a tree, and the working directory
+/// is then brought in line with `apply`.
+pub fn finish(repo: &Repository, target: Option<&Index>)
+              -> Result<(), Error> {
+    match target {
+        Some(target) => try!(update_excluded(repo, target)),
+        None => {}
+    }
+    apply(repo)
+}
+
+fn update_excluded(repo: &Repository, target: &Index) -> Result<(), Error> {
+    let patterns = try!(patterns(repo));
+    let patterns = patterns.as_slice();
+    let mut index = try!(repo.index());
+    let removed = index.iter().filter(|entry| {
+        let path = entry.path.as_bytes_no_nul();
+        entry.stage() == 0 && !included(patterns, path) &&
+            target.get_path(&Path::new(path), 0).is_none()
+    }).map(|entry| Path::new(entry.path.as_bytes_no_nul()))
+      .collect::<Vec<Path>>();
+    for path in removed.iter() {
+        try!(index.remove(path, 0));
+    }
+    for mut entry in target.iter() {
+        if included(patterns, entry.path.as_bytes_no_nul()) { continue }
+        entry.set_skip_worktree(true);
+        try!(index.add(&entry));
+    }
+    index.write()
+}
+
+fn patterns(repo: &Repository) -> Result<Vec<ignore::Pattern>, Error> {
+    let lines = try!(read(repo));
+    Ok(ignore::parse_patterns(lines.connect("\n").as_slice()))
+}
+
+/// Hide the files excluded from the sparse checkout, which would otherwise
+/// show up as deleted in the working directory.
+pub fn filter_statuses(repo: &Repository, statuses: &mut Statuses)
+                       -> Result<(), Error> {
+    let index = try!(repo.index());
+    statuses.retain(|entry| {
+        if entry.status() != ::STATUS_WT_DELETED { return true }
+        let path = Path::new(entry.path_bytes());
+        match index.get_path(&path, 0) {
+            Some(entry) => !entry.is_skip_worktree(),
+            None => true,
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::File;
+    use std::io::fs::PathExtensions;
+    use build::CheckoutBuilder;
+    use Repository;
+    use test::commit_files;
+
+    fn skipped(repo: &Repository, path: &str) -> bool {
+        let index = repo.index().unwrap();
+        index.get_path(&Path::new(path), 0).unwrap().is_skip_worktree()
+    }
+
+    #[test]
+    fn patterns() {
+        let dirs = super::cone_dirs(&["a/b/", "/d", "a/b/c"]);
+        assert_eq!(dirs, vec!["a/b".to_string(), "a/b/c".to_string(),
+                              "d".to_string()]);
+        let patterns = super::cone_patterns(dirs.as_slice());
+        assert_eq!(patterns, vec!["/*".to_string(), "!/*/".to_string(),
+                                  "/a/".to_string(), "!/a/*/".to_string(),
+                                  "/a/b/".to_string(), "/d/".to_string()]);
+        assert_eq!(super::cone_list(patterns.as_slice()),
+                   vec!["a/b".to_string(), "d".to_string()]);
+
+        let text = patterns.connect("\n");
+        let patterns = ::ignore::parse_patterns(text.as_slice());
+        let patterns = patterns.as_slice();
+        assert!(super::included(patterns, b"top.txt"));
+        assert!(super::included(patterns, b"a/x.txt"));
+        assert!(super::included(patterns, b"a/b/y.txt"));
+        assert!(super::included(patterns, b"a/b/c/z.txt"));
+        assert!(!super::included(patterns, b"a/c/z.txt"));
+        assert!(super::included(patterns, b"d/e/f.txt"));
+        assert!(!super::included(patterns, b"e/f.txt"));
+    }
+
+    #[test]
+    fn cone() {
+        let (td, repo) = ::test::repo_init();
+        let root = td.path();
+        commit_files(&repo, &[("top.txt", "top"), ("a/x.txt", "x"),
+                              ("a/b/y.txt", "y"), ("a/c/z.txt", "z"),
+                              ("d/w.txt", "w")]);
+
+        repo.sparse_checkout_set(&["a/b"], true).unwrap();
+        assert!(root.join("top.txt").exists());
+        assert!(root.join("a/x.txt").exists());
+        assert!(root.join("a/b/y.txt").exists());
+        assert!(!root.join("a/c").exists());
+        assert!(!root.join("d").exists());
+        assert!(skipped(&repo, "a/c/z.txt"));
+        assert!(skipped(&repo, "d/w.txt"));
+        assert!(!skipped(&repo, "a/b/y.txt"));
+        assert_eq!(repo.statuses(None).unwrap().len(), 0);
+        assert_eq!(repo.sparse_checkout_list().unwrap(),
+                   vec!["a/b".to_string()]);
+
+        repo.sparse_checkout_add(&["d"]).unwrap();
+        assert!(root.join("d/w.txt").exists());
+        assert!(!skipped(&repo, "d/w.txt"));
+        assert_eq!(repo.sparse_checkout_list().unwrap(),
+                   vec!["a/b".to_string(), "d".to_string()]);
+
+        let mut opts = CheckoutBuilder::new();
+        opts.force();
+        repo.checkout_head(Some(&mut opts)).unwrap();
+        assert!(!root.join("a/c/z.txt").exists());
+        assert!(root.join("d/w.txt").exists());
+
+        repo.sparse_checkout_disable().unwrap();
+        assert!(root.join("a/c/z.txt").exists());
+        assert!(!skipped(&repo, "a/c/z.txt"));
+        assert_eq!(repo.statuses(None).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn checkout_tree() {
+        let (td, repo) = ::test::repo_init();
+        let root = td.path();
+        commit_files(&repo, &[("top.txt", "top"), ("d/w.txt", "w")]);
+        let old = repo.head().unwrap().target().unwrap();
+        let old_w = repo.index().unwrap()
+                        .get_path(&Path::new("d/w.txt"), 0).unwrap().id;
+        commit_files(&repo, &[("top.txt", "new"), ("d/w.txt", "new"),
+                              ("e/v.txt", "v")]);
+
+        repo.sparse_checkout_set(&["/*", "!/*/"], false).unwrap();
+        assert!(!root.join("d").exists());
+        assert!(!root.join("e").exists());
+
+        let obj = repo.find_object(old, None).unwrap();
+        repo.checkout_tree(&obj, Some(&mut CheckoutBuilder::new()))
+            .unwrap();
+        assert_eq!(File::open(&root.join("top.txt")).read_to_string()
+                        .unwrap().as_slice(), "top");
+        assert!(!root.join("d").exists());
+        assert!(!root.join("e").exists());
+        let index = repo.index().unwrap();
+        let entry = index.get_path(&Path::new("d/w.txt"), 0).unwrap();
+        assert_eq!(entry.id, old_w);
+        assert!(entry.is_skip_worktree());
+        assert!(index.get_path(&Path::new("e/v.txt"), 0).is_none());
+    }
+
+    #[test]
+    fn non_cone() {
+        let (td, repo) = ::test::repo_init();
+        let root = td.path();
+        assert!(repo.sparse_checkout_add(&["d/"]).is_err());
+        commit_files(&repo, &[("top.txt", "top"), ("a/x.txt", "x"),
+                              ("a/b/y.txt", "y"), ("d/w.txt", "w")]);
+        File::create(&root.join("a/x.txt")).write_str("changed").unwrap();
+
+        repo.sparse_checkout_set(&["/top.txt", "d/"], false).unwrap();
+        assert!(root.join("top.txt").exists());
+        assert!(root.join("d/w.txt").exists());
+        assert!(root.join("a/x.txt").exists());
+        assert!(!skipped(&repo, "a/x.txt"));
+        assert!(!root.join("a/b").exists());
+        assert!(skipped(&repo, "a/b/y.txt"));
+        assert_eq!(repo.sparse_checkout_list().unwrap(),
+                   vec!["/top.txt".to_string(), "d/".to_string()]);
+    }
+}
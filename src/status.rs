@@ -38,6 +38,9 @@ pub enum StatusShow {
 /// allowing indexing as well as provding an iterator.
 pub struct Statuses<'repo> {
     raw: *mut raw::git_status_list,
+    // The positions in `raw` of the entries kept by `retain`, if it was
+    // called.
+    visible: Option<Vec<uint>>,
     marker1: marker::ContravariantLifetime<'repo>,
     marker2: marker::NoSend,
     marker3: marker::NoSync,
@@ -241,6 +244,7 @@ impl<'repo> Statuses<'repo> {
     pub unsafe fn from_raw(raw: *mut raw::git_status_list) -> Statuses<'repo> {
         Statuses {
             raw: raw,
+            visible: None,
             marker1: marker::ContravariantLifetime,
             marker2: marker::NoSend,
             marker3: marker::NoSync,
@@ -251,6 +255,13 @@ impl<'repo> Statuses<'repo> {
     ///
     /// Returns `None` if the index is out of bounds.
     pub fn get(&self, index: uint) -> Option<StatusEntry> {
+        let index = match self.visible {
+            Some(ref visible) => match visible.get(index) {
+                Some(&i) => i,
+                None => return None,
+            },
+            None => index,
+        };
         unsafe {
             let p = raw::git_status_byindex(self.raw, index as size_t);
             if p.is_null() {
@@ -266,7 +277,30 @@ impl<'repo> Statuses<'repo> {
     /// If there are no changes in status (at least according the options given
     /// when the status list was created), this can return 0.
     pub fn len(&self) -> uint {
-        unsafe { raw::git_status_list_entrycount(self.raw) as uint }
+        match self.visible {
+            Some(ref visible) => visible.len(),
+            None => unsafe {
+                raw::git_status_list_entrycount(self.raw) as uint
+            },
+        }
+    }
+
+    /// Keep only the entries for which `f` returns `true`.
+    ///
+    /// The remaining entries keep their order, and are renumbered from 0.
+    pub fn retain<F>(&mut self, mut f: F)
+                     where F: FnMut(&StatusEntry) -> bool {
+        let mut visible = Vec::new();
+        for i in range(0, self.len()) {
+            let keep = f(&self.get(i).unwrap());
+            if keep {
+                visible.push(match self.visible {
+                    Some(ref v) => v[i],
+                    None => i,
+                });
+            }
+        }
+        self.visible = Some(visible);
     }
 
     /// Returns an iterator over the statuses in this list.
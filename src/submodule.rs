@@ -1,7 +1,14 @@
+use std::c_str::CString;
 use std::str;
 use std::kinds::marker;
+use std::io::File;
+use std::io::fs::PathExtensions;
+use libc::c_char;
 
-use {raw, Oid, Repository, Error, SubmoduleStatus};
+use {raw, Oid, Repository, RepositoryInitOptions, Error, Config};
+use {SubmoduleStatus, SubmoduleUpdate, SubmoduleIgnore, SubmoduleRecurse};
+use RemoteCallbacks;
+use build::CheckoutBuilder;
 
 /// A structure to represent a git [submodule][1]
 ///
@@ -13,6 +20,14 @@ pub struct Submodule<'repo> {
     marker3: marker::NoSync,
 }
 
+/// Options which can be provided to `Submodule::update` and
+/// `Submodule::clone`.
+pub struct SubmoduleUpdateOptions<'cb> {
+    checkout: Option<CheckoutBuilder<'cb>>,
+    callbacks: Option<RemoteCallbacks<'cb>>,
+    allow_fetch: bool,
+}
+
 impl<'repo> Submodule<'repo> {
     /// Create a new object from its raw component.
     ///
@@ -216,6 +231,349 @@ impl<'repo> Submodule<'repo> {
         unsafe { try_call!(raw::git_submodule_status(&mut ret, self.raw)); }
         Ok(SubmoduleStatus::from_bits_truncate(ret as u32))
     }
+
+    /// Get where the submodule is present: in the HEAD tree, the index, the
+    /// configuration or the working directory of the superproject.
+    ///
+    /// Only the `SUBMODULE_STATUS_IN_*` flags are returned. This is cheaper
+    /// than `status` as the working directory of the submodule is not
+    /// inspected.
+    pub fn location(&self) -> Result<SubmoduleStatus, Error> {
+        let mut ret = 0;
+        unsafe { try_call!(raw::git_submodule_location(&mut ret, self.raw)); }
+        Ok(SubmoduleStatus::from_bits_truncate(ret as u32))
+    }
+
+    /// Get the rule deciding which changes to the submodule are ignored by
+    /// `status`.
+    pub fn ignore_rule(&self) -> SubmoduleIgnore {
+        SubmoduleIgnore::from_raw(unsafe {
+            raw::git_submodule_ignore(self.raw)
+        })
+    }
+
+    /// Get the rule used by `update` for the submodule.
+    pub fn update_strategy(&self) -> SubmoduleUpdate {
+        SubmoduleUpdate::from_raw(unsafe {
+            raw::git_submodule_update(self.raw)
+        })
+    }
+
+    /// Get whether the submodules of this submodule are fetched along with
+    /// it.
+    pub fn fetch_recurse_submodules(&self) -> SubmoduleRecurse {
+        SubmoduleRecurse::from_raw(unsafe {
+            raw::git_submodule_fetch_recurse_submodules(self.raw)
+        })
+    }
+
+    /// Set whether the submodules of this submodule are fetched along with
+    /// it, writing the setting to `.gitmodules`.
+    pub fn set_fetch_recurse_submodules(&mut self, recurse: SubmoduleRecurse)
+                                        -> Result<(), Error> {
+        unsafe {
+            call!(raw::git_submodule_set_fetch_recurse_submodules(self.raw,
+                                                                  recurse));
+        }
+        self.save()
+    }
+
+    /// Create the repository of a submodule which has not been checked out,
+    /// without fetching anything.
+    ///
+    /// The `origin` remote of the new repository points at the url of the
+    /// submodule. If `use_gitlink` is `true` the repository is created in
+    /// `.git/modules` of the superproject and a `.git` file linking to it is
+    /// written in the working directory, as git does, otherwise it is created
+    /// in the `.git` directory of the submodule.
+    pub fn repo_init(&mut self, use_gitlink: bool)
+                     -> Result<Repository, Error> {
+        let workdir = match self.owner_workdir() {
+            Some(dir) => dir.join(self.path()),
+            None => return Err(Error::from_str("cannot create the repository \
+                                                of a submodule in a bare \
+                                                repository")),
+        };
+        let url = match self.url() {
+            Some(url) => url,
+            None => return Err(Error::from_str("submodule url is not valid \
+                                                utf-8")),
+        };
+        let mut opts = RepositoryInitOptions::new();
+        opts.mkpath(true).origin_url(url);
+        if !use_gitlink {
+            return Repository::init_opts(&workdir, &opts)
+        }
+
+        let gitdir = self.owner_path().join("modules")
+                         .join(self.name_bytes());
+        opts.workdir_path(&workdir);
+        let repo = try!(Repository::init_opts(&gitdir, &opts));
+        // Both paths are absolute, so each is relative to the other.
+        let link = gitdir.path_relative_from(&workdir).unwrap();
+        let worktree = workdir.path_relative_from(&gitdir).unwrap();
+        try!(File::create(&workdir.join(".git")).write(
+            format!("gitdir: {}\n", link.display()).as_bytes()
+        ).map_err(::io_error));
+        try!(try!(repo.config()).set_str("core.worktree",
+                                         worktree.display().to_string()
+                                                 .as_slice()));
+        Ok(repo)
+    }
+
+    /// Set the url of the submodule, writing it to `.gitmodules`.
+    ///
+    /// Call `init(true)` to copy the new url to `.git/config` and `sync()` to
+    /// update the remote of the checked out submodule.
+    pub fn set_url(&mut self, url: &str) -> Result<(), Error> {
+        unsafe {
+            try_call!(raw::git_submodule_set_url(self.raw, url.to_c_str()));
+        }
+        self.save()
+    }
+
+    /// Set the branch tracked by the submodule, writing it to `.gitmodules`.
+    ///
+    /// Passing `None` removes the branch.
+    pub fn set_branch(&mut self, branch: Option<&str>) -> Result<(), Error> {
+        let workdir = match self.owner_workdir() {
+            Some(dir) => dir,
+            None => return Err(Error::from_str("cannot set the branch of a \
+                                                submodule in a bare \
+                                                repository")),
+        };
+        let key = format!("submodule.{}.branch",
+                          String::from_utf8_lossy(self.name_bytes()));
+        {
+            let mut config = try!(Config::open(&workdir.join(".gitmodules")));
+            match branch {
+                Some(branch) => try!(config.set_str(key.as_slice(), branch)),
+                None => match config.remove(key.as_slice()) {
+                    Ok(()) => {}
+                    Err(ref e) if e.code() == ::ErrorCode::NotFound => {}
+                    Err(e) => return Err(e),
+                },
+            }
+        }
+        self.reload(true)
+    }
+
+    /// Set the rule used by `update` for the submodule, writing it to
+    /// `.gitmodules`.
+    pub fn set_update(&mut self, update: SubmoduleUpdate)
+                      -> Result<(), Error> {
+        unsafe { call!(raw::git_submodule_set_update(self.raw, update)); }
+        self.save()
+    }
+
+    /// Set which changes to the submodule are ignored by `status`, writing
+    /// the rule to `.gitmodules`.
+    pub fn set_ignore(&mut self, ignore: SubmoduleIgnore)
+                      -> Result<(), Error> {
+        unsafe { call!(raw::git_submodule_set_ignore(self.raw, ignore)); }
+        self.save()
+    }
+
+    /// Update the submodule to the commit recorded in the index of the
+    /// superproject, as `git submodule update` does.
+    ///
+    /// The submodule is cloned if it has not been checked out yet. Otherwise
+    /// its `origin` remote is fetched if the commit is missing, then the commit
+    /// is checked out and HEAD detached at it. Submodules whose update rule is
+    /// `none` are left alone, while the `rebase` and `merge` rules are not
+    /// supported and return an error.
+    ///
+    /// If `init` is `true`, a submodule which has not been initialized yet is
+    /// initialized first, otherwise an error is returned for it.
+    pub fn update(&mut self, init: bool,
+                  opts: Option<&mut SubmoduleUpdateOptions>)
+                  -> Result<(), Error> {
+        let mut default = SubmoduleUpdateOptions::new();
+        let opts = match opts {
+            Some(opts) => opts,
+            None => &mut default,
+        };
+        match self.update_strategy() {
+            SubmoduleUpdate::None => return Ok(()),
+            SubmoduleUpdate::Rebase | SubmoduleUpdate::Merge => {
+                return Err(Error::from_str("only the checkout update rule \
+                                            is supported"))
+            }
+            SubmoduleUpdate::Checkout | SubmoduleUpdate::Default => {}
+        }
+        let target = match self.index_id() {
+            Some(id) => id,
+            None => return Err(Error::from_str("the superproject records no \
+                                                commit for the submodule")),
+        };
+        if !try!(self.is_initialized()) {
+            if !init {
+                return Err(Error::from_str("submodule is not initialized"))
+            }
+            try!(self.init(false));
+        }
+
+        let workdir = match self.owner_workdir() {
+            Some(dir) => dir,
+            None => return Err(Error::from_str("cannot update a submodule in \
+                                                a bare repository")),
+        };
+        if !workdir.join(self.path()).join(".git").exists() {
+            return self.clone(Some(opts)).map(|_| ())
+        }
+        let repo = try!(self.open());
+        if repo.find_commit(target).is_err() {
+            if !opts.allow_fetch {
+                return Err(Error::from_str("the commit of the submodule is \
+                                            missing and fetching is \
+                                            disabled"))
+            }
+            try!(fetch(&repo, opts));
+        }
+        checkout(&repo, target, opts)
+    }
+
+    /// Clone a submodule which has not been checked out yet.
+    ///
+    /// The repository of the submodule is created with `repo_init`, using the
+    /// gitlink layout. Its `origin` is then fetched and the commit recorded in the
+    /// superproject checked out, or the tip of the branch of the submodule
+    /// (`master` by default) if none is recorded yet.
+    pub fn clone(&mut self, opts: Option<&mut SubmoduleUpdateOptions>)
+                 -> Result<Repository, Error> {
+        let mut default = SubmoduleUpdateOptions::new();
+        let opts = match opts {
+            Some(opts) => opts,
+            None => &mut default,
+        };
+        let repo = try!(self.repo_init(true));
+        try!(fetch(&repo, opts));
+        let target = match self.index_id().or(self.head_id()) {
+            Some(id) => id,
+            None => {
+                let branch = self.branch().unwrap_or("master");
+                let name = format!("refs/remotes/origin/{}", branch);
+                try!(repo.refname_to_id(name.as_slice()))
+            }
+        };
+        try!(checkout(&repo, target, opts));
+        Ok(repo)
+    }
+
+    // Whether `git submodule init` was run, copying the url of the submodule
+    // to the configuration of the superproject.
+    fn is_initialized(&self) -> Result<bool, Error> {
+        let mut raw = 0 as *mut raw::git_config;
+        let config = unsafe {
+            try_call!(raw::git_repository_config(&mut raw,
+                                                 raw::git_submodule_owner(
+                                                     self.raw)));
+            Config::from_raw(raw)
+        };
+        let key = format!("submodule.{}.url",
+                          String::from_utf8_lossy(self.name_bytes()));
+        Ok(config.get_bytes(key.as_slice()).is_ok())
+    }
+
+    // The `.git` directory of the superproject.
+    fn owner_path(&self) -> Path {
+        unsafe {
+            let ptr = raw::git_repository_path(
+                raw::git_submodule_owner(self.raw));
+            Path::new(CString::new(ptr, false).as_bytes_no_nul())
+        }
+    }
+
+    // The working directory of the superproject.
+    fn owner_workdir(&self) -> Option<Path> {
+        unsafe {
+            let ptr = raw::git_repository_workdir(
+                raw::git_submodule_owner(self.raw));
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Path::new(CString::new(ptr, false).as_bytes_no_nul()))
+            }
+        }
+    }
+}
+
+impl<'cb> SubmoduleUpdateOptions<'cb> {
+    /// Creates a default set of update options.
+    ///
+    /// By default missing commits are fetched and the checkout is safe.
+    pub fn new() -> SubmoduleUpdateOptions<'cb> {
+        SubmoduleUpdateOptions {
+            checkout: None,
+            callbacks: None,
+            allow_fetch: true,
+        }
+    }
+
+    /// Configure the checkout of the submodule by consuming a checkout
+    /// builder.
+    pub fn checkout(&mut self, checkout: CheckoutBuilder<'cb>)
+                    -> &mut SubmoduleUpdateOptions<'cb> {
+        self.checkout = Some(checkout);
+        self
+    }
+
+    /// Set the callbacks used while fetching the submodule, to provide
+    /// credentials or monitor progress.
+    pub fn remote_callbacks(&mut self, callbacks: RemoteCallbacks<'cb>)
+                            -> &mut SubmoduleUpdateOptions<'cb> {
+        self.callbacks = Some(callbacks);
+        self
+    }
+
+    /// Allow fetching the submodule when the commit to check out is missing.
+    ///
+    /// This is `true` by default. A submodule which has not been cloned yet
+    /// is always fetched.
+    pub fn allow_fetch(&mut self, allow: bool)
+                       -> &mut SubmoduleUpdateOptions<'cb> {
+        self.allow_fetch = allow;
+        self
+    }
+}
+
+// Fetch the `origin` remote of the repository of a submodule.
+fn fetch(repo: &Repository, opts: &mut SubmoduleUpdateOptions)
+         -> Result<(), Error> {
+    let remote = try!(repo.find_remote("origin"));
+    unsafe {
+        match opts.callbacks {
+            Some(ref mut cbs) => {
+                let raw = cbs.raw();
+                try_call!(raw::git_remote_set_callbacks(remote.raw(), &raw));
+            }
+            None => {}
+        }
+        try_call_panic!(raw::git_remote_fetch(remote.raw(),
+                                              0 as *const raw::git_strarray,
+                                              0 as *const raw::git_signature,
+                                              0 as *const c_char));
+    }
+    Ok(())
+}
+
+// Check out `id` in the repository of a submodule and detach HEAD at it.
+fn checkout(repo: &Repository, id: Oid, opts: &mut SubmoduleUpdateOptions)
+            -> Result<(), Error> {
+    let commit = try!(repo.find_object(id, None));
+    match opts.checkout {
+        Some(ref mut c) => try!(repo.checkout_tree(&commit, Some(c))),
+        None => {
+            let mut c = CheckoutBuilder::new();
+            try!(repo.checkout_tree(&commit, Some(&mut c)))
+        }
+    }
+    unsafe {
+        try_call!(raw::git_repository_set_head_detached(repo.raw(), id.raw(),
+                                              0 as *const raw::git_signature,
+                                              0 as *const c_char));
+    }
+    Ok(())
 }
 
 #[unsafe_destructor]
@@ -227,8 +585,11 @@ impl<'repo> Drop for Submodule<'repo> {
 
 #[cfg(test)]
 mod tests {
-    use std::io::TempDir;
-    use Repository;
+    use std::io::{File, TempDir};
+    use std::io::fs::PathExtensions;
+    use {Repository, Config, SubmoduleUpdate, SubmoduleIgnore};
+    use {SubmoduleRecurse, SUBMODULE_STATUS_IN_HEAD, SUBMODULE_STATUS_IN_INDEX};
+    use {SUBMODULE_STATUS_IN_CONFIG, SUBMODULE_STATUS_IN_WD};
 
     #[test]
     fn smoke() {
@@ -256,4 +617,107 @@ mod tests {
         assert!(s.path() == Path::new("bar"));
         s.reload(true).unwrap();
     }
+
+    #[test]
+    fn update() {
+        let (td, _repo, _sub_td, sub) = ::test::repo_with_submodule();
+        let expected = sub.head().unwrap().target().unwrap();
+        let td2 = TempDir::new("test").unwrap();
+        let repo = Repository::clone(td.path().as_str().unwrap(),
+                                     td2.path()).unwrap();
+        let path = td2.path().join("sub");
+        assert!(!path.join("file").exists());
+
+        let mut s = repo.find_submodule("sub").unwrap();
+        assert!(s.update(false, None).is_err());
+        s.update(true, None).unwrap();
+        assert!(path.join("file").exists());
+        assert!(path.join(".git").is_file());
+        assert_eq!(File::open(&path.join(".git")).read_to_string().unwrap()
+                        .as_slice(), "gitdir: ../.git/modules/sub\n");
+        let r = s.open().unwrap();
+        assert_eq!(r.config().unwrap().get_str("core.worktree").unwrap(),
+                   "../../../sub");
+        assert_eq!(r.head().unwrap().target(), Some(expected));
+
+        // Already up to date.
+        s.update(false, None).unwrap();
+
+        let mut paths = Vec::new();
+        repo.submodule_foreach(true, |s, path| {
+            assert_eq!(s.name(), Some("sub"));
+            paths.push(path.clone());
+            true
+        }).unwrap();
+        assert_eq!(paths, vec![Path::new("sub")]);
+    }
+
+    #[test]
+    fn setters() {
+        let (td, repo, _sub_td, _sub) = ::test::repo_with_submodule();
+        let gitmodules = td.path().join(".gitmodules");
+        let mut s = repo.find_submodule("sub").unwrap();
+        assert_eq!(s.update_strategy(), SubmoduleUpdate::Default);
+        assert_eq!(s.ignore_rule(), SubmoduleIgnore::Default);
+        assert_eq!(s.fetch_recurse_submodules(), SubmoduleRecurse::No);
+
+        s.set_url("/path/to/elsewhere").unwrap();
+        s.set_branch(Some("dev")).unwrap();
+        s.set_update(SubmoduleUpdate::None).unwrap();
+        s.set_ignore(SubmoduleIgnore::All).unwrap();
+        s.set_fetch_recurse_submodules(SubmoduleRecurse::OnDemand).unwrap();
+        assert_eq!(s.update_strategy(), SubmoduleUpdate::None);
+        assert_eq!(s.ignore_rule(), SubmoduleIgnore::All);
+        assert_eq!(s.fetch_recurse_submodules(), SubmoduleRecurse::OnDemand);
+        assert_eq!(s.url(), Some("/path/to/elsewhere"));
+        assert_eq!(s.branch(), Some("dev"));
+        {
+            let config = Config::open(&gitmodules).unwrap();
+            assert_eq!(config.get_str("submodule.sub.url").unwrap(),
+                       "/path/to/elsewhere");
+            assert_eq!(config.get_str("submodule.sub.branch").unwrap(), "dev");
+            assert_eq!(config.get_str("submodule.sub.update").unwrap(),
+                       "none");
+            assert_eq!(config.get_str("submodule.sub.ignore").unwrap(), "all");
+            assert_eq!(config.get_str("submodule.sub.fetchRecurseSubmodules")
+                             .unwrap(), "on-demand");
+        }
+
+        // Submodules whose update rule is `none` are not checked out.
+        let td2 = TempDir::new("test").unwrap();
+        let repo2 = Repository::clone(td.path().as_str().unwrap(),
+                                      td2.path()).unwrap();
+        let mut s2 = repo2.find_submodule("sub").unwrap();
+        s2.set_update(SubmoduleUpdate::None).unwrap();
+        s2.update(true, None).unwrap();
+        assert!(!td2.path().join("sub/file").exists());
+
+        s.set_branch(None).unwrap();
+        assert_eq!(s.branch(), None);
+        let contents = File::open(&gitmodules).read_to_string().unwrap();
+        assert!(!contents.as_slice().contains("branch"));
+    }
+
+    #[test]
+    fn location_and_repo_init() {
+        let (td, repo, sub_td, _sub) = ::test::repo_with_submodule();
+        let all = SUBMODULE_STATUS_IN_HEAD | SUBMODULE_STATUS_IN_INDEX |
+                  SUBMODULE_STATUS_IN_CONFIG | SUBMODULE_STATUS_IN_WD;
+        let s = repo.find_submodule("sub").unwrap();
+        assert_eq!(s.location().unwrap(), all);
+
+        let td2 = TempDir::new("test").unwrap();
+        let repo2 = Repository::clone(td.path().as_str().unwrap(),
+                                      td2.path()).unwrap();
+        let mut s2 = repo2.find_submodule("sub").unwrap();
+        assert!(s2.location().unwrap().contains(SUBMODULE_STATUS_IN_HEAD |
+                                                SUBMODULE_STATUS_IN_INDEX |
+                                                SUBMODULE_STATUS_IN_CONFIG));
+        let r = s2.repo_init(true).unwrap();
+        assert!(td2.path().join("sub/.git").is_file());
+        assert!(td2.path().join(".git/modules/sub").is_dir());
+        let origin = r.find_remote("origin").unwrap();
+        assert_eq!(origin.url(), sub_td.path().as_str());
+        assert!(r.is_empty().unwrap());
+    }
 }
@@ -1,7 +1,8 @@
+use std::cmp::Ordering;
 use std::kinds::marker;
 use std::str;
 
-use {raw, Error, Oid, Object, Signature, ObjectType};
+use {raw, sign, Buf, Error, Oid, Object, Signature, ObjectType, Verifier};
 
 /// A structure to represent a git [tag][1]
 ///
@@ -93,6 +94,22 @@ impl<'repo> Tag<'repo> {
         }
     }
 
+    /// Get the tagged object of a tag, peeled until an object of type `kind`
+    /// is found.
+    ///
+    /// This follows nested tags and, for example, resolves a tag of a commit
+    /// to its tree when `kind` is `ObjectType::Tree`. An error is returned if
+    /// the target cannot be peeled to `kind`.
+    pub fn target_peel(&self, kind: ObjectType)
+                       -> Result<Object<'repo>, Error> {
+        let target = try!(self.target());
+        let mut ret = 0 as *mut raw::git_object;
+        unsafe {
+            try_call!(raw::git_object_peel(&mut ret, &*target.raw(), kind));
+            Ok(Object::from_raw(ret))
+        }
+    }
+
     /// Get the OID of the tagged object of a tag
     pub fn target_id(&self) -> Oid {
         unsafe { Oid::from_raw(raw::git_tag_target_id(&*self.raw)) }
@@ -103,6 +120,32 @@ impl<'repo> Tag<'repo> {
         unsafe { ObjectType::from_raw(raw::git_tag_target_type(&*self.raw)) }
     }
 
+    /// Extract the signature of a signed tag.
+    ///
+    /// Returns the signature and the data which was signed, that is the tag
+    /// object without the signature appended to its message. An error is
+    /// returned if the tag is not signed.
+    pub fn extract_signature(&self) -> Result<(Buf, Buf), Error> {
+        let content = try!(unsafe {
+            sign::read_object(raw::git_tag_owner(&*self.raw), self.id())
+        });
+        match sign::tag_signature_start(content.as_slice()) {
+            Some(i) => {
+                let signature = try!(sign::buf(content.slice_from(i)));
+                let data = try!(sign::buf(content.slice_to(i)));
+                Ok((signature, data))
+            }
+            None => Err(Error::from_str("tag is not signed")),
+        }
+    }
+
+    /// Check the signature of a signed tag with `verifier`.
+    pub fn verify_signature(&self, verifier: &mut Verifier)
+                            -> Result<bool, Error> {
+        let (signature, data) = try!(self.extract_signature());
+        verifier.verify(data.get(), signature.get())
+    }
+
     /// Get access to the underlying raw pointer.
     pub fn raw(&self) -> *mut raw::git_tag { self.raw }
 }
@@ -115,8 +158,73 @@ impl<'repo> Drop for Tag<'repo> {
 }
 
 
+// Compare two tag names as versions, so that `v1.10.0` sorts after `v1.9.0`
+// and a pre-release such as `v2.0.0-rc.1` sorts before `v2.0.0`.
+pub fn version_cmp(a: &str, b: &str) -> Ordering {
+    fn strip_v(s: &[u8]) -> &[u8] {
+        if s.len() > 1 && (s[0] == b'v' || s[0] == b'V') && is_digit(s[1]) {
+            s.slice_from(1)
+        } else {
+            s
+        }
+    }
+    fn split_pre(s: &[u8]) -> (&[u8], Option<&[u8]>) {
+        match s.position_elem(&b'-') {
+            Some(i) => (s.slice_to(i), Some(s.slice_from(i + 1))),
+            None => (s, None),
+        }
+    }
+    let (a, a_pre) = split_pre(strip_v(a.as_bytes()));
+    let (b, b_pre) = split_pre(strip_v(b.as_bytes()));
+    match natural_cmp(a, b) {
+        Ordering::Equal => {}
+        ord => return ord,
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(..)) => Ordering::Greater,
+        (Some(..), None) => Ordering::Less,
+        (Some(a), Some(b)) => natural_cmp(a, b),
+    }
+}
+
+// Compare two strings bytewise, except that runs of digits are compared by
+// their numeric value.
+fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    fn number(s: &[u8], start: uint) -> (&[u8], uint) {
+        let mut end = start;
+        while end < s.len() && is_digit(s[end]) { end += 1 }
+        let mut first = start;
+        while first < end - 1 && s[first] == b'0' { first += 1 }
+        (s.slice(first, end), end)
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if is_digit(a[i]) && is_digit(b[j]) {
+            let (x, next_i) = number(a, i);
+            let (y, next_j) = number(b, j);
+            let ord = match x.len().cmp(&y.len()) {
+                Ordering::Equal => x.cmp(y),
+                ord => ord,
+            };
+            if ord != Ordering::Equal { return ord }
+            i = next_i;
+            j = next_j;
+        } else {
+            if a[i] != b[j] { return a[i].cmp(&b[j]) }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+fn is_digit(b: u8) -> bool { b >= b'0' && b <= b'9' }
+
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering;
+
     #[test]
     fn smoke() {
         let (_td, repo) = ::test::repo_init();
@@ -145,4 +253,93 @@ mod tests {
 
         repo.tag_delete("foo").unwrap();
     }
+
+    #[test]
+    fn lightweight_and_foreach() {
+        let (_td, repo) = ::test::repo_init();
+        let id = repo.head().unwrap().target().unwrap();
+        let obj = repo.find_object(id, None).unwrap();
+        let sig = repo.signature().unwrap();
+
+        assert_eq!(repo.tag_lightweight("light", &obj, false).unwrap(), id);
+        assert!(repo.tag_lightweight("light", &obj, false).is_err());
+        let annotated = repo.tag("annotated", &obj, &sig, "msg", false)
+                            .unwrap();
+        let detached = repo.tag_annotation_create("detached", &obj, &sig,
+                                                  "msg").unwrap();
+        assert!(repo.find_reference("refs/tags/detached").is_err());
+        let tag = repo.find_tag(detached).unwrap();
+        assert_eq!(tag.name(), Some("detached"));
+        assert_eq!(tag.target_peel(::ObjectType::Commit).unwrap().id(), id);
+        let tree = repo.find_commit(id).unwrap().tree_id();
+        assert_eq!(tag.target_peel(::ObjectType::Tree).unwrap().id(), tree);
+        assert!(tag.target_peel(::ObjectType::Blob).is_err());
+
+        let mut tags = Vec::new();
+        repo.tag_foreach(|id, name| {
+            tags.push((String::from_utf8(name.to_vec()).unwrap(), id));
+            true
+        }).unwrap();
+        tags.sort();
+        assert_eq!(tags, vec![("refs/tags/annotated".to_string(), annotated),
+                              ("refs/tags/light".to_string(), id)]);
+
+        let mut n = 0u;
+        repo.tag_foreach(|_, _| { n += 1; false }).unwrap();
+        assert_eq!(n, 1);
+    }
+
+    #[test]
+    fn version_sort() {
+        let (_td, repo) = ::test::repo_init();
+        let id = repo.head().unwrap().target().unwrap();
+        let obj = repo.find_object(id, None).unwrap();
+        for name in ["v1.10.0", "v1.2.0", "v1.2.0-rc.2", "v1.2.0-rc.10",
+                     "v1.9.3", "v2.0.0", "other"].iter() {
+            repo.tag_lightweight(*name, &obj, false).unwrap();
+        }
+        assert_eq!(repo.tag_names_sorted(Some("v*")).unwrap(),
+                   vec!["v1.2.0-rc.2", "v1.2.0-rc.10", "v1.2.0", "v1.9.3",
+                        "v1.10.0", "v2.0.0"].iter().map(|s| s.to_string())
+                                            .collect::<Vec<_>>());
+        assert_eq!(super::version_cmp("v01.0", "1.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn signed() {
+        let (_td, repo) = ::test::repo_init();
+        let id = repo.head().unwrap().target().unwrap();
+        let obj = repo.find_object(id, None).unwrap();
+        let sig = repo.signature().unwrap();
+
+        let tag_id = repo.tag("plain", &obj, &sig, "msg", false).unwrap();
+        let tag = repo.find_tag(tag_id).unwrap();
+        assert!(tag.extract_signature().is_err());
+        assert!(tag.verify_signature(&mut ::test::TestSigner).is_err());
+
+        let buf = repo.tag_create_buffer("signed", &obj, &sig, "msg").unwrap();
+        assert!(buf.as_str().unwrap().ends_with("\n\nmsg"));
+        assert!(repo.tag_signed(buf.as_str().unwrap(), "sig", false).is_err());
+
+        let buf = repo.tag_create_buffer("signed", &obj, &sig,
+                                         "msg\n").unwrap();
+        let tag_id = repo.tag_with_signer(&mut ::test::TestSigner, "signed",
+                                          &obj, &sig, "msg\n",
+                                          false).unwrap();
+        let tag = repo.find_tag(tag_id).unwrap();
+        assert_eq!(tag.name(), Some("signed"));
+        assert_eq!(repo.refname_to_id("refs/tags/signed").unwrap(), tag_id);
+        let (signature, data) = tag.extract_signature().unwrap();
+        assert_eq!(data.get(), buf.get());
+        assert!(signature.as_str().unwrap()
+                         .starts_with("-----BEGIN PGP SIGNATURE-----"));
+        assert!(tag.verify_signature(&mut ::test::TestSigner).unwrap());
+
+        let forged = repo.tag_signed(buf.as_str().unwrap(),
+                                     "-----BEGIN PGP SIGNATURE-----\nbad\n\
+                                      -----END PGP SIGNATURE-----\n",
+                                     true).unwrap();
+        let tag = repo.find_tag(forged).unwrap();
+        assert!(!tag.verify_signature(&mut ::test::TestSigner).unwrap());
+    }
 }
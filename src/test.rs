@@ -1,5 +1,5 @@
-use std::io::TempDir;
-use Repository;
+use std::io::{fs, File, TempDir, USER_DIR};
+use {Repository, Error, Signer, Verifier, ResetType, Signature, Oid};
 
 pub fn repo_init() -> (TempDir, Repository) {
     let td = TempDir::new("test").unwrap();
@@ -18,3 +18,102 @@ pub fn repo_init() -> (TempDir, Repository) {
     }
     (td, repo)
 }
+
+// Write each of `files`, given as `(path, contents)`, to the working directory
+// of `repo` and commit them on top of HEAD. The message lists the paths.
+pub fn commit_files(repo: &Repository, files: &[(&str, &str)]) -> Oid {
+    let sig = repo.signature().unwrap();
+    commit_files_as(repo, files, &sig)
+}
+
+// Same as `commit_files`, with `sig` as the author and committer.
+pub fn commit_files_as(repo: &Repository, files: &[(&str, &str)],
+                       sig: &Signature) -> Oid {
+    let root = repo.workdir().unwrap();
+    let mut index = repo.index().unwrap();
+    for &(file, contents) in files.iter() {
+        let path = root.join(file);
+        fs::mkdir_recursive(&path.dir_path(), USER_DIR).unwrap();
+        File::create(&path).write_str(contents).unwrap();
+        index.add_path(&Path::new(file)).unwrap();
+    }
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let msg = files.iter().map(|&(file, _)| file).collect::<Vec<_>>()
+                   .connect(" ");
+    let head = repo.head().unwrap().target().unwrap();
+    let parent = repo.find_commit(head).unwrap();
+    repo.commit(Some("HEAD"), sig, sig, msg.as_slice(), &tree,
+                &[&parent]).unwrap()
+}
+
+// Create a repository whose second commit adds `file`, and a superproject
+// committing it as the submodule `sub`. The submodule repository is returned
+// along with the superproject.
+pub fn repo_with_submodule() -> (TempDir, Repository, TempDir, Repository) {
+    repo_with_submodules(&["sub"])
+}
+
+// Same as `repo_with_submodule`, adding the repository as a submodule at each
+// of `paths`.
+pub fn repo_with_submodules(paths: &[&str])
+                            -> (TempDir, Repository, TempDir, Repository) {
+    let (sub_td, sub) = repo_init();
+    {
+        File::create(&sub_td.path().join("file")).write_str("sub").unwrap();
+        let mut index = sub.index().unwrap();
+        index.add_path(&Path::new("file")).unwrap();
+        index.write().unwrap();
+        let tree = sub.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = sub.signature().unwrap();
+        let head = sub.head().unwrap().target().unwrap();
+        let parent = sub.find_commit(head).unwrap();
+        sub.commit(Some("HEAD"), &sig, &sig, "file", &tree,
+                   &[&parent]).unwrap();
+    }
+
+    let (td, repo) = repo_init();
+    {
+        let url = sub_td.path().as_str().unwrap();
+        for path in paths.iter() {
+            let mut s = repo.submodule(url, &Path::new(*path), true).unwrap();
+            let r = s.open().unwrap();
+            r.find_remote("origin").unwrap().fetch(&[], None, None).unwrap();
+            let id = r.refname_to_id("refs/remotes/origin/master").unwrap();
+            let obj = r.find_object(id, None).unwrap();
+            r.reset(&obj, ResetType::Hard, None, None).unwrap();
+            s.add_finalize().unwrap();
+        }
+
+        let mut index = repo.index().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = repo.signature().unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+        let parent = repo.find_commit(head).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "add submodules", &tree,
+                    &[&parent]).unwrap();
+    }
+    (td, repo, sub_td, sub)
+}
+
+// A toy signer producing a checksum wrapped in PGP armor.
+pub struct TestSigner;
+
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(7u32, |h, b| h * 31 + *b as u32)
+}
+
+impl Signer for TestSigner {
+    fn sign(&mut self, data: &[u8]) -> Result<String, Error> {
+        Ok(format!("-----BEGIN PGP SIGNATURE-----\n{:08x}\n\
+                    -----END PGP SIGNATURE-----\n", checksum(data)))
+    }
+}
+
+impl Verifier for TestSigner {
+    fn verify(&mut self, data: &[u8], signature: &[u8])
+              -> Result<bool, Error> {
+        let expected = try!(self.sign(data));
+        Ok(expected.as_bytes() == signature)
+    }
+}